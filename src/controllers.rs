@@ -1,5 +1,15 @@
+pub mod admin;
+pub mod analytics;
+pub mod catalog;
+pub mod completions;
 pub mod identity;
 pub mod customer;
 pub mod email;
+pub mod health;
 pub mod llm;
-pub mod org;
\ No newline at end of file
+pub mod org;
+pub mod org_config;
+pub mod request_logs;
+pub mod router_templates;
+pub mod usage;
+pub mod webhooks;
\ No newline at end of file
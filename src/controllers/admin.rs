@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    inference::reload::{reload_classification_model, reload_embedding_model},
+    server::sentence_embeddings_model_type,
+    types::{customer::GenericResponse, incoming_requests::ReloadModel, state::AppState},
+    utilities::helpers::{bad_request, ok, payload_analyzer, unauthorized},
+};
+
+use super::identity::{get_user_session_from_req, SessionData, SessionScopes};
+
+pub async fn extract_platform_admin(
+    headers: &HeaderMap,
+    state: &Arc<AppState>,
+) -> Result<SessionData, (StatusCode, Json<GenericResponse>)> {
+    let session_data = get_user_session_from_req(&headers, &state.redis_connection).await?;
+    if !session_data.scopes.contains(&SessionScopes::TotalAccess) {
+        return Err(unauthorized("not.enough.scopes", None));
+    }
+
+    if !state.platform_admins.contains(&session_data.customer_id) {
+        return Err(unauthorized("not.platform.admin", None));
+    }
+
+    Ok(session_data)
+}
+
+pub async fn reload_model(
+    headers: HeaderMap,
+    payload_result: Result<Json<ReloadModel>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    extract_platform_admin(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    if payload.name.len() < 1 || payload.name.len() > 256 {
+        return Err(bad_request("model.name.length.invalid", None));
+    }
+
+    let path = match &payload.path {
+        Some(path) if !path.is_empty() => Some(path.clone()),
+        _ => None,
+    };
+
+    match payload.kind.as_str() {
+        "prompt_classification" => {
+            let url = payload.url.clone().unwrap_or_default();
+            if url.is_empty() && path.is_none() {
+                return Err(bad_request("model.source.required", None));
+            }
+
+            let slot = Arc::clone(&state.llm_resources.prompt_classification_model.slot);
+            if !slot.begin_reload(&payload.name) {
+                return Err(bad_request("model.reload.in.progress", None));
+            }
+
+            tokio::spawn(reload_classification_model(Arc::clone(&slot), payload.name.clone(), url, path));
+
+            Ok(ok("model.reload.started", Some(json!(slot.info()))))
+        },
+        "sentence_embeddings" => {
+            if path.is_none() && sentence_embeddings_model_type(&payload.name).is_none() {
+                return Err(bad_request("model.name.invalid", None));
+            }
+
+            let model_id = payload.id.clone().unwrap_or_default();
+            let embedding_model = match state.llm_resources.embedding_models.get(&model_id) {
+                Some(model) => model,
+                None => return Err(bad_request("embedding.model.not.found", None)),
+            };
+
+            let slot = Arc::clone(&embedding_model.slot);
+            if !slot.begin_reload(&payload.name) {
+                return Err(bad_request("model.reload.in.progress", None));
+            }
+
+            tokio::spawn(reload_embedding_model(Arc::clone(&slot), payload.name.clone(), path));
+
+            Ok(ok("model.reload.started", Some(json!(slot.info()))))
+        },
+        _ => Err(bad_request("model.kind.invalid", None)),
+    }
+}
@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{Duration, Utc};
+use log::error;
+use tokio::task;
+
+use crate::{
+    storage::{
+        diesel_postgres::{
+            analytics::{find_router_analytics, RouterAnalyticsQuery, SCORE_BUCKETS},
+            usage::UsageGranularity,
+        },
+        mongo::{build_organizations_filter, find_organization},
+    },
+    types::{
+        customer::GenericResponse,
+        incoming_requests::RouterAnalyticsQueryParams,
+        organization::MemberRole,
+        state::AppState,
+        usage::{LatencyPercentiles, RouterAnalytics, ScoreHistogramBucket},
+    },
+    utilities::helpers::{bad_request, internal_server_error, ok, unauthorized},
+};
+
+use super::{
+    org::extract_access_data,
+    usage::{parse_date, DEFAULT_RANGE_DAYS, MAX_DAILY_RANGE_DAYS, MAX_HOURLY_RANGE_DAYS},
+};
+
+fn rate(part: i64, total: i64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => part as f64 / total as f64,
+    }
+}
+
+// strategy hit rates, category and model distribution, error and abstention
+// rates, score histogram and p50/p95 routing latency of one router
+pub async fn get_router_analytics(
+    headers: HeaderMap,
+    Query(params): Query<RouterAnalyticsQueryParams>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+
+    let router_id = match params.router_id {
+        Some(router_id) if !router_id.is_empty() => router_id,
+        _ => return Err(bad_request("router.id.required", None)),
+    };
+
+    let to = match params.to.as_deref() {
+        Some(to) => match parse_date(to, true) {
+            Some(to) => to,
+            None => return Err(bad_request("analytics.to.invalid", None)),
+        },
+        None => Utc::now(),
+    };
+
+    let from = match params.from.as_deref() {
+        Some(from) => match parse_date(from, false) {
+            Some(from) => from,
+            None => return Err(bad_request("analytics.from.invalid", None)),
+        },
+        None => to - Duration::days(DEFAULT_RANGE_DAYS),
+    };
+
+    let granularity = match UsageGranularity::parse(params.granularity.as_deref().unwrap_or("day")) {
+        Some(granularity) => granularity,
+        None => return Err(bad_request("analytics.granularity.invalid", None)),
+    };
+
+    let max_range = match granularity {
+        UsageGranularity::Day => Duration::days(MAX_DAILY_RANGE_DAYS),
+        UsageGranularity::Hour => Duration::days(MAX_HOURLY_RANGE_DAYS),
+    };
+
+    if from >= to || to - from > max_range {
+        return Err(bad_request("analytics.range.invalid", None));
+    }
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member || member.role == MemberRole::Viewer)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    if !org.routers.iter().any(|router| router.id == router_id) {
+        return Err(bad_request("router.not.found", None));
+    }
+
+    let pool = match &state.postgres_conn {
+        Some(pool) => pool.clone(),
+        None => return Err(bad_request("usage.metering.disabled", None)),
+    };
+
+    let query = RouterAnalyticsQuery {
+        org_id: org.id.clone(),
+        router_id: router_id.clone(),
+        from,
+        to,
+        granularity,
+    };
+
+    let rows = match task::spawn_blocking(move || find_router_analytics(&pool, &query)).await {
+        Ok(Ok(rows)) => rows,
+        Ok(Err(e)) => {
+            error!("failed to load analytics of router {}: {}", router_id, e);
+            return Err(internal_server_error("analytics.query.failed", None));
+        }
+        Err(_) => return Err(internal_server_error("analytics.query.failed", None)),
+    };
+
+    let mut analytics = RouterAnalytics {
+        router_id,
+        from: from.to_rfc3339(),
+        to: to.to_rfc3339(),
+        granularity: granularity.as_str().to_string(),
+        decisions: 0,
+        errors: 0,
+        abstentions: 0,
+        error_rate: 0.0,
+        abstention_rate: 0.0,
+        strategies: Default::default(),
+        categories: Default::default(),
+        models: Default::default(),
+        statuses: Default::default(),
+        score_histogram: Default::default(),
+        latency: vec![],
+    };
+
+    for row in &rows.decisions {
+        analytics.decisions += row.decisions;
+        match row.status.as_str() {
+            "ok" => (),
+            "no_match" => analytics.abstentions += row.decisions,
+            _ => analytics.errors += row.decisions,
+        }
+
+        *analytics.strategies.entry(row.strategy.clone()).or_insert(0) += row.decisions;
+        *analytics.statuses.entry(row.status.clone()).or_insert(0) += row.decisions;
+        if !row.category.is_empty() {
+            *analytics.categories.entry(row.category.clone()).or_insert(0) += row.decisions;
+        }
+        if !row.model_id.is_empty() {
+            *analytics.models.entry(row.model_id.clone()).or_insert(0) += row.decisions;
+        }
+    }
+
+    analytics.error_rate = rate(analytics.errors, analytics.decisions);
+    analytics.abstention_rate = rate(analytics.abstentions, analytics.decisions);
+
+    let width = 1.0 / SCORE_BUCKETS as f64;
+    for row in &rows.scores {
+        let histogram = analytics.score_histogram.entry(row.strategy.clone()).or_insert_with(|| {
+            (0..SCORE_BUCKETS)
+                .map(|index| ScoreHistogramBucket {
+                    min: index as f64 * width,
+                    max: (index + 1) as f64 * width,
+                    decisions: 0,
+                })
+                .collect()
+        });
+
+        // width_bucket puts scores under 0 in bucket 0 and scores of 1 or more past the last one
+        let index = (row.bucket - 1).clamp(0, SCORE_BUCKETS - 1) as usize;
+        histogram[index].decisions += row.decisions;
+    }
+
+    analytics.latency = rows.latency
+        .into_iter()
+        .map(|point| LatencyPercentiles {
+            period: point.period.to_rfc3339(),
+            decisions: point.decisions,
+            p50_latency_ms: point.p50_latency_ms,
+            p95_latency_ms: point.p95_latency_ms,
+        })
+        .collect();
+
+    return Ok(ok("ok", Some(serde_json::to_value(analytics).unwrap())));
+}
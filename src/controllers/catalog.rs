@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use log::error;
+use mongodb::{bson::doc, options::ReplaceOptions};
+use serde_json::json;
+
+use crate::{
+    storage::mongo::{get_model_catalog_collection, get_organizations_collection},
+    types::{catalog::CatalogModel, customer::GenericResponse, incoming_requests::RemoveCatalogModel, state::AppState},
+    utilities::{
+        catalog::load_catalog_overrides,
+        helpers::{bad_request, conflict, internal_server_error, not_found, ok, payload_analyzer},
+    },
+};
+
+use super::admin::extract_platform_admin;
+
+fn validate_catalog_model(model: &CatalogModel) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if model.id.len() < 1 || model.id.len() > 256 {
+        return Err(bad_request("model.id.length.invalid", None));
+    }
+
+    if model.api_model_id.len() > 256 {
+        return Err(bad_request("model.api_model_id.length.invalid", None));
+    }
+
+    if model.context_window == 0 {
+        return Err(bad_request("model.context_window.invalid", None));
+    }
+
+    if model.knowledge_cutoff.len() > 64 {
+        return Err(bad_request("model.knowledge_cutoff.length.invalid", None));
+    }
+
+    for cost in [model.input_cost_per_million_tokens, model.output_cost_per_million_tokens] {
+        if !cost.is_finite() || cost < 0.0 {
+            return Err(bad_request("model.cost.invalid", None));
+        }
+    }
+
+    if model.modalities.is_empty() {
+        return Err(bad_request("model.modalities.required", None));
+    }
+
+    Ok(())
+}
+
+// the merged catalog including disabled models, and which entries come from mongo
+pub async fn get_catalog_admin(
+    headers: HeaderMap,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    extract_platform_admin(&headers, &state).await?;
+
+    return Ok(ok("ok", Some(json!({
+        "models": state.model_catalog.all(),
+        "overrides": state.model_catalog.overrides(),
+    }))));
+}
+
+// overrides a bundled model with the same id or adds a new one
+pub async fn upsert_catalog_model(
+    headers: HeaderMap,
+    payload_result: Result<Json<CatalogModel>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    extract_platform_admin(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    validate_catalog_model(&payload)?;
+
+    let collection = get_model_catalog_collection(&state.mongo_db).await;
+    let options = ReplaceOptions::builder().upsert(true).build();
+    if let Err(e) = collection.replace_one(doc! {"id": &payload.id}, &payload.0, options).await {
+        error!("error saving catalog model {}: {}", payload.id, e);
+        return Err(internal_server_error("database.error", None));
+    }
+
+    if let Err(e) = load_catalog_overrides(&state.mongo_db, &state.model_catalog).await {
+        error!("error reloading model catalog: {}", e);
+    }
+
+    return Ok(ok("ok", Some(serde_json::to_value(payload.0).unwrap())));
+}
+
+// bundled models go back to their shipped entry, models only known from mongo
+// are gone and can't be removed while an org still uses them
+pub async fn remove_catalog_model(
+    headers: HeaderMap,
+    payload_result: Result<Json<RemoveCatalogModel>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    extract_platform_admin(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    if !state.model_catalog.is_bundled(&payload.id) {
+        let organizations = get_organizations_collection(&state.mongo_db).await;
+        let filter = doc! {"models.id": &payload.id, "deleted": false};
+        match organizations.count_documents(filter, None).await {
+            Ok(0) => {},
+            Ok(count) => return Err(conflict("catalog.model.in.use", Some(json!({"organizations": count})))),
+            Err(e) => {
+                error!("error counting organizations using {}: {}", payload.id, e);
+                return Err(internal_server_error("database.error", None));
+            },
+        }
+    }
+
+    let collection = get_model_catalog_collection(&state.mongo_db).await;
+    let deleted = match collection.delete_one(doc! {"id": &payload.id}, None).await {
+        Ok(result) => result.deleted_count,
+        Err(e) => {
+            error!("error removing catalog model {}: {}", payload.id, e);
+            return Err(internal_server_error("database.error", None));
+        },
+    };
+
+    if deleted == 0 {
+        return Err(not_found("catalog.override.not.found", None));
+    }
+
+    if let Err(e) = load_catalog_overrides(&state.mongo_db, &state.model_catalog).await {
+        error!("error reloading model catalog: {}", e);
+    }
+
+    return Ok(ok("ok", Some(json!({
+        "bundled": state.model_catalog.is_bundled(&payload.id),
+    }))));
+}
@@ -0,0 +1,1332 @@
+use std::{convert::Infallible, sync::Arc, time::{Duration, Instant}};
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
+    Json,
+};
+use futures_util::{Stream, StreamExt};
+use log::error;
+use rand::Rng;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    providers::{self, circuit_breaker::Credential, message_text, ChatRequest, ContentPart, ProviderError, ProviderStream, ResponseFormat, StreamEvent, Tool, ToolCall, ToolChoice, Usage},
+    storage::{
+        diesel_postgres::usage::{UsageRecord, UsageWriter, COMPLETION_KIND},
+        mongo::{build_organizations_access_token_filter, find_organization},
+    },
+    types::{
+        customer::GenericResponse,
+        incoming_requests::{ChatCompletion, ChatMessage},
+        catalog::{ModelCatalog, RequiredCapabilities},
+        organization::{AccessTokenScopes, ModelObject, ModelOwner, Organization, ProviderCredential},
+        router::Router,
+        request_log::RequestLogEntry,
+        state::AppState,
+        webhook::WebhookEvent,
+    },
+    utilities::{helpers::token_fingerprint, quotas::{consume_monthly_routing_call, org_plan}, model_stats::record_model_call, request_log::log_request, vault::decrypt_secret, webhooks::emit_event},
+};
+
+use super::llm::route_prompt;
+
+// errors keep the OpenAI shape so existing SDKs surface them as API errors
+fn completion_error(status: StatusCode, message: &str) -> Response {
+    let error_type = match status {
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::NOT_FOUND | StatusCode::BAD_REQUEST => "invalid_request_error",
+        _ => "api_error",
+    };
+
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message,
+                "type": error_type,
+                "code": message,
+            }
+        })),
+    )
+        .into_response()
+}
+
+fn generic_error((status, Json(response)): (StatusCode, Json<GenericResponse>)) -> Response {
+    completion_error(status, &response.message)
+}
+
+// the router classifies the last user message
+fn extract_prompt(messages: &[ChatMessage]) -> String {
+    match messages.iter().rev().find(|message| message.role == "user") {
+        Some(message) => message_text(&message.content),
+        None => String::new(),
+    }
+}
+
+// read from the OpenAI fields that need them, models missing any are skipped
+fn required_capabilities(payload: &ChatCompletion) -> RequiredCapabilities {
+    let has_part = |kind: &str| payload.messages.iter().any(|message| match &message.content {
+        Value::Array(parts) => parts.iter().any(|part| part.get("type").and_then(|value| value.as_str()) == Some(kind)),
+        _ => false,
+    });
+
+    let has_option = |key: &str| match payload.options.get(key) {
+        Some(Value::Array(items)) => !items.is_empty(),
+        Some(Value::Null) | None => false,
+        Some(_) => true,
+    };
+
+    let response_format = payload.options
+        .get("response_format")
+        .and_then(|format| format.get("type"))
+        .and_then(|kind| kind.as_str());
+
+    RequiredCapabilities {
+        vision: has_part("image_url"),
+        tools: has_option("tools") || has_option("functions"),
+        json_mode: response_format.map_or(false, |kind| kind != "text"),
+        streaming: payload.stream.unwrap_or(false),
+        max_output_tokens: payload.options
+            .get("max_tokens")
+            .and_then(|max_tokens| max_tokens.as_u64())
+            .map(|max_tokens| max_tokens.min(u32::MAX as u64) as u32),
+    }
+}
+
+// text and image_url parts, other part types have no equivalent on every provider
+fn content_parts(content: &Value) -> Vec<ContentPart> {
+    match content {
+        Value::String(text) => vec![ContentPart::Text(text.clone())],
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part["type"].as_str() {
+                Some("text") => part["text"].as_str().map(|text| ContentPart::Text(text.to_string())),
+                Some("image_url") => {
+                    // older clients send the url directly
+                    let image_url = &part["image_url"];
+                    image_url["url"].as_str().or(image_url.as_str()).map(|url| ContentPart::Image {
+                        url: url.to_string(),
+                        detail: image_url["detail"].as_str().map(|detail| detail.to_string()),
+                    })
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn tool_calls(message: &ChatMessage) -> Vec<ToolCall> {
+    message.tool_calls
+        .iter()
+        .map(|tool_call| ToolCall {
+            id: tool_call["id"].as_str().unwrap_or_default().to_string(),
+            name: tool_call["function"]["name"].as_str().unwrap_or_default().to_string(),
+            arguments: match &tool_call["function"]["arguments"] {
+                Value::String(arguments) => arguments.clone(),
+                Value::Null => String::from("{}"),
+                arguments => arguments.to_string(),
+            },
+        })
+        .collect()
+}
+
+// tools or the deprecated functions
+fn tools(options: &Map<String, Value>) -> Vec<Tool> {
+    let functions: Vec<&Value> = match (options.get("tools"), options.get("functions")) {
+        (Some(Value::Array(tools)), _) => tools.iter().map(|tool| &tool["function"]).collect(),
+        (_, Some(Value::Array(functions))) => functions.iter().collect(),
+        _ => vec![],
+    };
+
+    functions
+        .into_iter()
+        .filter_map(|function| function["name"].as_str().map(|name| Tool {
+            name: name.to_string(),
+            description: function["description"].as_str().map(|description| description.to_string()),
+            parameters: match &function["parameters"] {
+                Value::Null => json!({"type": "object", "properties": {}}),
+                parameters => parameters.clone(),
+            },
+        }))
+        .collect()
+}
+
+// tool_choice or the deprecated function_call
+fn tool_choice(options: &Map<String, Value>) -> Option<ToolChoice> {
+    let choice = options.get("tool_choice").or(options.get("function_call"))?;
+    match choice {
+        Value::String(choice) => match choice.as_str() {
+            "auto" => Some(ToolChoice::Auto),
+            "none" => Some(ToolChoice::None),
+            "required" => Some(ToolChoice::Required),
+            _ => None,
+        },
+        choice => choice["function"]["name"]
+            .as_str()
+            .or(choice["name"].as_str())
+            .map(|name| ToolChoice::Tool(name.to_string())),
+    }
+}
+
+fn response_format(options: &Map<String, Value>) -> Option<ResponseFormat> {
+    let format = options.get("response_format")?;
+    match format["type"].as_str()? {
+        "text" => Some(ResponseFormat::Text),
+        "json_object" => Some(ResponseFormat::JsonObject),
+        "json_schema" => Some(ResponseFormat::JsonSchema {
+            name: format["json_schema"]["name"].as_str().unwrap_or("response").to_string(),
+            schema: format["json_schema"]["schema"].clone(),
+        }),
+        _ => None,
+    }
+}
+
+fn build_chat_request(payload: &ChatCompletion, model: String) -> ChatRequest {
+    let stop = match payload.options.get("stop") {
+        Some(Value::String(stop)) => vec![stop.clone()],
+        Some(Value::Array(stops)) => stops
+            .iter()
+            .filter_map(|stop| stop.as_str().map(|stop| stop.to_string()))
+            .collect(),
+        _ => vec![],
+    };
+
+    ChatRequest {
+        model,
+        messages: payload.messages
+            .iter()
+            .map(|message| providers::ChatMessage {
+                role: message.role.clone(),
+                content: content_parts(&message.content),
+                tool_calls: tool_calls(message),
+                tool_call_id: message.tool_call_id.clone(),
+                name: message.name.clone(),
+            })
+            .collect(),
+        temperature: payload.options.get("temperature").and_then(|temperature| temperature.as_f64()),
+        max_tokens: payload.options.get("max_tokens").and_then(|max_tokens| max_tokens.as_u64()),
+        stop,
+        top_p: payload.options.get("top_p").and_then(|top_p| top_p.as_f64()),
+        seed: payload.options.get("seed").and_then(|seed| seed.as_u64()),
+        frequency_penalty: payload.options.get("frequency_penalty").and_then(|penalty| penalty.as_f64()),
+        presence_penalty: payload.options.get("presence_penalty").and_then(|penalty| penalty.as_f64()),
+        user: payload.options.get("user").and_then(|user| user.as_str()).map(|user| user.to_string()),
+        tools: tools(&payload.options),
+        tool_choice: tool_choice(&payload.options),
+        response_format: response_format(&payload.options),
+        options: payload.options.clone(),
+    }
+}
+
+fn provider_error(provider: &str, e: ProviderError) -> Response {
+    match e {
+        ProviderError::Timeout => {
+            error!("chat completion to {} timed out", provider);
+            completion_error(StatusCode::GATEWAY_TIMEOUT, "provider.timeout")
+        }
+        ProviderError::Unreachable(message) => {
+            error!("error forwarding chat completion to {}: {}", provider, message);
+            completion_error(StatusCode::BAD_GATEWAY, "provider.unreachable")
+        }
+        ProviderError::InvalidResponse(message) => {
+            error!("invalid chat completion response from {}: {}", provider, message);
+            completion_error(StatusCode::BAD_GATEWAY, "provider.invalid.response")
+        }
+        ProviderError::InvalidRequest(message) => {
+            error!("chat completion can't be sent to {}: {}", provider, message);
+            completion_error(StatusCode::BAD_REQUEST, "provider.request.unsupported")
+        }
+        // provider errors keep their status, so a 429 still reads as a rate limit
+        ProviderError::Status(status, body) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
+            (
+                status,
+                Json(json!({
+                    "error": {
+                        "message": "provider.error",
+                        "type": "api_error",
+                        "code": "provider.error",
+                        "provider": provider,
+                        "provider_response": body,
+                    }
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+// models with a credential use the org's own key, the rest use the platform key
+fn resolve_api_key(
+    state: &AppState,
+    credentials: &[ProviderCredential],
+    model: &ModelObject,
+    owner: &ModelOwner,
+) -> Result<String, Response> {
+    if model.credential_id.is_empty() {
+        return match providers::api_key_for(owner, &state.provider_settings) {
+            Some(api_key) => Ok(api_key),
+            None => Err(completion_error(StatusCode::SERVICE_UNAVAILABLE, "provider.credentials.missing")),
+        };
+    }
+
+    let credential = match credentials.iter().find(|credential| credential.id == model.credential_id) {
+        Some(credential) => credential,
+        None => return Err(completion_error(StatusCode::SERVICE_UNAVAILABLE, "credential.not.found")),
+    };
+
+    let encryption_key = match &state.credentials_encryption_key {
+        Some(key) => key,
+        None => return Err(completion_error(StatusCode::SERVICE_UNAVAILABLE, "credentials.vault.disabled")),
+    };
+
+    match decrypt_secret(encryption_key, &credential.encrypted_key) {
+        Ok(api_key) => Ok(api_key),
+        Err(e) => {
+            error!("error decrypting credential {}: {}", credential.id, e);
+            Err(completion_error(StatusCode::INTERNAL_SERVER_ERROR, "credential.decryption.error"))
+        }
+    }
+}
+
+// exponential with equal jitter, half of the delay is random so retries from
+// concurrent requests don't hit the provider at the same time
+fn backoff_delay(base: Duration, retry: u32) -> Duration {
+    let delay = base.saturating_mul(2u32.saturating_pow(retry - 1));
+    let half = delay / 2;
+    let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+    half + Duration::from_millis(jitter)
+}
+
+// fills in what a provider call consumed and queues it for the usage ledger
+fn record_usage(writer: &UsageWriter, catalog: &ModelCatalog, mut record: UsageRecord, status: &str, usage: &Usage, started: Instant) {
+    record.status = status.to_string();
+    record.prompt_tokens = usage.prompt_tokens as i64;
+    record.completion_tokens = usage.completion_tokens as i64;
+    record.latency_ms = started.elapsed().as_millis() as i64;
+    record.cost = catalog.cost(&record.model_id, usage.prompt_tokens, usage.completion_tokens).unwrap_or(0.0);
+    writer.record(record);
+}
+
+async fn call_provider(
+    state: &AppState,
+    owner: &ModelOwner,
+    api_key: &str,
+    chat_request: &ChatRequest,
+    stream: bool,
+    usage_record: UsageRecord,
+) -> Result<Response, ProviderError> {
+    let started = Instant::now();
+    let adapter = match providers::adapter_for(owner, &state.provider_settings) {
+        Some(adapter) => adapter,
+        None => return Err(ProviderError::InvalidResponse(String::from("provider not supported"))),
+    };
+
+    let provider = adapter.name();
+    let request_timeout = state.provider_settings.request_timeout;
+
+    if stream {
+        // the timeout only covers the provider accepting the request, not the whole stream
+        let events = match tokio::time::timeout(request_timeout, providers::stream_chat(&state.http_client, adapter, api_key, chat_request)).await {
+            Ok(result) => result?,
+            Err(_) => return Err(ProviderError::Timeout),
+        };
+
+        let writer = state.usage_writer.clone();
+        let catalog = Arc::clone(&state.model_catalog);
+        return Ok(Sse::new(completion_chunks(events, chat_request.clone(), provider, move |status, usage| {
+            record_usage(&writer, &catalog, usage_record, status, usage, started)
+        }))
+            .keep_alive(KeepAlive::default())
+            .into_response());
+    }
+
+    let chat_response = match tokio::time::timeout(request_timeout, providers::send_chat(&state.http_client, adapter.as_ref(), api_key, chat_request)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(ProviderError::Timeout),
+    };
+
+    record_usage(&state.usage_writer, &state.model_catalog, usage_record, "ok", &chat_response.usage, started);
+
+    let body = chat_response.to_openai(chrono::Utc::now().timestamp());
+    Ok((StatusCode::OK, Json(body)).into_response())
+}
+
+// sent when the routed model didn't serve the request, model_id is the fallback
+// that did or null when the whole chain failed
+fn notify_failover(state: &AppState, org: &Organization, router_id: &str, routed_model: &str, model_id: Option<&String>, attempts: &[String]) {
+    emit_event(state, org, WebhookEvent::ProviderFailover, json!({
+        "router_id": router_id,
+        "routed_model_id": routed_model,
+        "model_id": model_id,
+        "attempts": attempts,
+    }));
+}
+
+// X-Router-Attempts lists every model tried, e.g. gpt-4=429, gpt-4=timeout, claude-2=ok
+fn with_routing_headers(mut response: Response, router_id: &str, model_id: Option<&String>, attempts: &[String]) -> Response {
+    let headers = response.headers_mut();
+
+    if let Ok(router_id) = HeaderValue::from_str(router_id) {
+        headers.insert("X-Router-Id", router_id);
+    }
+
+    if let Some(model_id) = model_id.and_then(|model_id| HeaderValue::from_str(model_id).ok()) {
+        headers.insert("X-Router-Model", model_id);
+    }
+
+    if let Ok(attempts) = HeaderValue::from_str(&attempts.join(", ")) {
+        headers.insert("X-Router-Attempts", attempts);
+    }
+
+    response
+}
+
+// what a stream consumed so far, a stream dropped before it ends because the
+// client went away is still recorded, as client_disconnected
+struct StreamRecorder<F: FnOnce(&str, &Usage)> {
+    on_finish: Option<F>,
+    usage: Usage,
+    prompt_characters: usize,
+    completion_characters: usize,
+}
+
+impl<F: FnOnce(&str, &Usage)> StreamRecorder<F> {
+    // estimates what the provider didn't report, on_finish only runs once
+    fn finish(&mut self, status: &str) -> Usage {
+        if self.usage.prompt_tokens == 0 {
+            self.usage.prompt_tokens = providers::estimate_tokens(self.prompt_characters);
+        }
+
+        if self.usage.completion_tokens == 0 {
+            self.usage.completion_tokens = providers::estimate_tokens(self.completion_characters);
+        }
+
+        self.usage.total_tokens = self.usage.prompt_tokens + self.usage.completion_tokens;
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(status, &self.usage);
+        }
+
+        self.usage.clone()
+    }
+}
+
+impl<F: FnOnce(&str, &Usage)> Drop for StreamRecorder<F> {
+    fn drop(&mut self) {
+        self.finish("client_disconnected");
+    }
+}
+
+// re-emits any provider stream as OpenAI chat.completion.chunk events, on_finish
+// gets the final usage once the stream ends or is dropped
+fn completion_chunks(
+    mut events: ProviderStream,
+    request: ChatRequest,
+    provider: &'static str,
+    on_finish: impl FnOnce(&str, &Usage) + Send + 'static,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let id = format!("chatcmpl-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+    let created = chrono::Utc::now().timestamp();
+    let model = request.model.clone();
+    // same as OpenAI, the usage chunk is only sent when the client asks for it
+    let include_usage = request.options
+        .get("stream_options")
+        .and_then(|stream_options| stream_options.get("include_usage"))
+        .and_then(|include_usage| include_usage.as_bool())
+        .unwrap_or(false);
+
+    let chunk = move |choices: Value, usage: Option<&Usage>| {
+        let mut chunk = json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": choices,
+        });
+
+        if include_usage {
+            chunk["usage"] = json!(usage);
+        }
+
+        Event::default().data(chunk.to_string())
+    };
+    let choice = |delta: Value, finish_reason: Option<String>| json!([{
+        "index": 0,
+        "delta": delta,
+        "finish_reason": finish_reason,
+    }]);
+
+    async_stream::stream! {
+        let mut recorder = StreamRecorder {
+            on_finish: Some(on_finish),
+            usage: Usage::default(),
+            prompt_characters: request.messages.iter().map(|message| message.text().len()).sum(),
+            completion_characters: 0,
+        };
+        let mut finish_reason = String::from("stop");
+        let mut tool_indexes: Vec<u64> = vec![];
+
+        yield Ok(chunk(choice(json!({"role": "assistant", "content": ""}), None), None));
+
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(StreamEvent::Delta(text)) => {
+                    recorder.completion_characters += text.len();
+                    yield Ok(chunk(choice(json!({"content": text}), None), None));
+                }
+                Ok(StreamEvent::ToolCall { index, id, name, arguments }) => {
+                    // OpenAI numbers the calls from 0, providers may count other content blocks too
+                    let index = match tool_indexes.iter().position(|seen| *seen == index) {
+                        Some(position) => position,
+                        None => {
+                            tool_indexes.push(index);
+                            tool_indexes.len() - 1
+                        }
+                    };
+
+                    recorder.completion_characters += arguments.len();
+                    let mut tool_call = json!({"index": index, "function": {"arguments": arguments}});
+                    if let Some(id) = id {
+                        tool_call["id"] = json!(id);
+                        tool_call["type"] = json!("function");
+                    }
+                    if let Some(name) = name {
+                        recorder.completion_characters += name.len();
+                        tool_call["function"]["name"] = json!(name);
+                    }
+
+                    yield Ok(chunk(choice(json!({"tool_calls": [tool_call]}), None), None));
+                }
+                Ok(StreamEvent::Finish(reason)) => finish_reason = reason,
+                Ok(StreamEvent::PromptTokens(tokens)) => recorder.usage.prompt_tokens = tokens,
+                Ok(StreamEvent::CompletionTokens(tokens)) => recorder.usage.completion_tokens = tokens,
+                Ok(StreamEvent::Done) => break,
+                // the stream ends with the error event, there's no finish_reason for it
+                Err(e) => {
+                    error!("chat completion stream from {} failed: {:?}", provider, e);
+                    recorder.finish("stream_interrupted");
+                    yield Ok(Event::default().data(json!({
+                        "error": {
+                            "message": "provider.stream.interrupted",
+                            "type": "api_error",
+                            "code": "provider.stream.interrupted",
+                        }
+                    }).to_string()));
+                    return;
+                }
+            }
+        }
+
+        let usage = recorder.finish("ok");
+        yield Ok(chunk(choice(json!({}), Some(finish_reason)), None));
+        if include_usage {
+            yield Ok(chunk(json!([]), Some(&usage)));
+        }
+        yield Ok(Event::default().data("[DONE]"));
+    }
+}
+
+pub async fn chat_completions(
+    headers: HeaderMap,
+    payload_result: Result<Json<ChatCompletion>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Response {
+    let request_started = Instant::now();
+    let payload = match payload_result {
+        Ok(Json(payload)) => payload,
+        Err(_) => return completion_error(StatusCode::BAD_REQUEST, "invalid.payload"),
+    };
+
+    let access_token = match headers.get("Authorization").and_then(|value| value.to_str().ok()) {
+        Some(value) => value.trim_start_matches("Bearer ").trim().to_string(),
+        None => return completion_error(StatusCode::UNAUTHORIZED, "unauthorized.access.token"),
+    };
+
+    if access_token.is_empty() {
+        return completion_error(StatusCode::UNAUTHORIZED, "unauthorized.access.token");
+    }
+
+    let filter = build_organizations_access_token_filter(&access_token).await;
+    let org = match find_organization(&state.mongo_db, filter).await {
+        Ok(org) => org,
+        Err((StatusCode::NOT_FOUND, _)) => return completion_error(StatusCode::UNAUTHORIZED, "unauthorized.access.token"),
+        Err(e) => return generic_error(e),
+    };
+
+    let required_scope = vec![
+        AccessTokenScopes::AccessPromptModelSuggestion,
+        AccessTokenScopes::Admin,
+    ];
+
+    if !org.access_tokens.iter().any(|token| {
+        token.token == access_token &&
+        token.scopes.iter().any(|scope| required_scope.contains(scope))
+    }) {
+        return completion_error(StatusCode::UNAUTHORIZED, "unauthorized.access.token.scopes");
+    }
+
+    // SDKs always send a model, the RouterID header wins when both are present
+    let router_id = match headers.get("RouterID").and_then(|value| value.to_str().ok()) {
+        Some(router_id) => router_id.to_string(),
+        None => payload.model.clone().unwrap_or_default(),
+    };
+
+    let router = match org.routers.iter().find(|router| router.id == router_id) {
+        Some(router) if router.active && !router.deleted => router,
+        _ => return completion_error(StatusCode::NOT_FOUND, "router.not.found"),
+    };
+
+    // only calls to an existing router count against the quota
+    let plan = match org_plan(&state.mongo_db, &org).await {
+        Ok(plan) => plan,
+        Err(e) => return generic_error(e),
+    };
+
+    if let Err(e) = consume_monthly_routing_call(&state, &org, plan).await {
+        return generic_error(e);
+    }
+
+    complete(&state, &org, router, &payload, &access_token, request_started).await
+}
+
+// routes the prompt and walks the routed model and the router's fallback chain
+// until a provider answers
+async fn complete(
+    state: &AppState,
+    org: &Organization,
+    router: &Router,
+    payload: &ChatCompletion,
+    access_token: &str,
+    request_started: Instant,
+) -> Response {
+    // same decision as /api/llm/process, a model missing a required capability
+    // is already swapped for a compatible one
+    let prompt = extract_prompt(&payload.messages);
+    let required = required_capabilities(payload);
+    let routing = match route_prompt(state, &org.id, &org.models, router, &prompt, &required).await {
+        Ok(routing) => routing,
+        Err(e) => return generic_error(e),
+    };
+
+    let (routed_model, routed_strategy, routed_label, routed_score) = match routing.decision() {
+        Some(decision) => (decision.model.id.clone(), decision.strategy.as_str(), decision.label, decision.score),
+        None => return completion_error(StatusCode::BAD_REQUEST, "model.not.found"),
+    };
+
+    // written once the chain is done so it names the model that served
+    let now = chrono::Utc::now();
+    let log_entry = RequestLogEntry {
+        id: String::new(),
+        org_id: org.id.clone(),
+        router_id: router.id.clone(),
+        kind: COMPLETION_KIND.to_string(),
+        created_at: now.to_rfc3339(),
+        timestamp: now.timestamp_millis(),
+        prompt: prompt.clone(),
+        prompt_redacted: false,
+        prompt_size: prompt.len() as i64,
+        model_id: routed_model.clone(),
+        strategy: routed_strategy.to_string(),
+        label: routed_label.clone(),
+        score: routed_score,
+        status: String::new(),
+        latency_ms: 0,
+        candidates: vec![],
+    };
+
+    // the routed model first, then the router's fallback chain
+    let mut chain = vec![routed_model.clone()];
+    for model_id in &router.fallback_model_ids {
+        if !chain.contains(model_id) {
+            chain.push(model_id.clone());
+        }
+    }
+
+    let stream = payload.stream.unwrap_or(false);
+    let failover_settings = state.failover_settings;
+    let access_token_fingerprint = token_fingerprint(access_token);
+    let mut attempts: Vec<String> = vec![];
+    let mut last_error: Option<Response> = None;
+
+    for model_id in &chain {
+        let model = match org.models.iter().find(|model| &model.id == model_id) {
+            Some(model) => model,
+            None => {
+                attempts.push(format!("{}=model_not_found", model_id));
+                continue;
+            }
+        };
+
+        // fallback models aren't ranked, the routed one always passes
+        if !state.model_catalog.supports(&model.id, &required) {
+            attempts.push(format!("{}=capabilities_unsupported", model.id));
+            continue;
+        }
+
+        let catalog_model = match state.model_catalog.get(&model.id) {
+            Some(catalog_model) => catalog_model,
+            None => {
+                attempts.push(format!("{}=provider_not_supported", model.id));
+                last_error = Some(completion_error(StatusCode::BAD_REQUEST, "provider.not.supported"));
+                continue;
+            }
+        };
+
+        let owner = catalog_model.vendor.clone();
+        let provider = match providers::adapter_for(&owner, &state.provider_settings) {
+            Some(adapter) => adapter.name(),
+            None => {
+                attempts.push(format!("{}=provider_not_supported", model.id));
+                last_error = Some(completion_error(StatusCode::BAD_REQUEST, "provider.not.supported"));
+                continue;
+            }
+        };
+
+        let api_key = match resolve_api_key(state, &org.credentials, model, &owner) {
+            Ok(api_key) => api_key,
+            Err(e) => {
+                attempts.push(format!("{}=credentials_missing", model.id));
+                last_error = Some(e);
+                continue;
+            }
+        };
+
+        let chat_request = build_chat_request(payload, catalog_model.api_model_id());
+
+        // models further down the chain are only reached through failover
+        let (strategy, category, score) = match model.id == routed_model {
+            true => (routed_strategy, routed_label.clone(), routed_score),
+            false => ("fallback", None, None),
+        };
+
+        let usage_record = UsageRecord {
+            created_at: chrono::Utc::now(),
+            kind: COMPLETION_KIND.to_string(),
+            org_id: org.id.clone(),
+            router_id: router.id.clone(),
+            access_token: access_token_fingerprint.clone(),
+            model_id: model.id.clone(),
+            strategy: strategy.to_string(),
+            category,
+            status: String::new(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            latency_ms: 0,
+            cost: 0.0,
+            score,
+        };
+
+        let credential = Credential::of(&model.credential_id);
+        for retry in 0..=failover_settings.max_retries {
+            if state.circuit_breaker.is_open(provider, &credential) {
+                attempts.push(format!("{}=circuit_open", model.id));
+                last_error = Some(completion_error(StatusCode::SERVICE_UNAVAILABLE, "provider.circuit.open"));
+                break;
+            }
+
+            if retry > 0 {
+                tokio::time::sleep(backoff_delay(failover_settings.backoff, retry)).await;
+            }
+
+            let started = Instant::now();
+            match call_provider(state, &owner, &api_key, &chat_request, stream, usage_record.clone()).await {
+                Ok(response) => {
+                    state.circuit_breaker.record_success(provider, &credential);
+                    record_model_call(state, &org.id, &model.id, started.elapsed().as_millis() as u64, true).await;
+                    attempts.push(format!("{}=ok", model.id));
+                    let status = match model.id == routed_model {
+                        true => "ok",
+                        false => {
+                            notify_failover(state, org, &router.id, &routed_model, Some(&model.id), &attempts);
+                            "fallback"
+                        }
+                    };
+                    log_completion(state, org, log_entry, &model.id, status, request_started);
+
+                    return with_routing_headers(response, &router.id, Some(&model.id), &attempts);
+                }
+                // another provider of the chain may support what this one can't
+                Err(ProviderError::InvalidRequest(message)) => {
+                    attempts.push(format!("{}=request_unsupported", model.id));
+                    last_error = Some(provider_error(provider, ProviderError::InvalidRequest(message)));
+                    break;
+                }
+                Err(e) if e.is_retriable() => {
+                    record_usage(&state.usage_writer, &state.model_catalog, usage_record.clone(), &e.outcome(), &Usage::default(), started);
+                    record_model_call(state, &org.id, &model.id, started.elapsed().as_millis() as u64, false).await;
+                    state.circuit_breaker.record_failure(provider, &credential);
+                    attempts.push(format!("{}={}", model.id, e.outcome()));
+                    last_error = Some(provider_error(provider, e));
+                }
+                // client side errors would fail the same way on every model
+                Err(e) => {
+                    record_usage(&state.usage_writer, &state.model_catalog, usage_record.clone(), &e.outcome(), &Usage::default(), started);
+                    attempts.push(format!("{}={}", model.id, e.outcome()));
+                    log_completion(state, org, log_entry, &model.id, &e.outcome(), request_started);
+                    return with_routing_headers(provider_error(provider, e), &router.id, Some(&model.id), &attempts);
+                }
+            }
+        }
+    }
+
+    let response = match last_error {
+        Some(response) => response,
+        None => completion_error(StatusCode::BAD_REQUEST, "model.not.found"),
+    };
+
+    if chain.len() > 1 {
+        notify_failover(state, org, &router.id, &routed_model, None, &attempts);
+    }
+
+    log_completion(state, org, log_entry, "", "failed", request_started);
+    with_routing_headers(response, &router.id, None, &attempts)
+}
+
+// ok when the routed model served, fallback when a model of the chain did
+fn log_completion(state: &AppState, org: &Organization, mut entry: RequestLogEntry, model_id: &str, status: &str, request_started: Instant) {
+    entry.model_id = model_id.to_string();
+    entry.status = status.to_string();
+    entry.latency_ms = request_started.elapsed().as_millis() as i64;
+    log_request(state, &org.request_log, entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use axum::{body::to_bytes, extract::State, routing::post, Router as AxumRouter};
+    use mongodb::Client as MongoClient;
+    use redis::Client as RedisClient;
+
+    use super::*;
+    use crate::{
+        inference::{registry::EmbeddingModelRegistry, scheduler::{BatchSettings, ClassificationScheduler}, slot::ModelSlot},
+        providers::circuit_breaker::CircuitBreaker,
+        storage::diesel_postgres::usage::UsageWriterSettings,
+        types::{
+            lemonsqueezy::Products,
+            state::{EmailProviderSettings, FailoverSettings, GoogleAuth, LLMResources, MasterEmailEntity, PromptClassificationModel, ProviderSettings},
+        },
+    };
+
+    // answers keyed by the model of the request, the first answer of a model is
+    // removed once used and the last one repeats
+    #[derive(Clone, Default)]
+    struct MockProvider {
+        answers: Arc<Mutex<HashMap<String, Vec<(u16, String)>>>>,
+        requests: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl MockProvider {
+        fn answer(&self, model: &str, status: u16, body: &str) {
+            self.answers.lock().unwrap().entry(model.to_string()).or_default().push((status, body.to_string()));
+        }
+
+        fn requested_models(&self) -> Vec<String> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|request| request["model"].as_str().unwrap_or_default().to_string())
+                .collect()
+        }
+
+        async fn serve(&self) -> String {
+            let app = AxumRouter::new()
+                .route("/chat/completions", post(mock_chat_completions))
+                .with_state(self.clone());
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                axum::serve(listener, app).await.unwrap();
+            });
+
+            format!("http://{}", address)
+        }
+    }
+
+    async fn mock_chat_completions(State(mock): State<MockProvider>, Json(body): Json<Value>) -> Response {
+        let model = body["model"].as_str().unwrap_or_default().to_string();
+        let stream = body["stream"].as_bool().unwrap_or(false);
+        mock.requests.lock().unwrap().push(body);
+
+        let (status, answer) = {
+            let mut answers = mock.answers.lock().unwrap();
+            let answers = answers.entry(model).or_default();
+            match answers.len() {
+                0 => (404, String::from(r#"{"error": {"message": "unknown model"}}"#)),
+                1 => answers[0].clone(),
+                _ => answers.remove(0),
+            }
+        };
+
+        let content_type = match stream && status == 200 {
+            true => "text/event-stream",
+            false => "application/json",
+        };
+
+        (StatusCode::from_u16(status).unwrap(), [("content-type", content_type)], answer).into_response()
+    }
+
+    fn completion(model: &str, content: &str) -> String {
+        json!({
+            "id": "chatcmpl-mock",
+            "model": model,
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": content}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7},
+        })
+        .to_string()
+    }
+
+    async fn test_state(openai_api_base_url: String) -> AppState {
+        let mongodb_client = MongoClient::with_uri_str("mongodb://127.0.0.1:1").await.unwrap();
+        let batch_settings = BatchSettings {
+            max_batch_size: 1,
+            max_wait: Duration::from_millis(1),
+        };
+        let classification_slot = Arc::new(ModelSlot::new(None, String::new(), String::new(), None));
+
+        AppState {
+            production: false,
+            api_url: String::new(),
+            api_tokens_expiration_time: 0,
+            mongo_db: mongodb_client.database("test"),
+            mongodb_client,
+            redis_connection: RedisClient::open("redis://127.0.0.1:1").unwrap(),
+            redis_manager: Arc::new(tokio::sync::OnceCell::new()),
+            postgres_conn: None,
+            lemonsqueezy_webhook_signature_key: String::new(),
+            products: Products {
+                pro_product_id: 0,
+                pro_monthly_variant_id: 0,
+                pro_annually_variant_id: 0,
+            },
+            enabled_email_integration: false,
+            master_email_entity: MasterEmailEntity {
+                email: String::new(),
+                name: String::new(),
+            },
+            email_provider_settings: EmailProviderSettings {
+                email_verification_template_id: 0,
+            },
+            google_auth: GoogleAuth {
+                client_id: String::new(),
+                client_secret: String::new(),
+                redirect_url: String::new(),
+            },
+            platform_admins: vec![],
+            trust_proxy_headers: false,
+            llm_resources: LLMResources {
+                prompt_classification_model: PromptClassificationModel {
+                    scheduler: ClassificationScheduler::new(Arc::clone(&classification_slot), batch_settings),
+                    slot: classification_slot,
+                },
+                embedding_models: EmbeddingModelRegistry {
+                    default_model_id: String::from("default"),
+                    models: HashMap::new(),
+                },
+            },
+            http_client: reqwest::Client::new(),
+            provider_settings: ProviderSettings {
+                openai_api_key: Some(String::from("platform-key")),
+                openai_api_base_url,
+                anthropic_api_key: None,
+                anthropic_api_base_url: String::from("http://127.0.0.1:1"),
+                cohere_api_key: None,
+                cohere_api_base_url: String::from("http://127.0.0.1:1"),
+                request_timeout: Duration::from_secs(5),
+            },
+            failover_settings: FailoverSettings {
+                max_retries: 0,
+                backoff: Duration::from_millis(1),
+            },
+            circuit_breaker: Arc::new(CircuitBreaker::new(100, Duration::from_secs(60))),
+            credentials_encryption_key: None,
+            usage_writer: UsageWriter::new(None, UsageWriterSettings {
+                buffer_size: 1,
+                max_batch_size: 1,
+                flush_interval: Duration::from_millis(1),
+            }),
+            model_catalog: Arc::new(ModelCatalog::bundled().unwrap()),
+        }
+    }
+
+    // a single model router on gpt-4 falling back to gpt-3.5-turbo
+    fn test_org() -> Organization {
+        let model = |id: &str| json!({"id": id, "type": "legacy", "display_name": id, "registered_by": "owner"});
+
+        serde_json::from_value(json!({
+            "id": "org",
+            "name": "org",
+            "models": [model("gpt-4"), model("gpt-3.5-turbo")],
+            "routers": [{
+                "id": "router",
+                "name": "router",
+                "description": "",
+                "active": true,
+                "deleted": false,
+                "max_prompt_length": 1000,
+                "use_single_model": true,
+                "model_id": "gpt-4",
+                "use_prompt_calification_model": false,
+                "prompt_calification_model_categories": [],
+                "use_sentence_matching": false,
+                "sentences": [],
+                "fallback_model_ids": ["gpt-3.5-turbo"],
+            }],
+            "members": [],
+            "access_tokens": [],
+            "deleted": false,
+        }))
+        .unwrap()
+    }
+
+    fn chat_completion(stream: bool) -> ChatCompletion {
+        serde_json::from_value(json!({
+            "model": "router",
+            "messages": [{"role": "user", "content": "hello"}],
+            "stream": stream,
+            "temperature": 0.2,
+            "top_p": 0.9,
+            "seed": 7,
+            "user": "end-user",
+            "logit_bias": {"50256": -100},
+        }))
+        .unwrap()
+    }
+
+    async fn run(mock: &MockProvider, payload: ChatCompletion) -> (StatusCode, HeaderMap, String) {
+        let state = test_state(mock.serve().await).await;
+        let org = test_org();
+
+        let response = complete(&state, &org, &org.routers[0], &payload, "token", Instant::now()).await;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn header(headers: &HeaderMap, name: &str) -> String {
+        headers.get(name).map(|value| value.to_str().unwrap().to_string()).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn forwards_non_streaming_completions() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 200, &completion("gpt-4", "hi there"));
+
+        let (status, headers, body) = run(&mock, chat_completion(false)).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["object"], "chat.completion");
+        assert_eq!(body["choices"][0]["message"]["content"], "hi there");
+        assert_eq!(body["usage"]["total_tokens"], 7);
+        assert_eq!(header(&headers, "X-Router-Model"), "gpt-4");
+        assert_eq!(header(&headers, "X-Router-Attempts"), "gpt-4=ok");
+
+        let requests = mock.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["model"], "gpt-4");
+        assert_eq!(requests[0]["temperature"], 0.2);
+        assert_eq!(requests[0]["top_p"], 0.9);
+        assert_eq!(requests[0]["seed"], 7);
+        assert_eq!(requests[0]["user"], "end-user");
+        assert_eq!(requests[0]["logit_bias"]["50256"], -100);
+        assert!(requests[0].get("stream").is_none());
+    }
+
+    fn provider_chunk(delta: Value, finish_reason: Value) -> String {
+        format!(
+            "data: {}\n\n",
+            json!({"choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]}),
+        )
+    }
+
+    fn stream_chunks(body: &str) -> Vec<Value> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect()
+    }
+
+    fn stream_content(chunks: &[Value]) -> String {
+        chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect()
+    }
+
+    fn complete_stream() -> String {
+        [
+            provider_chunk(json!({"content": "hi"}), Value::Null),
+            provider_chunk(json!({"content": " there"}), Value::Null),
+            provider_chunk(json!({}), json!("stop")),
+            String::from("data: {\"choices\": [], \"usage\": {\"prompt_tokens\": 5, \"completion_tokens\": 2}}\n\n"),
+            String::from("data: [DONE]\n\n"),
+        ]
+        .concat()
+    }
+
+    #[tokio::test]
+    async fn streams_completion_chunks() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 200, &complete_stream());
+
+        let (status, headers, body) = run(&mock, chat_completion(true)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(header(&headers, "content-type").starts_with("text/event-stream"));
+        assert_eq!(header(&headers, "X-Router-Model"), "gpt-4");
+
+        let chunks = stream_chunks(&body);
+        assert_eq!(stream_content(&chunks), "hi there");
+        assert!(chunks.iter().all(|chunk| chunk["object"] == "chat.completion.chunk"));
+        assert!(chunks.iter().all(|chunk| chunk["id"] == chunks[0]["id"]));
+        // no usage chunk unless the client asks for it
+        assert!(chunks.iter().all(|chunk| chunk.get("usage").is_none()));
+        assert_eq!(chunks.last().unwrap()["choices"][0]["finish_reason"], "stop");
+        assert!(body.trim_end().ends_with("data: [DONE]"));
+        assert_eq!(mock.requests.lock().unwrap()[0]["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn streams_usage_when_requested() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 200, &complete_stream());
+
+        let mut payload = chat_completion(true);
+        payload.options.insert(String::from("stream_options"), json!({"include_usage": true}));
+        let (status, _, body) = run(&mock, payload).await;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let chunks = stream_chunks(&body);
+        let usage = chunks.last().unwrap();
+        assert_eq!(usage["id"], chunks[0]["id"]);
+        assert_eq!(usage["choices"], json!([]));
+        assert_eq!(usage["usage"]["prompt_tokens"], 5);
+        assert_eq!(usage["usage"]["completion_tokens"], 2);
+        assert_eq!(usage["usage"]["total_tokens"], 7);
+        assert!(chunks[..chunks.len() - 1].iter().all(|chunk| chunk["usage"].is_null()));
+    }
+
+    #[tokio::test]
+    async fn ends_interrupted_streams_with_an_error_event() {
+        let mock = MockProvider::default();
+        let events = [
+            provider_chunk(json!({"content": "hi"}), Value::Null),
+            String::from("data: {not json\n\n"),
+        ];
+        mock.answer("gpt-4", 200, &events.concat());
+
+        let (status, _, body) = run(&mock, chat_completion(true)).await;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let chunks = stream_chunks(&body);
+        assert_eq!(stream_content(&chunks), "hi");
+        assert_eq!(chunks.last().unwrap()["error"]["code"], "provider.stream.interrupted");
+        assert!(chunks.iter().all(|chunk| chunk["choices"][0]["finish_reason"].is_null()));
+        assert!(!body.contains("[DONE]"));
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_server_errors() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 503, r#"{"error": {"message": "overloaded"}}"#);
+        mock.answer("gpt-3.5-turbo", 200, &completion("gpt-3.5-turbo", "from the fallback"));
+
+        let (status, headers, body) = run(&mock, chat_completion(false)).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["choices"][0]["message"]["content"], "from the fallback");
+        assert_eq!(header(&headers, "X-Router-Model"), "gpt-3.5-turbo");
+        assert_eq!(header(&headers, "X-Router-Attempts"), "gpt-4=503, gpt-3.5-turbo=ok");
+        assert_eq!(mock.requested_models(), vec!["gpt-4", "gpt-3.5-turbo"]);
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_rate_limits() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 429, r#"{"error": {"message": "rate limited"}}"#);
+        mock.answer("gpt-3.5-turbo", 200, &completion("gpt-3.5-turbo", "from the fallback"));
+
+        let (status, headers, _) = run(&mock, chat_completion(false)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(header(&headers, "X-Router-Attempts"), "gpt-4=429, gpt-3.5-turbo=ok");
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_error_when_the_chain_fails() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 500, r#"{"error": {"message": "down"}}"#);
+        mock.answer("gpt-3.5-turbo", 429, r#"{"error": {"message": "rate limited"}}"#);
+
+        let (status, headers, body) = run(&mock, chat_completion(false)).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["error"]["provider_response"]["error"]["message"], "rate limited");
+        assert_eq!(header(&headers, "X-Router-Model"), "");
+        assert_eq!(header(&headers, "X-Router-Attempts"), "gpt-4=500, gpt-3.5-turbo=429");
+    }
+
+    #[tokio::test]
+    async fn passes_client_errors_through_without_failover() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4", 400, r#"{"error": {"message": "bad temperature"}}"#);
+        mock.answer("gpt-3.5-turbo", 200, &completion("gpt-3.5-turbo", "unused"));
+
+        let (status, headers, body) = run(&mock, chat_completion(false)).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "provider.error");
+        assert_eq!(body["error"]["provider"], "openai");
+        assert_eq!(body["error"]["provider_response"]["error"]["message"], "bad temperature");
+        assert_eq!(header(&headers, "X-Router-Attempts"), "gpt-4=400");
+        assert_eq!(mock.requested_models(), vec!["gpt-4"]);
+    }
+
+    #[tokio::test]
+    async fn forwards_image_parts_to_a_vision_model() {
+        let mock = MockProvider::default();
+        mock.answer("gpt-4-vision-preview", 200, &completion("gpt-4-vision-preview", "a cat"));
+
+        let state = test_state(mock.serve().await).await;
+        let mut org = test_org();
+        org.models.push(serde_json::from_value(json!({"id": "gpt-4-vision", "type": "legacy", "display_name": "vision", "registered_by": "owner"})).unwrap());
+
+        let payload: ChatCompletion = serde_json::from_value(json!({
+            "model": "router",
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "what's this?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0", "detail": "low"}},
+            ]}],
+        }))
+        .unwrap();
+
+        let response = complete(&state, &org, &org.routers[0], &payload, "token", Instant::now()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(response.headers(), "X-Router-Model"), "gpt-4-vision");
+
+        let requests = mock.requests.lock().unwrap();
+        assert_eq!(requests[0]["messages"], json!([{"role": "user", "content": [
+            {"type": "text", "text": "what's this?"},
+            {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0", "detail": "low"}},
+        ]}]));
+    }
+
+    fn tool_payload(stream: bool) -> ChatCompletion {
+        serde_json::from_value(json!({
+            "model": "router",
+            "stream": stream,
+            "messages": [
+                {"role": "user", "content": "weather in Paris?"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\": \"Paris\"}"}},
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "sunny"},
+                {"role": "user", "content": "and in Rome?"},
+            ],
+            "tools": [{"type": "function", "function": {"name": "weather", "parameters": {"type": "object"}}}],
+            "tool_choice": "auto",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn forwards_tool_calls_and_tool_results() {
+        let mock = MockProvider::default();
+        let answer = json!({
+            "id": "chatcmpl-mock",
+            "model": "gpt-4",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": null, "tool_calls": [
+                {"id": "call_2", "type": "function", "function": {"name": "weather", "arguments": "{\"city\": \"Rome\"}"}},
+            ]}, "finish_reason": "tool_calls"}],
+            "usage": {"prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25},
+        });
+        mock.answer("gpt-4", 200, &answer.to_string());
+
+        let (status, _, body) = run(&mock, tool_payload(false)).await;
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["choices"][0]["message"]["content"], Value::Null);
+        assert_eq!(body["choices"][0]["message"]["tool_calls"], answer["choices"][0]["message"]["tool_calls"]);
+        assert_eq!(body["choices"][0]["finish_reason"], "tool_calls");
+
+        let requests = mock.requests.lock().unwrap();
+        assert_eq!(requests[0]["messages"][1]["content"], Value::Null);
+        assert_eq!(requests[0]["messages"][1]["tool_calls"][0]["function"]["name"], "weather");
+        assert_eq!(requests[0]["messages"][2], json!({"role": "tool", "tool_call_id": "call_1", "content": "sunny"}));
+        assert_eq!(requests[0]["tools"][0]["function"]["name"], "weather");
+        assert_eq!(requests[0]["tool_choice"], "auto");
+    }
+
+    #[tokio::test]
+    async fn streams_tool_call_chunks() {
+        let mock = MockProvider::default();
+        let events = [
+            provider_chunk(json!({"tool_calls": [{"index": 0, "id": "call_2", "type": "function", "function": {"name": "weather", "arguments": ""}}]}), Value::Null),
+            provider_chunk(json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"city\": \"Rome\"}"}}]}), Value::Null),
+            provider_chunk(json!({}), json!("tool_calls")),
+            String::from("data: [DONE]\n\n"),
+        ];
+        mock.answer("gpt-4", 200, &events.concat());
+
+        let (status, _, body) = run(&mock, tool_payload(true)).await;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let chunks = stream_chunks(&body);
+        let tool_calls: Vec<&Value> = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["tool_calls"].get(0))
+            .collect();
+        assert_eq!(tool_calls.len(), 2);
+        assert_eq!(tool_calls[0]["id"], "call_2");
+        assert_eq!(tool_calls[0]["function"]["name"], "weather");
+        assert_eq!(tool_calls[1]["index"], 0);
+        assert_eq!(tool_calls[1]["function"]["arguments"], "{\"city\": \"Rome\"}");
+        assert_eq!(chunks.last().unwrap()["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[tokio::test]
+    async fn records_usage_when_the_client_disconnects() {
+        let events: ProviderStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(StreamEvent::Delta(String::from("hello there"))),
+            Ok(StreamEvent::Delta(String::from(" and more"))),
+            Ok(StreamEvent::Done),
+        ]));
+        let recorded: Arc<Mutex<Vec<(String, Usage)>>> = Arc::default();
+        let on_finish = {
+            let recorded = Arc::clone(&recorded);
+            move |status: &str, usage: &Usage| recorded.lock().unwrap().push((status.to_string(), usage.clone()))
+        };
+
+        let request = build_chat_request(&chat_completion(true), String::from("gpt-4"));
+        let mut chunks = Box::pin(completion_chunks(events, request, "openai", on_finish));
+
+        // the role chunk and the first delta, then the client goes away
+        chunks.next().await;
+        chunks.next().await;
+        assert!(recorded.lock().unwrap().is_empty());
+        drop(chunks);
+
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].0, "client_disconnected");
+        assert_eq!(recorded[0].1.completion_tokens, providers::estimate_tokens("hello there".len()));
+        assert_eq!(recorded[0].1.prompt_tokens, providers::estimate_tokens("hello".len()));
+    }
+}
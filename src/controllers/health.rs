@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, Json};
+use serde_json::json;
+
+use crate::{types::{customer::GenericResponse, state::AppState}, utilities::helpers::ok};
+
+pub async fn get_health(
+    state: Arc<AppState>,
+) -> (StatusCode, Json<GenericResponse>) {
+    let llm_resources = &state.llm_resources;
+
+    ok("OK", Some(json!({
+        "models": {
+            "prompt_classification": llm_resources.prompt_classification_model.slot.info(),
+            "sentence_embeddings": llm_resources.embedding_models.info(),
+        },
+        // unloaded models fall back to lexical similarity outside production
+        "lexical_fallback": !state.production,
+    })))
+}
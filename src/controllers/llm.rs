@@ -1,26 +1,40 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use crate::{
-    storage::mongo::{build_organizations_filter, find_organization},
+    inference::{lexical, registry::EmbeddingModel},
+    providers::{estimate_tokens, is_callable},
+    storage::{
+        diesel_postgres::usage::{UsageRecord, ROUTING_KIND},
+        mongo::{build_organizations_filter, find_organization},
+    },
     types::{
         customer::GenericResponse,
-        incoming_requests::ProcessPrompt,
-        router::Category,
-        llms::LLMs,
+        incoming_requests::{ModelCatalogQueryParams, ModelFeedback, ProcessPrompt},
+        router::{Category, Router, Sentence},
+        catalog::{CatalogStatus, ModelCatalog, ModelInfo, RequiredCapabilities},
         organization::{AccessTokenScopes, ModelObject},
-        state::AppState,
+        request_log::{RequestLogCandidate, RequestLogEntry, RequestLogSettings},
+        state::{AppState, ProviderSettings},
     },
-    utilities::helpers::{
-        bad_request, detect_similar_sentences, ok, payload_analyzer,
-        unauthorized,
+    utilities::{
+        helpers::{
+            bad_request, detect_similar_sentences, ok, payload_analyzer,
+            token_fingerprint, unauthorized,
+        },
+        quotas::{consume_monthly_routing_call, org_plan},
+        model_stats::{model_health, pick_by_latency, record_model_call, ModelHealth},
+        request_log::log_request,
+        routing_rules::matching_rule,
     },
 };
 use axum::{
-    extract::rejection::JsonRejection,
+    extract::{rejection::JsonRejection, Query},
     http::{HeaderMap, StatusCode},
     Json,
 };
+use crate::inference::backend::Label;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use super::org::extract_access_data;
 
@@ -30,6 +44,8 @@ pub struct PromptClassification {
     pub label: Option<String>,
     pub precision: Option<f64>,
     pub model: Option<ModelObject>,
+    // stats of the model latency routing picked for the category
+    pub latency: Option<ModelHealth>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,21 +65,282 @@ pub struct SentenceMatching {
     pub model: Option<ModelObject>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingRuleMatch {
+    pub used: bool,
+    pub name: String,
+    pub model: Option<ModelObject>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankingStrategy {
+    SingleModel,
+    ZeroShotLabel,
+    ExactMatch,
+    SentenceSimilarity,
+    CostRank,
+    Rule,
+    Latency,
+}
+
+impl RankingStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RankingStrategy::SingleModel => "single_model",
+            RankingStrategy::ZeroShotLabel => "zero_shot_label",
+            RankingStrategy::ExactMatch => "exact_match",
+            RankingStrategy::SentenceSimilarity => "sentence_similarity",
+            RankingStrategy::CostRank => "cost_rank",
+            RankingStrategy::Rule => "rule",
+            RankingStrategy::Latency => "latency",
+        }
+    }
+
+    // zero-shot probabilities and cosine similarities aren't comparable, candidates
+    // are ranked by strategy first, in the order process_prompt tries them
+    fn tier(&self) -> u8 {
+        match self {
+            RankingStrategy::Rule => 0,
+            RankingStrategy::SingleModel => 1,
+            RankingStrategy::ZeroShotLabel | RankingStrategy::Latency => 2,
+            RankingStrategy::ExactMatch | RankingStrategy::SentenceSimilarity => 3,
+            RankingStrategy::CostRank => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedModel {
+    pub rank: usize,
+    pub score: f64,
+    pub strategy: RankingStrategy,
+    // category label or sentence text that produced the score
+    pub label: Option<String>,
+    pub input_cost_per_million_tokens: Option<f64>,
+    pub model: ModelObject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityUpgrade {
+    // model the router picked, it can't serve the request
+    pub from_model_id: String,
+    pub missing: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProccesedPrompt {
+    pub routing_rule: Option<RoutingRuleMatch>,
     pub single_model: Option<SingleModel>,
     pub prompt_calification: Option<PromptClassification>,
     pub sentence_matching: Option<SentenceMatching>,
+    pub ranked_models: Option<Vec<RankedModel>>,
+    pub capability_upgrade: Option<CapabilityUpgrade>,
 
     pub prompt: String,
     pub prompt_size: i32,
 }
 
+// what a routing decision ended on, kept for the usage ledger
+pub struct Decision<'a> {
+    pub model: &'a ModelObject,
+    pub strategy: RankingStrategy,
+    pub label: Option<String>,
+    pub score: Option<f64>,
+}
+
+impl ProccesedPrompt {
+    pub fn decision(&self) -> Option<Decision<'_>> {
+        if let Some(routing_rule) = &self.routing_rule {
+            if let Some(model) = &routing_rule.model {
+                return Some(Decision {
+                    model,
+                    strategy: RankingStrategy::Rule,
+                    label: Some(routing_rule.name.clone()),
+                    score: Some(1.0),
+                });
+            }
+        }
+
+        if let Some(ranked_models) = &self.ranked_models {
+            return ranked_models.first().map(|candidate| Decision {
+                model: &candidate.model,
+                strategy: candidate.strategy.clone(),
+                label: candidate.label.clone(),
+                score: Some(candidate.score),
+            });
+        }
+
+        if let Some(model) = self.single_model.as_ref().and_then(|single_model| single_model.model.as_ref()) {
+            return Some(Decision {
+                model,
+                strategy: RankingStrategy::SingleModel,
+                label: None,
+                score: Some(1.0),
+            });
+        }
+
+        if let Some(prompt_calification) = &self.prompt_calification {
+            if let Some(model) = &prompt_calification.model {
+                let strategy = match prompt_calification.latency {
+                    Some(_) => RankingStrategy::Latency,
+                    None => RankingStrategy::ZeroShotLabel,
+                };
+
+                return Some(Decision {
+                    model,
+                    strategy,
+                    label: prompt_calification.label.clone(),
+                    score: prompt_calification.precision,
+                });
+            }
+        }
+
+        if let Some(sentence_matching) = &self.sentence_matching {
+            if let Some(model) = &sentence_matching.model {
+                let (strategy, score) = match sentence_matching.exact {
+                    true => (RankingStrategy::ExactMatch, Some(1.0)),
+                    false => (RankingStrategy::SentenceSimilarity, sentence_matching.similarity_level.map(|level| level as f64)),
+                };
+
+                return Some(Decision {
+                    model,
+                    strategy,
+                    label: None,
+                    score,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+// caller of a routing decision, kept for the usage ledger
+struct RoutingUsage<'a> {
+    org_id: &'a str,
+    router_id: &'a str,
+    access_token: String,
+    // what the router is configured for, reported when no decision is made
+    strategy: RankingStrategy,
+    prompt: &'a str,
+    request_log: RequestLogSettings,
+    started: Instant,
+}
+
+struct RoutingOutcome {
+    // empty when the decision ended in an error
+    model_id: String,
+    strategy: RankingStrategy,
+    label: Option<String>,
+    score: Option<f64>,
+    status: String,
+    candidates: Vec<RequestLogCandidate>,
+}
+
+impl RoutingUsage<'_> {
+    fn record(&self, state: &AppState, outcome: RoutingOutcome) {
+        // the prompt is sent by the caller, its tokens are an estimate and nothing
+        // is billed for a routing decision, cost only counts provider spend
+        let prompt_tokens = estimate_tokens(self.prompt.len());
+        let latency_ms = self.started.elapsed().as_millis() as i64;
+        let now = chrono::Utc::now();
+
+        log_request(state, &self.request_log, RequestLogEntry {
+            id: String::new(),
+            org_id: self.org_id.to_string(),
+            router_id: self.router_id.to_string(),
+            kind: ROUTING_KIND.to_string(),
+            created_at: now.to_rfc3339(),
+            timestamp: now.timestamp_millis(),
+            prompt: self.prompt.to_string(),
+            prompt_redacted: false,
+            prompt_size: self.prompt.len() as i64,
+            model_id: outcome.model_id.clone(),
+            strategy: outcome.strategy.as_str().to_string(),
+            label: outcome.label.clone(),
+            score: outcome.score,
+            status: outcome.status.clone(),
+            latency_ms,
+            candidates: outcome.candidates,
+        });
+
+        state.usage_writer.record(UsageRecord {
+            created_at: now,
+            kind: ROUTING_KIND.to_string(),
+            org_id: self.org_id.to_string(),
+            router_id: self.router_id.to_string(),
+            access_token: self.access_token.clone(),
+            model_id: outcome.model_id.clone(),
+            strategy: outcome.strategy.as_str().to_string(),
+            category: outcome.label,
+            status: outcome.status,
+            prompt_tokens: prompt_tokens as i64,
+            completion_tokens: 0,
+            latency_ms,
+            cost: 0.0,
+            score: outcome.score,
+        });
+    }
+}
+
+fn routed(state: &AppState, usage: &RoutingUsage, data: ProccesedPrompt) -> (StatusCode, Json<GenericResponse>) {
+    if let Some(decision) = data.decision() {
+        // the last sentence is returned even when nothing matched, that's an abstention
+        let status = match &data.sentence_matching {
+            Some(sentence_matching) if !sentence_matching.appropiate_match => "no_match",
+            _ => "ok",
+        };
+
+        let candidates = match &data.ranked_models {
+            Some(ranked_models) => request_log_candidates(ranked_models),
+            None => vec![],
+        };
+
+        usage.record(state, RoutingOutcome {
+            model_id: decision.model.id.clone(),
+            strategy: decision.strategy,
+            label: decision.label,
+            score: decision.score,
+            status: status.to_string(),
+            candidates,
+        });
+    }
+
+    ok("ok", Some(serde_json::to_value(data).unwrap()))
+}
+
+pub fn request_log_candidates(ranked_models: &[RankedModel]) -> Vec<RequestLogCandidate> {
+    ranked_models
+        .iter()
+        .map(|candidate| RequestLogCandidate {
+            model_id: candidate.model.id.clone(),
+            strategy: candidate.strategy.as_str().to_string(),
+            score: candidate.score,
+            label: candidate.label.clone(),
+        })
+        .collect()
+}
+
+// decisions that end in an error are recorded with the error as status
+fn not_routed(state: &AppState, usage: &RoutingUsage, error: (StatusCode, Json<GenericResponse>)) -> (StatusCode, Json<GenericResponse>) {
+    usage.record(state, RoutingOutcome {
+        model_id: String::new(),
+        strategy: usage.strategy.clone(),
+        label: None,
+        score: None,
+        status: error.1.message.clone(),
+        candidates: vec![],
+    });
+    error
+}
+
 pub async fn process_prompt(
     headers: HeaderMap,
     payload_result: Result<Json<ProcessPrompt>, JsonRejection>,
     state: Arc<AppState>,
 ) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let started = Instant::now();
     let access_data = extract_access_data(&headers, &state).await?;
     let payload = payload_analyzer(payload_result)?;
 
@@ -113,40 +390,134 @@ pub async fn process_prompt(
         return Err(unauthorized("unauthorized.access.token.scopes", None));
     }
 
-    let router = match org.routers.into_iter().find(|r| r.id == router_id) {
+    let router = match org.routers.iter().find(|r| r.id == router_id) {
         Some(router) => {
             if router.active == false || router.deleted == true {
                 return Err(bad_request("router.not.found", None));
             }
 
-            router
+            router.clone()
         }
         None => {
             return Err(bad_request("router.not.found", None));
         }
     };
 
+    // only calls to an existing router count against the quota
+    let plan = org_plan(&state.mongo_db, &org).await?;
+    consume_monthly_routing_call(&state, &org, plan).await?;
+
+    let configured_strategy = match (router.use_single_model, router.use_prompt_calification_model) {
+        (true, _) => RankingStrategy::SingleModel,
+        (false, true) => RankingStrategy::ZeroShotLabel,
+        (false, false) => RankingStrategy::SentenceSimilarity,
+    };
+
+    let usage = RoutingUsage {
+        org_id,
+        router_id,
+        access_token: token_fingerprint(org_access_token),
+        strategy: configured_strategy,
+        prompt,
+        request_log: org.request_log.clone(),
+        started,
+    };
+
+    if payload.ranked.unwrap_or(false) {
+        if !router.use_single_model && (prompt.len() > router.max_prompt_length.try_into().unwrap() || prompt.len() < 1) {
+            return Err(not_routed(&state, &usage, bad_request("prompt.length.invalid", None)));
+        }
+
+        let mut ranked_models = match rank_router_models(&state, org_id, &org.models, &router, prompt, &payload.capabilities).await {
+            Ok(ranked_models) => ranked_models,
+            Err(e) => return Err(not_routed(&state, &usage, e)),
+        };
+        if ranked_models.is_empty() && !payload.capabilities.is_empty() {
+            return Err(not_routed(&state, &usage, bad_request("model.capabilities.unsupported", None)));
+        }
+
+        if let Some(max_candidates) = payload.max_candidates {
+            ranked_models.truncate(max_candidates);
+        }
+
+        let data = ProccesedPrompt {
+            routing_rule: None,
+            single_model: None,
+            prompt_calification: None,
+            sentence_matching: None,
+            ranked_models: Some(ranked_models),
+            capability_upgrade: None,
+            prompt: prompt.to_string(),
+            prompt_size: prompt.len().try_into().unwrap(),
+        };
+
+        return Ok(routed(&state, &usage, data));
+    }
+
+    match route_prompt(&state, org_id, &org.models, &router, prompt, &payload.capabilities).await {
+        Ok(data) => Ok(routed(&state, &usage, data)),
+        Err(e) => Err(not_routed(&state, &usage, e)),
+    }
+}
+
+// the decision shared by /api/llm/process and /v1/chat/completions: a matching
+// rule, the single model, the classified category, then the first matching sentence
+pub async fn route_prompt(
+    state: &AppState,
+    org_id: &str,
+    models: &[ModelObject],
+    router: &Router,
+    prompt: &str,
+    required: &RequiredCapabilities,
+) -> Result<ProccesedPrompt, (StatusCode, Json<GenericResponse>)> {
+    let mut selector = ModelSelector {
+        catalog: &state.model_catalog,
+        provider_settings: &state.provider_settings,
+        models,
+        fallback_model_ids: &router.fallback_model_ids,
+        required,
+        upgrade: None,
+    };
+
+    if let Some(rule) = matching_rule(state, org_id, router).await {
+        let selected_model_object = selector.select(&rule.model_id)?;
+
+        let data = ProccesedPrompt {
+            routing_rule: Some(RoutingRuleMatch {
+                used: true,
+                name: rule.name.clone(),
+                model: Some(selected_model_object.clone()),
+            }),
+            single_model: None,
+            prompt_calification: None,
+            sentence_matching: None,
+            ranked_models: None,
+            capability_upgrade: selector.upgrade.clone(),
+            prompt: prompt.to_string(),
+            prompt_size: prompt.len().try_into().unwrap(),
+        };
+
+        return Ok(data);
+    }
+
     if router.use_single_model {
-        let selected_model_object =
-            match org.models.iter().find(|model| model.id == router.model_id) {
-                Some(model) => model,
-                None => {
-                    return Err(bad_request("model.not.found", None));
-                }
-            };
+        let selected_model_object = selector.select(&router.model_id)?;
 
         let data = ProccesedPrompt {
+            routing_rule: None,
             single_model: Some(SingleModel {
                 used: true,
                 model: Some(selected_model_object.clone()),
             }),
             prompt_calification: None,
             sentence_matching: None,
+            ranked_models: None,
+            capability_upgrade: selector.upgrade.clone(),
             prompt: prompt.to_string(),
             prompt_size: prompt.len().try_into().unwrap(),
         };
 
-        return Ok(ok("ok", Some(serde_json::to_value(data).unwrap())));
+        return Ok(data);
     }
 
     if prompt.len() > router.max_prompt_length.try_into().unwrap() || prompt.len() < 1 {
@@ -154,42 +525,7 @@ pub async fn process_prompt(
     }
 
     if router.use_prompt_calification_model {
-        let model = match &state.llm_resources.prompt_classification_model.model {
-            Some(model) => match model.lock() {
-                Ok(model) => model,
-                Err(_) => {
-                    return Err(bad_request("prompt.calification.error", None));
-                }
-            },
-            None => {
-                return Err(bad_request("prompt.calification.error", None));
-            }
-        };
-
-        let input = [payload.prompt.as_deref().unwrap_or_default()];
-
-        let router_categories: &[Category] = &router.prompt_calification_model_categories;
-        let candidate_labels: Vec<&str> = router_categories
-            .iter()
-            .map(|category| category.label.as_str())
-            .collect();
-
-        let output = match model.predict_multilabel(
-            input,
-            candidate_labels,
-            Some(Box::new(|label: &str| format!("{label}"))),
-            128,
-        ) {
-            Ok(output) => output,
-            Err(_) => {
-                return Err(bad_request("prompt.calification.error", None));
-            }
-        };
-
-        let prompt_output = output[0].clone();
-
-        // unlock model to be used in other request
-        drop(model);
+        let prompt_output = classify_prompt(state, prompt, &router.prompt_calification_model_categories).await?;
 
         let (label_text, score) = prompt_output.iter().fold(("", 0.0), |acc, label| {
             if label.score > acc.1 {
@@ -199,54 +535,47 @@ pub async fn process_prompt(
             }
         });
 
-        for category in router.prompt_calification_model_categories {
-            if category.label == label_text {
-                let selected_model_object = match org
-                    .models
-                    .iter()
-                    .find(|model| model.id == category.model_id)
-                {
-                    Some(model) => model,
-                    None => {
-                        return Err(bad_request("model.not.found", None));
-                    }
-                };
+        let category = match router.prompt_calification_model_categories.iter().find(|category| category.label == label_text) {
+            Some(category) => category,
+            None => return Err(bad_request("prompt.calification.error", None)),
+        };
 
-                let data = ProccesedPrompt {
-                    single_model: None,
-                    prompt_calification: Some(PromptClassification {
-                        used: true,
-                        label: Some(label_text.to_string()),
-                        precision: Some(score),
-                        model: Some(selected_model_object.clone()),
-                    }),
-                    sentence_matching: None,
-                    prompt: prompt.to_string(),
-                    prompt_size: prompt.len().try_into().unwrap(),
-                };
+        let (model_id, latency) = match router.use_latency_routing {
+            true => latency_routed_model(state, org_id, category, router.latency_slo_ms).await,
+            false => (category.model_id.clone(), None),
+        };
 
-                return Ok(ok("ok", Some(serde_json::to_value(data).unwrap())));
-            }
+        let selected_model_object = selector.select(&model_id)?;
 
-            return Err(bad_request("prompt.calification.error", None));
-        }
+        let data = ProccesedPrompt {
+            routing_rule: None,
+            single_model: None,
+            prompt_calification: Some(PromptClassification {
+                used: true,
+                label: Some(label_text.to_string()),
+                precision: Some(score),
+                model: Some(selected_model_object.clone()),
+                latency,
+            }),
+            sentence_matching: None,
+            ranked_models: None,
+            capability_upgrade: selector.upgrade.clone(),
+            prompt: prompt.to_string(),
+            prompt_size: prompt.len().try_into().unwrap(),
+        };
+
+        return Ok(data);
     }
 
     if router.use_sentence_matching {
-        for (index, sentence) in router.sentences.iter().enumerate() {
-            let selected_model_object = match org
-                .models
-                .iter()
-                .find(|model| model.id == sentence.model_id)
-            {
-                Some(model) => model,
-                None => {
-                    return Err(bad_request("model.not.found", None));
-                }
-            };
+        let mut scorer = SentenceScorer::new(state, router, prompt);
 
+        for (index, sentence) in router.sentences.iter().enumerate() {
             if sentence.exact && sentence.text.to_lowercase() == prompt.to_lowercase() {
+                let selected_model_object = selector.select(&sentence.model_id)?;
+
                 let data = ProccesedPrompt {
+                    routing_rule: None,
                     single_model: None,
                     prompt_calification: None,
                     sentence_matching: Some(SentenceMatching {
@@ -258,58 +587,25 @@ pub async fn process_prompt(
                         appropiate_match: true,
                         model: Some(selected_model_object.clone()),
                     }),
+                    ranked_models: None,
+                    capability_upgrade: selector.upgrade.clone(),
                     prompt: prompt.to_string(),
                     prompt_size: prompt.len().try_into().unwrap(),
                 };
 
-                return Ok(ok("ok", Some(serde_json::to_value(data).unwrap())));
+                return Ok(data);
             } else if sentence.use_cosine_similarity {
-                let emb_model = match &state.llm_resources.embedding_model {
-                    Some(model) => model,
-                    None => {
-                        return Err(bad_request("sentence.matching.error", None));
-                    }
-                };
-
-                let (similar, score) = match detect_similar_sentences(
-                    emb_model,
-                    sentence.text.clone(),
-                    prompt.to_string(),
-                    sentence.cosine_similarity_temperature,
-                )
-                .await
-                {
-                    Ok((similar, score)) => (similar, score),
-                    Err(_) => {
-                        return Err(bad_request("sentence.matching.error", None));
-                    }
-                };
-
-                if index == router.sentences.len() - 1 && !similar {
-                    let data = ProccesedPrompt {
-                        single_model: None,
-                        prompt_calification: None,
-                        sentence_matching: Some(SentenceMatching {
-                            used: true,
-                            exact: false,
-                            cosine_similarity: true,
-                            similarity_level: Some(score),
-                            temperature: Some(sentence.cosine_similarity_temperature),
-                            appropiate_match: similar,
-                            model: Some(selected_model_object.clone()),
-                        }),
-                        prompt: prompt.to_string(),
-                        prompt_size: prompt.len().try_into().unwrap(),
-                    };
-
-                    return Ok(ok("ok", Some(serde_json::to_value(data).unwrap())));
-                }
+                let (similar, score, temperature) = scorer.score(sentence).await?;
 
-                if !similar {
+                // the last sentence answers even when it isn't similar
+                if !similar && index != router.sentences.len() - 1 {
                     continue;
                 }
 
+                let selected_model_object = selector.select(&sentence.model_id)?;
+
                 let data = ProccesedPrompt {
+                    routing_rule: None,
                     single_model: None,
                     prompt_calification: None,
                     sentence_matching: Some(SentenceMatching {
@@ -317,15 +613,17 @@ pub async fn process_prompt(
                         exact: false,
                         cosine_similarity: true,
                         similarity_level: Some(score),
-                        temperature: Some(sentence.cosine_similarity_temperature),
+                        temperature: Some(temperature),
                         appropiate_match: similar,
                         model: Some(selected_model_object.clone()),
                     }),
+                    ranked_models: None,
+                    capability_upgrade: selector.upgrade.clone(),
                     prompt: prompt.to_string(),
                     prompt_size: prompt.len().try_into().unwrap(),
                 };
 
-                return Ok(ok("ok", Some(serde_json::to_value(data).unwrap())));
+                return Ok(data);
             }
         }
     }
@@ -333,8 +631,448 @@ pub async fn process_prompt(
     return Err(bad_request("prompt.calification.error", None));
 }
 
+// longer than any provider timeout, most likely a client clock issue
+const MAX_REPORTED_LATENCY_MS: u64 = 10 * 60 * 1000;
+
+// clients calling providers themselves report how the routed model did, it feeds
+// the same stats as proxied calls
+pub async fn report_model_feedback(
+    headers: HeaderMap,
+    payload_result: Result<Json<ModelFeedback>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let org_access_token = match headers.get("Authorization").and_then(|value| value.to_str().ok()) {
+        Some(org_access_token) => org_access_token,
+        None => return Err(unauthorized("unauthorized.access.token", None)),
+    };
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    let required_scope = vec![
+        AccessTokenScopes::AccessPromptModelSuggestion,
+        AccessTokenScopes::Admin,
+    ];
+
+    if !org.access_tokens.iter().any(|access_token| {
+        access_token.token == org_access_token && 
+        access_token.scopes.iter().any(|scope| required_scope.contains(scope))
+    }) {
+        return Err(unauthorized("unauthorized.access.token.scopes", None));
+    }
+
+    if !org.models.iter().any(|model| model.id == payload.model_id) {
+        return Err(bad_request("model.not.found", None));
+    }
+
+    if payload.latency_ms > MAX_REPORTED_LATENCY_MS {
+        return Err(bad_request("feedback.latency_ms.invalid", None));
+    }
+
+    record_model_call(&state, &org.id, &payload.model_id, payload.latency_ms, payload.success).await;
+
+    return Ok(ok("ok", None));
+}
+
+// the category's own model and its alternatives, the category's model is kept
+// when every one of them is unhealthy
+async fn latency_routed_model(state: &AppState, org_id: &str, category: &Category, slo_ms: u32) -> (String, Option<ModelHealth>) {
+    let mut allowed = vec![category.model_id.clone()];
+    allowed.extend(category.alternative_model_ids.iter().cloned());
+
+    let mut health = model_health(state, org_id, &allowed).await;
+    let model_id = pick_by_latency(&health, &allowed, slo_ms).unwrap_or(&category.model_id).clone();
+    let latency = health.remove(&model_id);
+
+    (model_id, latency)
+}
+
+// development mode doesn't load the torch model, categories are scored by shared words instead
+async fn classify_prompt(
+    state: &AppState,
+    prompt: &str,
+    categories: &[Category],
+) -> Result<Vec<Label>, (StatusCode, Json<GenericResponse>)> {
+    let classification_model = &state.llm_resources.prompt_classification_model;
+    if !state.production && classification_model.slot.current().is_none() {
+        return Ok(lexical::classify(prompt, categories));
+    }
+
+    let candidate_labels: Vec<String> = categories
+        .iter()
+        .map(|category| category.label.clone())
+        .collect();
+
+    // batched together with other concurrent requests by the inference scheduler
+    match classification_model.scheduler.classify(prompt.to_string(), candidate_labels).await {
+        Ok(output) => Ok(output),
+        Err(_) => Err(bad_request("prompt.calification.error", None)),
+    }
+}
+
+// compares the prompt against the sentences of a router, the prompt is encoded
+// once, by the embedding model the router picked
+struct SentenceScorer<'a> {
+    embedding_model: Option<&'a EmbeddingModel>,
+    use_lexical_matching: bool,
+    sentence_texts: Vec<String>,
+    prompt: &'a str,
+    prompt_embedding: Option<Vec<f32>>,
+}
+
+impl<'a> SentenceScorer<'a> {
+    fn new(state: &'a AppState, router: &Router, prompt: &'a str) -> SentenceScorer<'a> {
+        let embedding_model = state.llm_resources.embedding_models.get(&router.embedding_model_id);
+
+        SentenceScorer {
+            embedding_model,
+            use_lexical_matching: !state.production
+                && embedding_model.map_or(true, |model| model.slot.current().is_none()),
+            sentence_texts: router.sentences.iter().map(|sentence| sentence.text.clone()).collect(),
+            prompt,
+            prompt_embedding: None,
+        }
+    }
+
+    // returns whether the sentence matched, its similarity and the threshold used
+    async fn score(&mut self, sentence: &Sentence) -> Result<(bool, f32, f32), (StatusCode, Json<GenericResponse>)> {
+        if self.use_lexical_matching {
+            let temperature = match self.embedding_model {
+                Some(model) => model.threshold(sentence.cosine_similarity_temperature),
+                None => sentence.cosine_similarity_temperature,
+            };
+
+            let (similar, score) = lexical::detect_similar_sentences(&self.sentence_texts, &sentence.text, self.prompt, temperature);
+            return Ok((similar, score, temperature));
+        }
+
+        let embedding_model = match self.embedding_model {
+            Some(model) => model,
+            None => {
+                return Err(bad_request("sentence.matching.error", None));
+            }
+        };
+
+        if self.prompt_embedding.is_none() {
+            self.prompt_embedding = match embedding_model.encode(&self.prompt.to_string()).await {
+                Ok(embedding) => Some(embedding),
+                Err(_) => {
+                    return Err(bad_request("sentence.matching.error", None));
+                }
+            };
+        }
+
+        let temperature = embedding_model.threshold(sentence.cosine_similarity_temperature);
+        match detect_similar_sentences(
+            embedding_model,
+            &sentence.text,
+            self.prompt_embedding.as_ref().unwrap(),
+            temperature,
+        )
+        .await
+        {
+            Ok((similar, score)) => Ok((similar, score, temperature)),
+            Err(_) => Err(bad_request("sentence.matching.error", None)),
+        }
+    }
+}
+
+fn model_input_cost(catalog: &ModelCatalog, model: &ModelObject) -> Option<f64> {
+    catalog.get(&model.id).map(|entry| entry.input_cost_per_million_tokens)
+}
+
+// the org model serving a routing decision, one missing a required capability is
+// swapped for the first compatible fallback model, then the cheapest compatible
+// one, only models the proxy can call are upgraded to
+struct ModelSelector<'a> {
+    catalog: &'a ModelCatalog,
+    provider_settings: &'a ProviderSettings,
+    models: &'a [ModelObject],
+    fallback_model_ids: &'a [String],
+    required: &'a RequiredCapabilities,
+    // set by the last select when it had to swap the model
+    upgrade: Option<CapabilityUpgrade>,
+}
+
+impl<'a> ModelSelector<'a> {
+    fn callable(&self, model: &ModelObject) -> bool {
+        self.catalog
+            .owner(&model.id)
+            .map_or(false, |owner| is_callable(&owner, &model.credential_id, self.provider_settings))
+    }
+
+    fn select(&mut self, model_id: &str) -> Result<&'a ModelObject, (StatusCode, Json<GenericResponse>)> {
+        self.upgrade = None;
+
+        let model = match self.models.iter().find(|model| model.id == model_id) {
+            Some(model) => model,
+            None => return Err(bad_request("model.not.found", None)),
+        };
+
+        if self.catalog.supports(&model.id, self.required) {
+            return Ok(model);
+        }
+
+        let mut cheapest: Vec<&'a ModelObject> = self.models
+            .iter()
+            .filter(|model| !self.fallback_model_ids.contains(&model.id))
+            .collect();
+        cheapest.sort_by(|a, b| {
+            let a_cost = model_input_cost(self.catalog, a).unwrap_or(f64::MAX);
+            let b_cost = model_input_cost(self.catalog, b).unwrap_or(f64::MAX);
+            a_cost.partial_cmp(&b_cost).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let upgraded = self.fallback_model_ids
+            .iter()
+            .filter_map(|fallback_id| self.models.iter().find(|model| &model.id == fallback_id))
+            .chain(cheapest)
+            .filter(|candidate| self.callable(candidate))
+            .find(|candidate| self.catalog.supports(&candidate.id, self.required));
+
+        let missing = self.required.missing(self.catalog.get(&model.id).as_ref());
+        match upgraded {
+            Some(upgraded) => {
+                self.upgrade = Some(CapabilityUpgrade {
+                    from_model_id: model.id.clone(),
+                    missing,
+                });
+                Ok(upgraded)
+            },
+            None => Err(bad_request("model.capabilities.unsupported", Some(json!({"missing": missing})))),
+        }
+    }
+}
+
+// a model referenced by several categories or sentences keeps its best strategy,
+// then its best score within it
+fn add_candidate(
+    candidates: &mut Vec<RankedModel>,
+    catalog: &ModelCatalog,
+    models: &[ModelObject],
+    model_id: &str,
+    score: f64,
+    strategy: RankingStrategy,
+    label: Option<String>,
+) {
+    let model = match models.iter().find(|model| model.id == model_id) {
+        Some(model) => model,
+        None => return,
+    };
+
+    if let Some(candidate) = candidates.iter_mut().find(|candidate| candidate.model.id == model_id) {
+        let better_tier = strategy.tier() < candidate.strategy.tier();
+        if better_tier || (strategy.tier() == candidate.strategy.tier() && score > candidate.score) {
+            candidate.score = score;
+            candidate.strategy = strategy;
+            candidate.label = label;
+        }
+        return;
+    }
+
+    candidates.push(RankedModel {
+        rank: 0,
+        score,
+        strategy,
+        label,
+        input_cost_per_million_tokens: model_input_cost(catalog, model),
+        model: model.clone(),
+    });
+}
+
+// every model the router can reach ordered by how well it fits the prompt, the
+// remaining org models are appended cheapest first so clients can fail over, a
+// routing rule that applies puts its model first
+pub async fn rank_router_models(
+    state: &AppState,
+    org_id: &str,
+    models: &[ModelObject],
+    router: &Router,
+    prompt: &str,
+    required: &RequiredCapabilities,
+) -> Result<Vec<RankedModel>, (StatusCode, Json<GenericResponse>)> {
+    let mut candidates: Vec<RankedModel> = vec![];
+    let model_ids: Vec<String> = models.iter().map(|model| model.id.clone()).collect();
+    let health = model_health(state, org_id, &model_ids).await;
+
+    if router.use_single_model {
+        add_candidate(&mut candidates, &state.model_catalog, models, &router.model_id, 1.0, RankingStrategy::SingleModel, None);
+    }
+
+    let categories = &router.prompt_calification_model_categories;
+    if !router.use_single_model && router.use_prompt_calification_model && !categories.is_empty() {
+        for label in classify_prompt(state, prompt, categories).await? {
+            if let Some(category) = categories.iter().find(|category| category.label == label.text) {
+                if router.use_latency_routing {
+                    let mut allowed = vec![category.model_id.clone()];
+                    allowed.extend(category.alternative_model_ids.iter().cloned());
+
+                    let model_id = pick_by_latency(&health, &allowed, router.latency_slo_ms).unwrap_or(&category.model_id);
+                    add_candidate(&mut candidates, &state.model_catalog, models, model_id, label.score, RankingStrategy::Latency, Some(label.text));
+                    continue;
+                }
+
+                add_candidate(&mut candidates, &state.model_catalog, models, &category.model_id, label.score, RankingStrategy::ZeroShotLabel, Some(label.text));
+            }
+        }
+    }
+
+    if !router.use_single_model && router.use_sentence_matching {
+        let mut scorer = SentenceScorer::new(state, router, prompt);
+
+        for sentence in &router.sentences {
+            if sentence.exact && sentence.text.to_lowercase() == prompt.to_lowercase() {
+                add_candidate(&mut candidates, &state.model_catalog, models, &sentence.model_id, 1.0, RankingStrategy::ExactMatch, Some(sentence.text.clone()));
+            } else if sentence.use_cosine_similarity {
+                // sentences under their threshold don't vote for their model
+                let (similar, score, _) = scorer.score(sentence).await?;
+                if !similar {
+                    continue;
+                }
+
+                add_candidate(&mut candidates, &state.model_catalog, models, &sentence.model_id, score as f64, RankingStrategy::SentenceSimilarity, Some(sentence.text.clone()));
+            }
+        }
+    }
+
+    for model in models {
+        add_candidate(&mut candidates, &state.model_catalog, models, &model.id, 0.0, RankingStrategy::CostRank, None);
+    }
+
+    // models that can't serve the request are left out, the next compatible one moves up
+    candidates.retain(|candidate| state.model_catalog.supports(&candidate.model.id, required));
+
+    // equal scores go to the cheaper model, custom models without pricing go last
+    candidates.sort_by(|a, b| {
+        a.strategy.tier()
+            .cmp(&b.strategy.tier())
+            .then_with(|| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal))
+            .then_with(|| match (a.input_cost_per_million_tokens, b.input_cost_per_million_tokens) {
+                (Some(a_cost), Some(b_cost)) => a_cost.partial_cmp(&b_cost).unwrap_or(std::cmp::Ordering::Equal),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            })
+    });
+
+    // unhealthy models keep their relative order behind every healthy one
+    candidates.sort_by_key(|candidate| !health.get(&candidate.model.id).map_or(true, |model| model.healthy));
+
+    if let Some(rule) = matching_rule(state, org_id, router).await {
+        if let Some(position) = candidates.iter().position(|candidate| candidate.model.id == rule.model_id) {
+            let mut candidate = candidates.remove(position);
+            candidate.score = 1.0;
+            candidate.strategy = RankingStrategy::Rule;
+            candidate.label = Some(rule.name.clone());
+            candidates.insert(0, candidate);
+        }
+    }
+
+    for (index, candidate) in candidates.iter_mut().enumerate() {
+        candidate.rank = index + 1;
+    }
+
+    Ok(candidates)
+}
+
+// disabled models are left out, platform admins see them through /api/admin/catalog
 pub async fn get_models_list(
+    Query(params): Query<ModelCatalogQueryParams>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let data: Vec<ModelInfo> = state.model_catalog
+        .all()
+        .into_iter()
+        .filter(|model| model.status != CatalogStatus::Disabled)
+        .filter(|model| params.vendor.as_ref().map_or(true, |vendor| &model.vendor == vendor))
+        .filter(|model| params.capability.as_ref().map_or(true, |capability| model.has_capability(capability)))
+        .map(ModelInfo::from)
+        .collect();
+
+    Ok(ok("ok", Some(serde_json::to_value(data).unwrap())))
+}
+
+pub async fn get_embedding_models_list(
+    state: Arc<AppState>,
 ) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
-    let data = LLMs::all_models_info();
+    let data = state.llm_resources.embedding_models.info();
     Ok(ok("ok", Some(serde_json::to_value(data).unwrap())))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::types::catalog::Modality;
+
+    fn provider_settings() -> ProviderSettings {
+        ProviderSettings {
+            openai_api_key: Some(String::from("platform-key")),
+            openai_api_base_url: String::new(),
+            anthropic_api_key: None,
+            anthropic_api_base_url: String::new(),
+            cohere_api_key: None,
+            cohere_api_base_url: String::new(),
+            request_timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn model(id: &str, credential_id: &str) -> ModelObject {
+        serde_json::from_value(json!({
+            "id": id,
+            "type": "legacy",
+            "display_name": id,
+            "registered_by": "owner",
+            "credential_id": credential_id,
+        }))
+        .unwrap()
+    }
+
+    // claude-2.1 sees images and is cheaper than gpt-4-vision, but there's no
+    // anthropic platform key
+    fn catalog() -> ModelCatalog {
+        let catalog = ModelCatalog::bundled().unwrap();
+        let mut claude = catalog.get("claude-2.1").unwrap();
+        claude.modalities.push(Modality::Image);
+        claude.input_cost_per_million_tokens = 0.0;
+        catalog.set_overrides(vec![claude]);
+        catalog
+    }
+
+    fn select(models: &[ModelObject]) -> Option<String> {
+        let catalog = catalog();
+        let provider_settings = provider_settings();
+        let required = RequiredCapabilities {
+            vision: true,
+            ..Default::default()
+        };
+        let mut selector = ModelSelector {
+            catalog: &catalog,
+            provider_settings: &provider_settings,
+            models,
+            fallback_model_ids: &[],
+            required: &required,
+            upgrade: None,
+        };
+
+        selector.select("gpt-4").ok().map(|model| model.id.clone())
+    }
+
+    #[test]
+    fn upgrades_only_to_callable_models() {
+        let models = [model("gpt-4", ""), model("claude-2.1", ""), model("gpt-4-vision", "")];
+        assert_eq!(select(&models).as_deref(), Some("gpt-4-vision"));
+
+        let models = [model("gpt-4", ""), model("claude-2.1", "credential"), model("gpt-4-vision", "")];
+        assert_eq!(select(&models).as_deref(), Some("claude-2.1"));
+
+        let models = [model("gpt-4", ""), model("claude-2.1", "")];
+        assert_eq!(select(&models), None);
+    }
+}
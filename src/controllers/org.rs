@@ -1,13 +1,13 @@
 use std::sync::Arc;
 
 use crate::{
-    storage::mongo::{build_organizations_filter, find_organization, get_organizations_collection, update_organization},
+    storage::mongo::{build_organizations_filter, find_organization, find_organization_snapshot, get_organizations_collection, update_organization, update_organization_if},
     types::{
-        customer::{CustomerID, GenericResponse}, incoming_requests::{CreateModel, CreateOrg, CreateRouter, EditModel, EditOrg, EditRouter, EditRouterPromptClassification, EditRouterSentenceMatching, EditRouterSingleModel, RemoveModel}, llms::{LLMs, ModelInfo}, organization::{MemberRole, ModelObject, ModelType, OrgMember, Organization}, router::{self, Router}, state::AppState
-    },
-    utilities::helpers::{
-        bad_request, internal_server_error, ok, payload_analyzer, random_string, unauthorized
+        customer::{CustomerID, GenericResponse}, incoming_requests::{CreateCredential, CreateModel, CreateOrg, CreateRouter, EditModel, EditOrg, EditRouter, EditRouterFallbackModels, EditRouterLatency, EditRouterPromptClassification, EditRouterRules, EditRouterSentenceMatching, EditRouterSingleModel, RemoveCredential, RemoveModel, RotateCredential}, catalog::{CatalogStatus, ModelCatalog}, organization::{MaskedCredential, MemberRole, ModelObject, ModelOwner, ModelType, OrgMember, Organization, ProviderCredential}, request_log::RequestLogSettings, router::{self, Router, RoutingRule}, state::AppState, webhook::WebhookEvent
     },
+    utilities::{helpers::{
+        bad_request, conflict, internal_server_error, ok, payload_analyzer, random_string, unauthorized
+    }, model_stats::{model_health, ModelHealth}, quotas::{check_quota, count_owned_orgs, customer_plan, org_plan, quota_exceeded}, vault::{encrypt_secret, mask_secret}, webhooks::emit_event},
 };
 
 use axum::{
@@ -16,7 +16,8 @@ use axum::{
     Json,
 };
 
-use mongodb::bson::{doc, Bson};
+use mongodb::bson::{doc, Bson, Document};
+use serde_json::{json, Value};
 
 use super::identity::{get_user_session_from_req,  SessionScopes};
 
@@ -65,6 +66,10 @@ pub async fn get_org(
         return Err(unauthorized("not.org.member", None));
     }
 
+    // owners list them masked through /credentials and /webhooks
+    org.credentials = vec![];
+    org.webhooks = vec![];
+
     let member = org.members.iter().find(|member| member.id == access_data.customer_id).unwrap();
     if member.role == MemberRole::Owner {
         return Ok(ok("ok", Some(serde_json::to_value(org).unwrap())));
@@ -86,6 +91,10 @@ pub async fn create_org(
         return Err(bad_request("org.name.length.invalid", None));
     }
 
+    let plan = customer_plan(&state.mongo_db, &session_data.customer_id).await?;
+    let owned_orgs = count_owned_orgs(&state.mongo_db, &session_data.customer_id).await?;
+    check_quota(plan, "orgs", owned_orgs, plan.limits().orgs)?;
+
     // create
     let id = random_string(32).await;
     let org = Organization {
@@ -98,6 +107,9 @@ pub async fn create_org(
             role: MemberRole::Owner,
         }],
         access_tokens: vec![],
+        credentials: vec![],
+        request_log: RequestLogSettings::default(),
+        webhooks: vec![],
         deleted: false,
     };
 
@@ -107,6 +119,14 @@ pub async fn create_org(
         Err(_) => return Err(internal_server_error("database.error", None)),
     }
 
+    for member in org.members.iter() {
+        emit_event(&state, &org, WebhookEvent::MemberChanged, json!({
+            "member_id": member.id,
+            "role": member.role,
+            "change": "added",
+        }));
+    }
+
     return Ok(ok("ok", Some(serde_json::to_value(org).unwrap())));
 }
 
@@ -182,6 +202,26 @@ pub async fn get_models(
     return Ok(ok("ok", Some(serde_json::to_value(org.models).unwrap())));
 }
 
+// rolling latency and error rate of every org model, from proxied calls and client feedback
+pub async fn get_models_health(
+    headers: HeaderMap,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member || member.role == MemberRole::Viewer)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    let model_ids: Vec<String> = org.models.iter().map(|model| model.id.clone()).collect();
+    let mut health = model_health(&state, &org.id, &model_ids).await;
+    let models: Vec<ModelHealth> = model_ids.iter().filter_map(|model_id| health.remove(model_id)).collect();
+
+    return Ok(ok("ok", Some(serde_json::to_value(models).unwrap())));
+}
+
 pub async fn create_model_org(
     headers: HeaderMap,
     payload_result: Result<Json<CreateModel>, JsonRejection>,
@@ -201,34 +241,28 @@ pub async fn create_model_org(
         return Err(unauthorized("not.org.member", None));
     }
 
-    let all_models_list = LLMs::all_models_info();
-    let mut model_type = ModelType::Legacy;
-    let mut model_info = ModelInfo {
-        model: "".to_string(),
-        company: None,
-        context_window: 0,
-        training_data: "",
+    let plan = org_plan(&state.mongo_db, &org).await?;
+    check_quota(plan, "models_per_org", org.models.len() as u64, plan.limits().models_per_org)?;
+
+    let catalog_model = state.model_catalog.get(&payload.id);
+    let model_type = match &catalog_model {
+        Some(model) if model.status == CatalogStatus::Disabled => return Err(bad_request("model.disabled", None)),
+        Some(_) => ModelType::Legacy,
+        None => ModelType::Custom,
     };
 
-    match all_models_list.iter().find(|model| model.model.clone() == payload.id) {
-        Some(model) => {
-            model_info = model.clone();
-        },
-        None => {
-            model_type = ModelType::Custom;
-        },
-    }
     if payload.display_name.len() < 1 || payload.display_name.len() > 32 {
         return Err(bad_request("model.display_name.length.invalid", None));
     }
 
     let model_object: ModelObject;
-    if model_type == ModelType::Legacy {
+    if let Some(catalog_model) = catalog_model {
         model_object = ModelObject {
-            id: model_info.model.clone(),
+            id: catalog_model.id.clone(),
             r#type: model_type,
-            display_name: model_info.model.clone(),
+            display_name: catalog_model.id.clone(),
             registered_by: access_data.customer_id.clone(),
+            credential_id: payload.credential_id.clone().unwrap_or_default(),
         };
     } else {
         if payload.display_name.len() < 1 || payload.display_name.len() > 32 {
@@ -240,28 +274,28 @@ pub async fn create_model_org(
             r#type: model_type,
             display_name: payload.display_name.clone(),
             registered_by: access_data.customer_id.clone(),
+            credential_id: payload.credential_id.clone().unwrap_or_default(),
         };
     }
 
-    let model_object_bson = doc! {
-        "id": model_object.id.clone(),
-        "type": model_object.r#type.clone(),
-        "display_name": model_object.display_name.clone(),
-        "registered_by": model_object.registered_by.clone(),
-    };
+    validate_model_credential(&state.model_catalog, &org, &model_object.id, &model_object.credential_id)?;
 
     if org.models.iter().any(|model| model.id == model_object.id) {
         return Err(bad_request("model.already.exist", None));
     }
 
     let update = doc! {"$push": {
-            "models": model_object_bson,
+            "models": model_object.clone(),
         }
     };
 
     let filter = build_organizations_filter(&access_data.org_id).await;
     update_organization(&state.mongo_db, filter, update).await?;
 
+    emit_event(&state, &org, WebhookEvent::ModelAdded, json!({
+        "model": model_object,
+    }));
+
     return Ok(ok("ok", Some(serde_json::to_value(model_object).unwrap())));
 }
 
@@ -278,7 +312,7 @@ pub async fn delete_model_org(
     }
 
     let filter = build_organizations_filter(&access_data.org_id).await;
-    let org = find_organization(&state.mongo_db, filter).await?;
+    let (org, snapshot) = find_organization_snapshot(&state.mongo_db, filter).await?;
 
     if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
         return Err(unauthorized("not.org.member", None));
@@ -288,15 +322,93 @@ pub async fn delete_model_org(
         return Err(bad_request("model.not.found", None));
     }
 
-    let update = doc! {"$pull": {
-            "models": doc!{"id": payload.id.clone()},
+    // deleted routers can't be routed to, they are rewritten but never block the delete
+    let dependents: Vec<Value> = org.routers
+        .iter()
+        .filter(|router| !router.deleted)
+        .filter_map(|router| match router.model_references(&payload.id) {
+            references if references.is_empty() => None,
+            references => Some(json!({
+                "id": router.id,
+                "name": router.name,
+                "references": references,
+            })),
+        })
+        .collect();
+
+    let replacement = match payload.replace_with.as_ref().filter(|replacement| !replacement.is_empty()) {
+        Some(replacement) => replacement,
+        None if dependents.is_empty() => {
+            let update = doc! {"$pull": {
+                    "models": doc!{"id": payload.id.clone()},
+                }
+            };
+
+            // a router may have started using the model since it was read
+            let filter = doc! {
+                "id": &org.id,
+                "routers": {"$not": {"$elemMatch": router::Router::model_reference_filter(&payload.id)}},
+            };
+
+            if !update_organization_if(&state.mongo_db, filter, update).await? {
+                return Err(conflict("model.in.use", None));
+            }
+
+            emit_event(&state, &org, WebhookEvent::ModelRemoved, json!({
+                "model_id": payload.id,
+            }));
+
+            return Ok(ok("ok", None));
         }
+        None => return Err(bad_request("model.in.use", Some(json!({"routers": dependents})))),
     };
 
-    let filter = build_organizations_filter(&access_data.org_id).await;
-    update_organization(&state.mongo_db, filter, update).await?;
+    if replacement == &payload.id || !org.models.iter().any(|model| &model.id == replacement) {
+        return Err(bad_request("model.replacement.invalid", None));
+    }
 
-    return Ok(ok("ok", None));
+    let mut routers = org.routers.clone();
+    for router in routers.iter_mut() {
+        router.replace_model(&payload.id, replacement);
+    }
+
+    // one update so routers never point at a model that's gone, and only over
+    // the routers the replacement was made from
+    let update = doc! {
+        "$pull": {
+            "models": doc!{"id": payload.id.clone()},
+        },
+        "$set": {
+            "routers": routers.iter().map(|router| <router::Router as Into<Bson>>::into(router.clone())).collect::<Vec<Bson>>(),
+        },
+    };
+
+    let filter = doc! {
+        "id": &org.id,
+        "models.id": &payload.id,
+        "routers": snapshot.get("routers").cloned().unwrap_or(Bson::Null),
+    };
+
+    if !update_organization_if(&state.mongo_db, filter, update).await? {
+        return Err(conflict("org.changed", None));
+    }
+
+    emit_event(&state, &org, WebhookEvent::ModelRemoved, json!({
+        "model_id": payload.id,
+        "replaced_with": replacement,
+    }));
+
+    for dependent in dependents.iter() {
+        emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+            "router_id": dependent["id"],
+            "change": "model_replaced",
+        }));
+    }
+
+    return Ok(ok("ok", Some(json!({
+        "replaced_with": replacement,
+        "routers": dependents,
+    }))));
 }
 
 pub async fn edit_model_org(
@@ -322,15 +434,36 @@ pub async fn edit_model_org(
         return Err(bad_request("model.not.found", None));
     }
 
+    if let Some(credential_id) = &payload.credential_id {
+        validate_model_credential(&state.model_catalog, &org, &payload.id, credential_id)?;
+    }
+
     let filter = doc! { 
         "id": org.id, 
         "models.id": payload.id.clone(),
     };
 
-    let update = doc! {
-        "$set": { 
-            "models.$.display_name": &payload.display_name,
+    // omitted fields keep their current value
+    let mut update_fields = doc! {};
+
+    if let Some(display_name) = &payload.display_name {
+        if display_name.len() < 1 || display_name.len() > 32 {
+            return Err(bad_request("model.display_name.length.invalid", None));
         }
+
+        update_fields.insert("models.$.display_name", display_name);
+    }
+
+    if let Some(credential_id) = &payload.credential_id {
+        update_fields.insert("models.$.credential_id", credential_id);
+    }
+
+    if update_fields.is_empty() {
+        return Ok(ok("ok", None));
+    }
+
+    let update = doc! {
+        "$set": update_fields,
     };
 
     update_organization(&state.mongo_db, filter, update).await?;
@@ -376,6 +509,10 @@ pub async fn create_router_org(
         return Err(bad_request("router.description.length.invalid", None));
     }
 
+    let plan = org_plan(&state.mongo_db, &org).await?;
+    let routers = org.routers.iter().filter(|router| !router.deleted).count() as u64;
+    check_quota(plan, "routers_per_org", routers, plan.limits().routers_per_org)?;
+
     let id = random_string(32).await;
     let router = Router {
         id: id.clone(),
@@ -395,6 +532,11 @@ pub async fn create_router_org(
 
         use_sentence_matching: false,
         sentences: vec![],
+        embedding_model_id: "".to_string(),
+        fallback_model_ids: vec![],
+        rules: vec![],
+        use_latency_routing: false,
+        latency_slo_ms: 0,
     };
 
     let update = doc! {"$push": {
@@ -408,6 +550,19 @@ pub async fn create_router_org(
     return Ok(ok("ok", Some(serde_json::to_value(router).unwrap())));
 }
 
+// models the router points at that the org doesn't have, sorted and unique
+fn missing_router_models<'a>(router: &'a Router, models: &[ModelObject]) -> Vec<&'a str> {
+    let mut missing: Vec<&str> = router
+        .referenced_model_ids()
+        .into_iter()
+        .filter(|model_id| !models.iter().any(|model| &model.id == model_id))
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    missing
+}
+
 pub async fn edit_router_org(
     headers: HeaderMap,
     payload_result: Result<Json<EditRouter>, JsonRejection>,
@@ -417,7 +572,7 @@ pub async fn edit_router_org(
     let payload = payload_analyzer(payload_result)?;
 
     let filter = build_organizations_filter(&access_data.org_id).await;
-    let org = find_organization(&state.mongo_db, filter).await?;
+    let (org, snapshot) = find_organization_snapshot(&state.mongo_db, filter).await?;
 
     if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
         return Err(unauthorized("not.org.member", None));
@@ -427,9 +582,10 @@ pub async fn edit_router_org(
         return Err(bad_request("router.id.required", None));
     }
 
-    if !org.routers.iter().any(|router| router.id == payload.id) {
-        return Err(bad_request("router.not.found", None));
-    }
+    let router = match org.routers.iter().find(|router| router.id == payload.id) {
+        Some(router) => router,
+        None => return Err(bad_request("router.not.found", None)),
+    };
 
     if payload.name.len() < 1 || payload.name.len() > 32 {
         return Err(bad_request("router.name.length.invalid", None));
@@ -439,21 +595,52 @@ pub async fn edit_router_org(
         return Err(bad_request("router.description.length.invalid", None));
     }
 
-    let filter = doc! { 
-        "id": org.id, 
+    let mut filter = doc! { 
+        "id": org.id.clone(), 
         "routers.id": payload.id.clone(),
     };
+    let mut path = String::from("routers.$");
+
+    // deleted routers aren't rewritten when a model is removed, a restored one
+    // must only point at models the org still has, both checks below hold only
+    // for the snapshot they were made on so the router is set by its position in it
+    if router.deleted && !payload.deleted {
+        let missing = missing_router_models(router, &org.models);
+        if !missing.is_empty() {
+            return Err(bad_request("model.not.found", Some(json!({"missing": missing}))));
+        }
+
+        // a restored router counts against the plan like a new one
+        let plan = org_plan(&state.mongo_db, &org).await?;
+        let routers = org.routers.iter().filter(|router| !router.deleted).count() as u64;
+        check_quota(plan, "routers_per_org", routers, plan.limits().routers_per_org)?;
+
+        let position = org.routers.iter().position(|router| router.id == payload.id).unwrap_or_default();
+        filter = doc! {
+            "id": org.id.clone(),
+            "models": snapshot.get("models").cloned().unwrap_or(Bson::Null),
+            "routers": snapshot.get("routers").cloned().unwrap_or(Bson::Null),
+        };
+        path = format!("routers.{}", position);
+    }
 
     let update = doc! {
         "$set": { 
-            "routers.$.name": &payload.name,
-            "routers.$.description": &payload.description,
-            "routers.$.active": payload.active,
-            "routers.$.deleted": payload.deleted,
+            format!("{}.name", path): &payload.name,
+            format!("{}.description", path): &payload.description,
+            format!("{}.active", path): payload.active,
+            format!("{}.deleted", path): payload.deleted,
         }
     };
 
-    update_organization(&state.mongo_db, filter, update).await?;
+    if !update_organization_if(&state.mongo_db, filter, update).await? {
+        return Err(conflict("org.changed", None));
+    }
+
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "settings",
+    }));
 
     return Ok(ok("ok", None));
 }
@@ -482,7 +669,7 @@ pub async fn edit_router_single_model_org(
     }
 
     let filter = doc! { 
-        "id": org.id, 
+        "id": org.id.clone(), 
         "routers.id": payload.id.clone(),
     };
 
@@ -503,9 +690,230 @@ pub async fn edit_router_single_model_org(
 
     update_organization(&state.mongo_db, filter, update).await?;
 
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "single_model",
+    }));
+
+    return Ok(ok("ok", None));
+}
+
+// at most MAX_FALLBACK_MODELS models, every one registered in the org
+pub const MAX_FALLBACK_MODELS: usize = 5;
+
+pub async fn edit_router_fallback_models_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<EditRouterFallbackModels>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    if payload.id == "" {
+        return Err(bad_request("router.id.required", None));
+    }
+
+    if !org.routers.iter().any(|router| router.id == payload.id) {
+        return Err(bad_request("router.not.found", None));
+    }
+
+    if payload.fallback_model_ids.len() > MAX_FALLBACK_MODELS {
+        return Err(bad_request("router.fallback_models.length.invalid", None));
+    }
+
+    for (index, model_id) in payload.fallback_model_ids.iter().enumerate() {
+        if !org.models.iter().any(|model| &model.id == model_id) {
+            return Err(bad_request("model.not.found", None));
+        }
+
+        if payload.fallback_model_ids[..index].contains(model_id) {
+            return Err(bad_request("router.fallback_models.duplicated", None));
+        }
+    }
+
+    let filter = doc! { 
+        "id": org.id.clone(), 
+        "routers.id": payload.id.clone(),
+    };
+
+    let update = doc! {
+        "$set": { 
+            "routers.$.fallback_model_ids": payload.fallback_model_ids.clone(),
+        }
+    };
+
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "fallback_models",
+    }));
+
+    return Ok(ok("ok", None));
+}
+
+// slo is the latency a category's model is expected to answer under
+pub const MAX_LATENCY_SLO_MS: u32 = 120_000;
+
+pub async fn edit_router_latency_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<EditRouterLatency>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    if payload.id == "" {
+        return Err(bad_request("router.id.required", None));
+    }
+
+    if !org.routers.iter().any(|router| router.id == payload.id) {
+        return Err(bad_request("router.not.found", None));
+    }
+
+    if payload.latency_slo_ms < 1 || payload.latency_slo_ms > MAX_LATENCY_SLO_MS {
+        return Err(bad_request("router.latency_slo_ms.invalid", None));
+    }
+
+    let filter = doc! { 
+        "id": org.id.clone(), 
+        "routers.id": payload.id.clone(),
+    };
+
+    let update = doc! {
+        "$set": { 
+            "routers.$.use_latency_routing": payload.use_latency_routing,
+            "routers.$.latency_slo_ms": payload.latency_slo_ms as i64,
+        }
+    };
+
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "latency",
+    }));
+
+    return Ok(ok("ok", None));
+}
+
+// at most MAX_ROUTING_RULES rules, offsets cover every timezone in use
+pub const MAX_ROUTING_RULES: usize = 10;
+const MIN_UTC_OFFSET_MINUTES: i32 = -12 * 60;
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+pub fn validate_routing_rule(models: &[ModelObject], rule: &RoutingRule) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if rule.name.len() < 1 || rule.name.len() > 32 {
+        return Err(bad_request("rule.name.length.invalid", None));
+    }
+
+    if !models.iter().any(|model| model.id == rule.model_id) {
+        return Err(bad_request("model.not.found", None));
+    }
+
+    let conditions = &rule.conditions;
+    if conditions.start_hour.is_none() && conditions.end_hour.is_none() && conditions.weekdays.is_empty() && conditions.min_monthly_spend.is_none() {
+        return Err(bad_request("rule.conditions.required", None));
+    }
+
+    if !conditions.timezone.is_empty() && conditions.timezone.parse::<chrono_tz::Tz>().is_err() {
+        return Err(bad_request("rule.timezone.invalid", None));
+    }
+
+    if conditions.utc_offset_minutes < MIN_UTC_OFFSET_MINUTES || conditions.utc_offset_minutes > MAX_UTC_OFFSET_MINUTES {
+        return Err(bad_request("rule.utc_offset_minutes.invalid", None));
+    }
+
+    if conditions.start_hour.map_or(false, |hour| hour > 23) || conditions.end_hour.map_or(false, |hour| hour > 24) {
+        return Err(bad_request("rule.hour.invalid", None));
+    }
+
+    if conditions.start_hour.is_some() && conditions.start_hour == conditions.end_hour {
+        return Err(bad_request("rule.hours.empty", None));
+    }
+
+    for (index, weekday) in conditions.weekdays.iter().enumerate() {
+        if *weekday > 6 || conditions.weekdays[..index].contains(weekday) {
+            return Err(bad_request("rule.weekdays.invalid", None));
+        }
+    }
+
+    if conditions.min_monthly_spend.map_or(false, |spend| !spend.is_finite() || spend < 0.0) {
+        return Err(bad_request("rule.min_monthly_spend.invalid", None));
+    }
+
+    Ok(())
+}
+
+pub async fn edit_router_rules_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<EditRouterRules>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    if payload.id == "" {
+        return Err(bad_request("router.id.required", None));
+    }
+
+    if !org.routers.iter().any(|router| router.id == payload.id) {
+        return Err(bad_request("router.not.found", None));
+    }
+
+    if payload.rules.len() > MAX_ROUTING_RULES {
+        return Err(bad_request("router.rules.length.invalid", None));
+    }
+
+    for rule in payload.rules.iter() {
+        validate_routing_rule(&org.models, rule)?;
+    }
+
+    let filter = doc! { 
+        "id": org.id.clone(), 
+        "routers.id": payload.id.clone(),
+    };
+
+    let update = doc! {
+        "$set": { 
+            "routers.$.rules": payload.rules.clone(),
+        }
+    };
+
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "rules",
+    }));
+
     return Ok(ok("ok", None));
 }
 
+// alternatives a category can be latency routed to besides its own model
+pub const MAX_ALTERNATIVE_MODELS: usize = 4;
+
 pub async fn edit_router_prompt_classification_org(
     headers: HeaderMap,
     payload_result: Result<Json<EditRouterPromptClassification>, JsonRejection>,
@@ -530,7 +938,7 @@ pub async fn edit_router_prompt_classification_org(
     }
 
     let filter = doc! { 
-        "id": org.id, 
+        "id": org.id.clone(), 
         "routers.id": payload.id.clone(),
     };
 
@@ -550,21 +958,43 @@ pub async fn edit_router_prompt_classification_org(
         if !org.models.iter().any(|model| model.id == category.model_id) {
             return Err(bad_request("model.not.found", None));
         }
-    }
 
-    let update = doc! {
-        "$set": { 
-            "routers.$.use_prompt_classification": payload.use_prompt_classification,
-            "routers.$.prompt_classification_categories": payload.prompt_classification_categories.clone(),
+        if category.alternative_model_ids.len() > MAX_ALTERNATIVE_MODELS {
+            return Err(bad_request("category.alternative_models.length.invalid", None));
         }
-    };
 
-    update_organization(&state.mongo_db, filter, update).await?;
+        for (index, model_id) in category.alternative_model_ids.iter().enumerate() {
+            if !org.models.iter().any(|model| &model.id == model_id) {
+                return Err(bad_request("model.not.found", None));
+            }
+
+            if model_id == &category.model_id || category.alternative_model_ids[..index].contains(model_id) {
+                return Err(bad_request("category.alternative_models.duplicated", None));
+            }
+        }
+    }
+
+    update_organization(&state.mongo_db, filter, prompt_classification_update(&payload)).await?;
+
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "prompt_classification",
+    }));
 
     return Ok(ok("ok", None));
 }
 
 
+// the payload names differ from the stored router fields
+fn prompt_classification_update(payload: &EditRouterPromptClassification) -> Document {
+    doc! {
+        "$set": {
+            "routers.$.use_prompt_calification_model": payload.use_prompt_classification,
+            "routers.$.prompt_calification_model_categories": payload.prompt_classification_categories.clone(),
+        }
+    }
+}
+
 pub async fn edit_router_sentence_matching_org(
     headers: HeaderMap,
     payload_result: Result<Json<EditRouterSentenceMatching>, JsonRejection>,
@@ -588,8 +1018,14 @@ pub async fn edit_router_sentence_matching_org(
         return Err(bad_request("router.not.found", None));
     }
 
+    let plan = org_plan(&state.mongo_db, &org).await?;
+    let sentences_limit = plan.limits().sentences_per_router;
+    if payload.sentence_matching_sentences.len() as u64 > sentences_limit {
+        return Err(quota_exceeded(plan, "sentences_per_router", payload.sentence_matching_sentences.len() as u64, sentences_limit));
+    }
+
     let filter = doc! { 
-        "id": org.id, 
+        "id": org.id.clone(), 
         "routers.id": payload.id.clone(),
     };
 
@@ -607,10 +1043,185 @@ pub async fn edit_router_sentence_matching_org(
         }
     }
 
+    let mut set = doc! {
+        "routers.$.use_sentence_matching": payload.use_sentence_matching,
+        "routers.$.sentences": payload.sentence_matching_sentences.clone(),
+    };
+
+    // omitting the embedding model keeps the one the router already uses
+    if let Some(embedding_model_id) = &payload.embedding_model_id {
+        if state.llm_resources.embedding_models.get(embedding_model_id).is_none() {
+            return Err(bad_request("embedding.model.not.found", None));
+        }
+
+        set.insert("routers.$.embedding_model_id", embedding_model_id);
+    } else if payload.use_sentence_matching {
+        let current = org.routers.iter().find(|router| router.id == payload.id).map(|router| router.embedding_model_id.clone()).unwrap_or_default();
+        if state.llm_resources.embedding_models.get(&current).is_none() {
+            return Err(bad_request("embedding.model.not.found", None));
+        }
+    }
+
+    let update = doc! { "$set": set };
+
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+        "router_id": payload.id,
+        "change": "sentence_matching",
+    }));
+
+    return Ok(ok("ok", None));
+}
+
+// legacy models can only use a credential of their own provider
+pub fn validate_model_credential(
+    catalog: &ModelCatalog,
+    org: &Organization,
+    model_id: &str,
+    credential_id: &str,
+) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if credential_id.is_empty() {
+        return Ok(());
+    }
+
+    let credential = match org.credentials.iter().find(|credential| credential.id == credential_id) {
+        Some(credential) => credential,
+        None => return Err(bad_request("credential.not.found", None)),
+    };
+
+    if let Some(owner) = catalog.owner(model_id) {
+        if owner != credential.provider {
+            return Err(bad_request("credential.provider.mismatch", None));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_api_key(api_key: &str) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if api_key.trim().len() < 8 || api_key.len() > 512 {
+        return Err(bad_request("credential.api_key.length.invalid", None));
+    }
+
+    Ok(())
+}
+
+pub async fn get_credentials(
+    headers: HeaderMap,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && member.role == MemberRole::Owner) {
+        return Err(unauthorized("not.org.owner", None));
+    }
+
+    let credentials: Vec<MaskedCredential> = org.credentials.iter().map(MaskedCredential::from).collect();
+
+    return Ok(ok("ok", Some(serde_json::to_value(credentials).unwrap())));
+}
+
+pub async fn create_credential_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<CreateCredential>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let encryption_key = match &state.credentials_encryption_key {
+        Some(key) => key,
+        None => return Err(internal_server_error("credentials.vault.disabled", None)),
+    };
+
+    if payload.name.len() < 1 || payload.name.len() > 32 {
+        return Err(bad_request("credential.name.length.invalid", None));
+    }
+
+    if payload.provider == ModelOwner::OpenSource {
+        return Err(bad_request("credential.provider.invalid", None));
+    }
+
+    validate_api_key(&payload.api_key)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && member.role == MemberRole::Owner) {
+        return Err(unauthorized("not.org.owner", None));
+    }
+
+    let encrypted_key = match encrypt_secret(encryption_key, payload.api_key.trim()) {
+        Ok(encrypted_key) => encrypted_key,
+        Err(_) => return Err(internal_server_error("credential.encryption.error", None)),
+    };
+
+    let credential = ProviderCredential {
+        id: random_string(32).await,
+        provider: payload.provider.clone(),
+        name: payload.name.clone(),
+        masked_key: mask_secret(payload.api_key.trim()),
+        encrypted_key,
+        created_by: access_data.customer_id.clone(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        rotated_at: None,
+    };
+
+    let update = doc! {"$push": {
+            "credentials": credential.clone(),
+        }
+    };
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    return Ok(ok("ok", Some(serde_json::to_value(MaskedCredential::from(&credential)).unwrap())));
+}
+
+pub async fn rotate_credential_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<RotateCredential>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let encryption_key = match &state.credentials_encryption_key {
+        Some(key) => key,
+        None => return Err(internal_server_error("credentials.vault.disabled", None)),
+    };
+
+    validate_api_key(&payload.api_key)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && member.role == MemberRole::Owner) {
+        return Err(unauthorized("not.org.owner", None));
+    }
+
+    if !org.credentials.iter().any(|credential| credential.id == payload.id) {
+        return Err(bad_request("credential.not.found", None));
+    }
+
+    let encrypted_key = match encrypt_secret(encryption_key, payload.api_key.trim()) {
+        Ok(encrypted_key) => encrypted_key,
+        Err(_) => return Err(internal_server_error("credential.encryption.error", None)),
+    };
+
+    let filter = doc! {
+        "id": org.id,
+        "credentials.id": payload.id.clone(),
+    };
+
     let update = doc! {
-        "$set": { 
-            "routers.$.use_sentence_matching": payload.use_sentence_matching,
-            "routers.$.sentences": payload.sentence_matching_sentences.clone(),
+        "$set": {
+            "credentials.$.encrypted_key": encrypted_key,
+            "credentials.$.masked_key": mask_secret(payload.api_key.trim()),
+            "credentials.$.rotated_at": chrono::Utc::now().to_rfc3339(),
         }
     };
 
@@ -619,6 +1230,41 @@ pub async fn edit_router_sentence_matching_org(
     return Ok(ok("ok", None));
 }
 
+pub async fn delete_credential_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<RemoveCredential>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && member.role == MemberRole::Owner) {
+        return Err(unauthorized("not.org.owner", None));
+    }
+
+    if !org.credentials.iter().any(|credential| credential.id == payload.id) {
+        return Err(bad_request("credential.not.found", None));
+    }
+
+    // models would silently fall back to the platform key otherwise
+    if org.models.iter().any(|model| model.credential_id == payload.id) {
+        return Err(bad_request("credential.in.use", None));
+    }
+
+    let update = doc! {"$pull": {
+            "credentials": doc!{"id": payload.id.clone()},
+        }
+    };
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    return Ok(ok("ok", None));
+}
+
 
 // org access template
 #[allow(dead_code)]
@@ -637,3 +1283,78 @@ pub async fn org_access_template_org(
 
     return Ok(ok("ok", None));
 }
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson;
+
+    use super::*;
+
+    // applies a positional $set the way mongo would to the matched router
+    fn apply_router_update(router: Router, update: &Document) -> Router {
+        let mut stored = match Into::<Bson>::into(router) {
+            Bson::Document(document) => document,
+            _ => unreachable!(),
+        };
+
+        for (path, value) in update.get_document("$set").unwrap() {
+            stored.insert(path.trim_start_matches("routers.$."), value.clone());
+        }
+
+        bson::from_document(stored).unwrap()
+    }
+
+    fn test_router() -> Router {
+        serde_json::from_value(json!({
+            "id": "router",
+            "name": "router",
+            "description": "",
+            "active": true,
+            "deleted": false,
+            "max_prompt_length": 1000,
+            "use_single_model": false,
+            "model_id": "gpt-4",
+            "use_prompt_calification_model": false,
+            "prompt_calification_model_categories": [],
+            "use_sentence_matching": false,
+            "sentences": [],
+        }))
+        .unwrap()
+    }
+
+    fn model(id: &str) -> ModelObject {
+        serde_json::from_value(json!({"id": id, "type": "legacy", "display_name": id, "registered_by": "owner"})).unwrap()
+    }
+
+    #[test]
+    fn prompt_classification_update_is_read_back() {
+        let router = test_router();
+        let payload: EditRouterPromptClassification = serde_json::from_value(json!({
+            "id": "router",
+            "use_prompt_classification": true,
+            "prompt_classification_categories": [{
+                "label": "code",
+                "description": "programming questions",
+                "model_id": "gpt-4",
+                "alternative_model_ids": ["gpt-3.5-turbo"],
+            }],
+        }))
+        .unwrap();
+
+        let router = apply_router_update(router, &prompt_classification_update(&payload));
+
+        assert!(router.use_prompt_calification_model);
+        assert_eq!(router.prompt_calification_model_categories.len(), 1);
+        assert_eq!(router.prompt_calification_model_categories[0].alternative_model_ids, vec!["gpt-3.5-turbo"]);
+    }
+
+    #[test]
+    fn lists_models_a_restored_router_would_miss() {
+        let mut router = test_router();
+        router.fallback_model_ids = vec![String::from("claude-2"), String::from("gpt-3.5-turbo")];
+        router.rules = serde_json::from_value(json!([{"name": "nights", "model_id": "claude-2", "conditions": {"start_hour": 0, "end_hour": 6}}])).unwrap();
+
+        assert_eq!(missing_router_models(&router, &[model("gpt-4")]), vec!["claude-2", "gpt-3.5-turbo"]);
+        assert!(missing_router_models(&router, &[model("gpt-4"), model("claude-2"), model("gpt-3.5-turbo")]).is_empty());
+    }
+}
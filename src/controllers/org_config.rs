@@ -0,0 +1,453 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use mongodb::bson::{doc, Bson};
+use serde_json::{json, Value};
+
+use crate::{
+    storage::mongo::{build_organizations_filter, find_organization, find_organization_snapshot, update_organization_if},
+    types::{
+        customer::{CustomerID, GenericResponse},
+        incoming_requests::OrgConfigQueryParams,
+        catalog::{CatalogStatus, ModelCatalog},
+        org_config::{ImportPlan, ModelConfig, OrgConfig, PlannedAction, PlannedChange, RouterConfig, ORG_CONFIG_VERSION},
+        organization::{MemberRole, ModelObject, ModelType, Organization},
+        router::Router,
+        state::AppState,
+        subscription::Slug,
+        webhook::WebhookEvent,
+    },
+    utilities::{
+        helpers::{bad_request, conflict, internal_server_error, ok, random_string, unauthorized},
+        quotas::{org_plan, quota_exceeded},
+        webhooks::emit_event,
+    },
+};
+
+use super::org::{
+    extract_access_data, validate_model_credential, validate_routing_rule, MAX_ALTERNATIVE_MODELS, MAX_FALLBACK_MODELS,
+    MAX_LATENCY_SLO_MS, MAX_ROUTING_RULES,
+};
+
+const MAX_PROMPT_LENGTH: i32 = 8192;
+
+#[derive(PartialEq)]
+enum ConfigFormat {
+    Json,
+    Yaml,
+}
+
+// the format query param wins, imports fall back to the content type
+fn config_format(params: &OrgConfigQueryParams, headers: &HeaderMap) -> Result<ConfigFormat, (StatusCode, Json<GenericResponse>)> {
+    match params.format.as_deref() {
+        Some("json") => return Ok(ConfigFormat::Json),
+        Some("yaml") | Some("yml") => return Ok(ConfigFormat::Yaml),
+        Some(_) => return Err(bad_request("config.format.invalid", None)),
+        None => (),
+    }
+
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or("");
+    match content_type.contains("yaml") {
+        true => Ok(ConfigFormat::Yaml),
+        false => Ok(ConfigFormat::Json),
+    }
+}
+
+fn export_config(org: &Organization) -> OrgConfig {
+    OrgConfig {
+        version: ORG_CONFIG_VERSION,
+        models: org.models.iter().map(ModelConfig::from).collect(),
+        routers: org.routers.iter().filter(|router| !router.deleted).map(RouterConfig::from).collect(),
+    }
+}
+
+pub async fn export_org_config(
+    headers: HeaderMap,
+    Query(params): Query<OrgConfigQueryParams>,
+    state: Arc<AppState>,
+) -> Result<Response, (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member || member.role == MemberRole::Viewer)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    let config = export_config(&org);
+    let (body, content_type, extension) = match config_format(&params, &HeaderMap::new())? {
+        ConfigFormat::Json => match serde_json::to_string_pretty(&config) {
+            Ok(body) => (body, "application/json", "json"),
+            Err(_) => return Err(internal_server_error("config.export.failed", None)),
+        },
+        ConfigFormat::Yaml => match serde_yaml::to_string(&config) {
+            Ok(body) => (body, "application/yaml", "yaml"),
+            Err(_) => return Err(internal_server_error("config.export.failed", None)),
+        },
+    };
+
+    let filename = format!("attachment; filename=\"org-{}.{}\"", org.id, extension);
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, filename)],
+        body,
+    ).into_response())
+}
+
+// validation errors of a router say which one failed, documents hold many
+fn in_router(name: &str, (status, Json(mut response)): (StatusCode, Json<GenericResponse>)) -> (StatusCode, Json<GenericResponse>) {
+    match response.data.as_object_mut() {
+        Some(data) => {
+            data.insert(String::from("router"), Value::String(name.to_string()));
+        }
+        None => response.data = json!({"router": name}),
+    }
+
+    (status, Json(response))
+}
+
+fn validate_model_config(model: &ModelConfig) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if model.id.len() < 1 || model.id.len() > 256 {
+        return Err(bad_request("model.id.length.invalid", Some(json!({"model_id": model.id}))));
+    }
+
+    if model.display_name.len() < 1 || model.display_name.len() > 32 {
+        return Err(bad_request("model.display_name.length.invalid", Some(json!({"model_id": model.id}))));
+    }
+
+    Ok(())
+}
+
+// same checks as the router PATCH endpoints, against the models the org ends up with
+fn validate_router_config(
+    state: &AppState,
+    models: &[ModelObject],
+    router: &RouterConfig,
+    plan: Slug,
+) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    let model_exists = |model_id: &String| models.iter().any(|model| &model.id == model_id);
+
+    if router.name.len() < 1 || router.name.len() > 32 {
+        return Err(bad_request("router.name.length.invalid", None));
+    }
+
+    if router.description.len() < 1 || router.description.len() > 128 {
+        return Err(bad_request("router.description.length.invalid", None));
+    }
+
+    if router.max_prompt_length < 1 || router.max_prompt_length > MAX_PROMPT_LENGTH {
+        return Err(bad_request("router.max_prompt_length.invalid", None));
+    }
+
+    if (router.use_single_model || !router.model_id.is_empty()) && !model_exists(&router.model_id) {
+        return Err(bad_request("model.not.found", Some(json!({"model_id": router.model_id}))));
+    }
+
+    for category in router.categories.iter() {
+        if category.label.len() < 1 || category.label.len() > 32 {
+            return Err(bad_request("category.label.length.invalid", None));
+        }
+
+        if category.description.len() < 1 || category.description.len() > 128 {
+            return Err(bad_request("category.description.length.invalid", None));
+        }
+
+        if !model_exists(&category.model_id) {
+            return Err(bad_request("model.not.found", Some(json!({"model_id": category.model_id}))));
+        }
+
+        if category.alternative_model_ids.len() > MAX_ALTERNATIVE_MODELS {
+            return Err(bad_request("category.alternative_models.length.invalid", None));
+        }
+
+        for (index, model_id) in category.alternative_model_ids.iter().enumerate() {
+            if !model_exists(model_id) {
+                return Err(bad_request("model.not.found", Some(json!({"model_id": model_id}))));
+            }
+
+            if model_id == &category.model_id || category.alternative_model_ids[..index].contains(model_id) {
+                return Err(bad_request("category.alternative_models.duplicated", None));
+            }
+        }
+    }
+
+    let sentences_limit = plan.limits().sentences_per_router;
+    if router.sentences.len() as u64 > sentences_limit {
+        return Err(quota_exceeded(plan, "sentences_per_router", router.sentences.len() as u64, sentences_limit));
+    }
+
+    for sentence in router.sentences.iter() {
+        if sentence.text.len() < 1 || sentence.text.len() > 128 {
+            return Err(bad_request("sentence.text.length.invalid", None));
+        }
+
+        if !model_exists(&sentence.model_id) {
+            return Err(bad_request("model.not.found", Some(json!({"model_id": sentence.model_id}))));
+        }
+    }
+
+    if state.llm_resources.embedding_models.get(&router.embedding_model_id).is_none() {
+        return Err(bad_request("embedding.model.not.found", None));
+    }
+
+    if router.fallback_model_ids.len() > MAX_FALLBACK_MODELS {
+        return Err(bad_request("router.fallback_models.length.invalid", None));
+    }
+
+    for (index, model_id) in router.fallback_model_ids.iter().enumerate() {
+        if !model_exists(model_id) {
+            return Err(bad_request("model.not.found", Some(json!({"model_id": model_id}))));
+        }
+
+        if router.fallback_model_ids[..index].contains(model_id) {
+            return Err(bad_request("router.fallback_models.duplicated", None));
+        }
+    }
+
+    if router.rules.len() > MAX_ROUTING_RULES {
+        return Err(bad_request("router.rules.length.invalid", None));
+    }
+
+    for rule in router.rules.iter() {
+        validate_routing_rule(models, rule)?;
+    }
+
+    if router.use_latency_routing && (router.latency_slo_ms < 1 || router.latency_slo_ms > MAX_LATENCY_SLO_MS) {
+        return Err(bad_request("router.latency_slo_ms.invalid", None));
+    }
+
+    Ok(())
+}
+
+// top level fields whose serialized values differ
+fn changed_fields<T: serde::Serialize>(current: &T, incoming: &T) -> Vec<String> {
+    let current = serde_json::to_value(current).unwrap_or(Value::Null);
+    let incoming = serde_json::to_value(incoming).unwrap_or(Value::Null);
+
+    match (current.as_object(), incoming.as_object()) {
+        (Some(current), Some(incoming)) => incoming
+            .iter()
+            .filter(|(field, value)| current.get(*field) != Some(*value))
+            .map(|(field, _)| field.clone())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn planned_change(action: PlannedAction, key: &str, changed_fields: Vec<String>) -> PlannedChange {
+    PlannedChange {
+        action,
+        key: key.to_string(),
+        changed_fields,
+    }
+}
+
+fn model_object(catalog: &ModelCatalog, config: &ModelConfig, current: Option<&ModelObject>, customer_id: &CustomerID) -> ModelObject {
+    // updates keep who registered the model, and its credential unless one is set
+    if let Some(current) = current {
+        let mut model = current.clone();
+        model.display_name = config.display_name.clone();
+        if !config.credential_id.is_empty() {
+            model.credential_id = config.credential_id.clone();
+        }
+        return model;
+    }
+
+    let model_type = match catalog.get(&config.id) {
+        Some(_) => ModelType::Legacy,
+        None => ModelType::Custom,
+    };
+
+    ModelObject {
+        id: config.id.clone(),
+        r#type: model_type,
+        display_name: config.display_name.clone(),
+        registered_by: customer_id.clone(),
+        credential_id: config.credential_id.clone(),
+    }
+}
+
+// plans the import against the org as it is now and applies it in one update
+// unless it's a dry run, prune deletes models and routers missing from the
+// document, deleted routers are only flagged so their usage stays readable.
+// The update only applies while models and routers are still what the plan was
+// made from, a concurrent change makes it fail with config.conflict
+pub async fn import_org_config(
+    headers: HeaderMap,
+    Query(params): Query<OrgConfigQueryParams>,
+    body: String,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let (org, snapshot) = find_organization_snapshot(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    let config: OrgConfig = match config_format(&params, &headers)? {
+        ConfigFormat::Json => serde_json::from_str(&body).map_err(|e| e.to_string()),
+        ConfigFormat::Yaml => serde_yaml::from_str(&body).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| bad_request("config.invalid", Some(json!({"error": e}))))?;
+
+    if config.version != ORG_CONFIG_VERSION {
+        return Err(bad_request("config.version.unsupported", Some(json!({"supported": ORG_CONFIG_VERSION}))));
+    }
+
+    let dry_run = params.dry_run.unwrap_or(false);
+    let prune = params.prune.unwrap_or(false);
+    let plan = org_plan(&state.mongo_db, &org).await?;
+
+    // models
+    let mut model_changes = vec![];
+    let mut models: Vec<ModelObject> = vec![];
+    for (index, model) in config.models.iter().enumerate() {
+        validate_model_config(model)?;
+        if config.models[..index].iter().any(|other| other.id == model.id) {
+            return Err(bad_request("config.model.duplicated", Some(json!({"model_id": model.id}))));
+        }
+
+        let current = org.models.iter().find(|current| current.id == model.id);
+        if current.is_none() && state.model_catalog.get(&model.id).map_or(false, |entry| entry.status == CatalogStatus::Disabled) {
+            return Err(bad_request("model.disabled", Some(json!({"model_id": model.id}))));
+        }
+
+        // same check as adding the model or setting its credential
+        if current.is_none() || !model.credential_id.is_empty() {
+            validate_model_credential(&state.model_catalog, &org, &model.id, &model.credential_id)
+                .map_err(|(status, Json(mut response))| {
+                    response.data = json!({"model_id": model.id});
+                    (status, Json(response))
+                })?;
+        }
+
+        let change = match current {
+            Some(current) => match changed_fields(&ModelConfig::from(current), model) {
+                fields if fields.is_empty() => planned_change(PlannedAction::Unchanged, &model.id, fields),
+                fields => planned_change(PlannedAction::Update, &model.id, fields),
+            },
+            None => planned_change(PlannedAction::Create, &model.id, vec![]),
+        };
+
+        model_changes.push(change);
+        models.push(model_object(&state.model_catalog, model, current, &access_data.customer_id));
+    }
+
+    for current in org.models.iter().filter(|current| !config.models.iter().any(|model| model.id == current.id)) {
+        match prune {
+            true => model_changes.push(planned_change(PlannedAction::Delete, &current.id, vec![])),
+            false => models.push(current.clone()),
+        }
+    }
+
+    let models_limit = plan.limits().models_per_org;
+    if models.len() as u64 > models_limit {
+        return Err(quota_exceeded(plan, "models_per_org", models.len() as u64, models_limit));
+    }
+
+    // routers
+    let mut router_changes = vec![];
+    let mut routers: Vec<Router> = org.routers.clone();
+    for (index, router) in config.routers.iter().enumerate() {
+        if config.routers[..index].iter().any(|other| other.name == router.name) {
+            return Err(bad_request("config.router.name.duplicated", Some(json!({"router": router.name}))));
+        }
+
+        validate_router_config(&state, &models, router, plan).map_err(|e| in_router(&router.name, e))?;
+
+        let matches: Vec<usize> = routers
+            .iter()
+            .enumerate()
+            .filter(|(_, current)| !current.deleted && current.name == router.name)
+            .map(|(position, _)| position)
+            .collect();
+
+        match matches.as_slice() {
+            [] => {
+                router_changes.push(planned_change(PlannedAction::Create, &router.name, vec![]));
+                routers.push(router.clone().into_router(random_string(32).await));
+            }
+            [position] => {
+                let current = &routers[*position];
+                let fields = changed_fields(&RouterConfig::from(current), router);
+                let action = match fields.is_empty() {
+                    true => PlannedAction::Unchanged,
+                    false => PlannedAction::Update,
+                };
+
+                router_changes.push(planned_change(action, &router.name, fields));
+                routers[*position] = router.clone().into_router(current.id.clone());
+            }
+            _ => return Err(bad_request("config.router.name.ambiguous", Some(json!({"router": router.name})))),
+        }
+    }
+
+    if prune {
+        for current in routers.iter_mut().filter(|current| !current.deleted && !config.routers.iter().any(|router| router.name == current.name)) {
+            router_changes.push(planned_change(PlannedAction::Delete, &current.name, vec![]));
+            current.deleted = true;
+        }
+    }
+
+    let active_routers = routers.iter().filter(|router| !router.deleted).count() as u64;
+    let routers_limit = plan.limits().routers_per_org;
+    if active_routers > routers_limit {
+        return Err(quota_exceeded(plan, "routers_per_org", active_routers, routers_limit));
+    }
+
+    let has_changes = model_changes.iter().chain(router_changes.iter()).any(|change| change.action != PlannedAction::Unchanged);
+    let mut import_plan = ImportPlan {
+        dry_run,
+        applied: false,
+        models: model_changes,
+        routers: router_changes,
+    };
+
+    if dry_run || !has_changes {
+        return Ok(ok("ok", Some(serde_json::to_value(import_plan).unwrap())));
+    }
+
+    let update = doc! {"$set": {
+            "models": models.iter().map(|model| <ModelObject as Into<Bson>>::into(model.clone())).collect::<Vec<Bson>>(),
+            "routers": routers.iter().map(|router| <Router as Into<Bson>>::into(router.clone())).collect::<Vec<Bson>>(),
+        }
+    };
+
+    let filter = doc! {
+        "id": &org.id,
+        "models": snapshot.get("models").cloned().unwrap_or(Bson::Null),
+        "routers": snapshot.get("routers").cloned().unwrap_or(Bson::Null),
+    };
+
+    if !update_organization_if(&state.mongo_db, filter, update).await? {
+        return Err(conflict("config.conflict", None));
+    }
+    import_plan.applied = true;
+
+    let router_ids: HashMap<&String, &String> = routers.iter().map(|router| (&router.name, &router.id)).collect();
+    for change in import_plan.models.iter() {
+        match change.action {
+            PlannedAction::Create => emit_event(&state, &org, WebhookEvent::ModelAdded, json!({"model_id": change.key})),
+            PlannedAction::Delete => emit_event(&state, &org, WebhookEvent::ModelRemoved, json!({"model_id": change.key})),
+            _ => (),
+        }
+    }
+
+    for change in import_plan.routers.iter().filter(|change| change.action != PlannedAction::Unchanged) {
+        emit_event(&state, &org, WebhookEvent::RouterUpdated, json!({
+            "router_id": router_ids.get(&change.key),
+            "change": "import",
+        }));
+    }
+
+    return Ok(ok("ok", Some(serde_json::to_value(import_plan).unwrap())));
+}
@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::JsonRejection, Query},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use futures_util::StreamExt;
+use log::error;
+use mongodb::{bson::{doc, Document}, options::FindOptions};
+
+use crate::{
+    storage::mongo::{build_organizations_filter, find_organization, get_request_logs_collection, update_organization},
+    types::{
+        customer::GenericResponse,
+        incoming_requests::{EditRequestLogSettings, RequestLogQueryParams},
+        organization::MemberRole,
+        request_log::{RequestLogEntry, RequestLogSettings},
+        state::AppState,
+    },
+    utilities::helpers::{bad_request, internal_server_error, ok, payload_analyzer, unauthorized},
+};
+
+use super::{admin::extract_platform_admin, org::extract_access_data, usage::parse_date};
+
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+const MAX_SEARCH_LIMIT: i64 = 200;
+const MAX_RETENTION_DAYS: u32 = 90;
+const MAX_SEARCH_TEXT_LENGTH: usize = 256;
+
+fn build_request_log_filter(org_id: &str, params: &RequestLogQueryParams) -> Result<Document, (StatusCode, Json<GenericResponse>)> {
+    let mut filter = doc! {"org_id": org_id};
+
+    if let Some(router_id) = params.router_id.as_ref().filter(|router_id| !router_id.is_empty()) {
+        filter.insert("router_id", router_id);
+    }
+
+    if let Some(model_id) = params.model_id.as_ref().filter(|model_id| !model_id.is_empty()) {
+        filter.insert("model_id", model_id);
+    }
+
+    if let Some(label) = params.label.as_ref().filter(|label| !label.is_empty()) {
+        filter.insert("label", label);
+    }
+
+    let mut timestamp = Document::new();
+    if let Some(from) = params.from.as_deref() {
+        match parse_date(from, false) {
+            Some(from) => timestamp.insert("$gte", from.timestamp_millis()),
+            None => return Err(bad_request("request.logs.from.invalid", None)),
+        };
+    }
+
+    if let Some(to) = params.to.as_deref() {
+        match parse_date(to, true) {
+            Some(to) => timestamp.insert("$lt", to.timestamp_millis()),
+            None => return Err(bad_request("request.logs.to.invalid", None)),
+        };
+    }
+
+    // pages walk backwards from the newest entry
+    let before_id = params.before_id.as_ref().filter(|before_id| !before_id.is_empty());
+    match (params.before, before_id) {
+        (Some(before), Some(before_id)) => {
+            filter.insert("$or", vec![
+                doc! {"timestamp": {"$lt": before}},
+                doc! {"timestamp": before, "id": {"$lt": before_id}},
+            ]);
+        }
+        (Some(before), None) => {
+            let before = match timestamp.get_i64("$lt") {
+                Ok(to) => to.min(before),
+                Err(_) => before,
+            };
+            timestamp.insert("$lt", before);
+        }
+        (None, _) => (),
+    }
+
+    if !timestamp.is_empty() {
+        filter.insert("timestamp", timestamp);
+    }
+
+    if let Some(text) = params.q.as_ref().filter(|text| !text.is_empty()) {
+        if text.len() > MAX_SEARCH_TEXT_LENGTH {
+            return Err(bad_request("request.logs.q.length.invalid", None));
+        }
+
+        filter.insert("prompt", doc! {"$regex": regex::escape(text), "$options": "i"});
+    }
+
+    Ok(filter)
+}
+
+async fn search_request_logs(
+    state: &AppState,
+    org_id: &str,
+    params: &RequestLogQueryParams,
+) -> Result<Vec<RequestLogEntry>, (StatusCode, Json<GenericResponse>)> {
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    if limit < 1 || limit > MAX_SEARCH_LIMIT {
+        return Err(bad_request("request.logs.limit.invalid", None));
+    }
+
+    let filter = build_request_log_filter(org_id, params)?;
+    let options = FindOptions::builder()
+        .sort(doc! {"timestamp": -1, "id": -1})
+        .limit(limit)
+        .build();
+
+    let collection = get_request_logs_collection(&state.mongo_db).await;
+    let mut cursor = match collection.find(filter, options).await {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            error!("error searching request logs: {}", e);
+            return Err(internal_server_error("database.error", None));
+        }
+    };
+
+    let mut entries = vec![];
+    while let Some(entry) = cursor.next().await {
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                error!("error reading request log: {}", e);
+                return Err(internal_server_error("database.error", None));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+pub async fn get_request_logs(
+    headers: HeaderMap,
+    Query(params): Query<RequestLogQueryParams>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    // prompts are customer data, viewers only get the aggregated analytics
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    let entries = search_request_logs(&state, &org.id, &params).await?;
+
+    return Ok(ok("ok", Some(serde_json::to_value(entries).unwrap())));
+}
+
+// support engineers investigating a ticket search any org's logs
+pub async fn get_request_logs_admin(
+    headers: HeaderMap,
+    Query(params): Query<RequestLogQueryParams>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    extract_platform_admin(&headers, &state).await?;
+
+    let org_id = match params.org_id.as_ref() {
+        Some(org_id) if !org_id.is_empty() => org_id.clone(),
+        _ => return Err(bad_request("org.id.required", None)),
+    };
+
+    let entries = search_request_logs(&state, &org_id, &params).await?;
+
+    return Ok(ok("ok", Some(serde_json::to_value(entries).unwrap())));
+}
+
+pub async fn edit_request_log_settings_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<EditRequestLogSettings>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && member.role == MemberRole::Owner) {
+        return Err(unauthorized("not.org.owner", None));
+    }
+
+    if !(0.0..=1.0).contains(&payload.sample_rate) {
+        return Err(bad_request("request.log.sample_rate.invalid", None));
+    }
+
+    if payload.retention_days < 1 || payload.retention_days > MAX_RETENTION_DAYS {
+        return Err(bad_request("request.log.retention_days.invalid", None));
+    }
+
+    let settings = RequestLogSettings {
+        enabled: payload.enabled,
+        sample_rate: payload.sample_rate,
+        redact_prompts: payload.redact_prompts,
+        retention_days: payload.retention_days,
+    };
+
+    let update = doc! {"$set": {
+            "request_log": settings.clone(),
+        }
+    };
+
+    update_organization(&state.mongo_db, build_organizations_filter(&org.id).await, update).await?;
+
+    return Ok(ok("ok", Some(serde_json::to_value(settings).unwrap())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(before: Option<i64>, before_id: Option<&str>) -> RequestLogQueryParams {
+        RequestLogQueryParams {
+            org_id: None,
+            router_id: None,
+            model_id: None,
+            label: None,
+            from: None,
+            to: None,
+            q: None,
+            limit: None,
+            before,
+            before_id: before_id.map(String::from),
+        }
+    }
+
+    #[test]
+    fn pages_entries_of_the_same_millisecond_by_id() {
+        let filter = build_request_log_filter("org", &params(Some(1000), Some("b"))).unwrap();
+        assert_eq!(filter, doc! {
+            "org_id": "org",
+            "$or": [
+                {"timestamp": {"$lt": 1000_i64}},
+                {"timestamp": 1000_i64, "id": {"$lt": "b"}},
+            ],
+        });
+
+        let filter = build_request_log_filter("org", &params(Some(1000), None)).unwrap();
+        assert_eq!(filter, doc! {"org_id": "org", "timestamp": {"$lt": 1000_i64}});
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::rejection::JsonRejection,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mongodb::bson::{doc, Bson};
+use serde_json::json;
+
+use crate::{
+    storage::mongo::{build_organizations_filter, find_organization, update_organization},
+    types::{
+        customer::GenericResponse,
+        incoming_requests::CreateRouterFromTemplate,
+        organization::{MemberRole, Organization},
+        router::{self, Category, Router, Sentence},
+        router_template::RouterTemplate,
+        state::AppState,
+    },
+    utilities::{
+        helpers::{bad_request, not_found, ok, payload_analyzer, random_string, unauthorized},
+        quotas::{check_quota, org_plan, quota_exceeded},
+    },
+};
+
+use super::org::extract_access_data;
+
+pub async fn get_router_templates(
+    headers: HeaderMap,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    extract_access_data(&headers, &state).await?;
+
+    return Ok(ok("ok", Some(serde_json::to_value(RouterTemplate::all()).unwrap())));
+}
+
+// slots the request binds must point at org models, the rest take the first
+// suggested model the org has registered
+fn bind_template_slots(
+    org: &Organization,
+    template: &RouterTemplate,
+    bindings: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, (StatusCode, Json<GenericResponse>)> {
+    for slot_id in bindings.keys() {
+        if !template.slots.iter().any(|slot| slot.id == slot_id) {
+            return Err(bad_request("template.slot.not.found", Some(json!({"slot": slot_id}))));
+        }
+    }
+
+    let mut bound = HashMap::new();
+    for slot in template.slots.iter() {
+        let model_id = match bindings.get(slot.id) {
+            Some(model_id) => {
+                if !org.models.iter().any(|model| &model.id == model_id) {
+                    return Err(bad_request("model.not.found", Some(json!({"slot": slot.id, "model_id": model_id}))));
+                }
+
+                model_id.clone()
+            }
+            None => match slot.suggested_model_ids.iter().find(|model_id| org.models.iter().any(|model| &model.id == *model_id)) {
+                Some(model_id) => model_id.to_string(),
+                None => {
+                    return Err(bad_request("template.slot.unbound", Some(json!({
+                        "slot": slot.id,
+                        "suggested_model_ids": slot.suggested_model_ids,
+                    }))));
+                }
+            },
+        };
+
+        bound.insert(slot.id.to_string(), model_id);
+    }
+
+    Ok(bound)
+}
+
+pub async fn create_router_from_template_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<CreateRouterFromTemplate>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(&headers, &state).await?;
+    let payload = payload_analyzer(payload_result)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    let template = match RouterTemplate::find(&payload.template_id) {
+        Some(template) => template,
+        None => return Err(not_found("template.not.found", None)),
+    };
+
+    let name = payload.name.clone().unwrap_or(template.name.to_string());
+    if name.len() < 1 || name.len() > 32 {
+        return Err(bad_request("router.name.length.invalid", None));
+    }
+
+    let description = payload.description.clone().unwrap_or(template.description.to_string());
+    if description.len() < 1 || description.len() > 128 {
+        return Err(bad_request("router.description.length.invalid", None));
+    }
+
+    let plan = org_plan(&state.mongo_db, &org).await?;
+    let routers = org.routers.iter().filter(|router| !router.deleted).count() as u64;
+    check_quota(plan, "routers_per_org", routers, plan.limits().routers_per_org)?;
+
+    let sentences_limit = plan.limits().sentences_per_router;
+    if template.sentences.len() as u64 > sentences_limit {
+        return Err(quota_exceeded(plan, "sentences_per_router", template.sentences.len() as u64, sentences_limit));
+    }
+
+    let bindings = bind_template_slots(&org, &template, &payload.bindings)?;
+
+    let categories: Vec<Category> = template.categories
+        .iter()
+        .map(|category| Category {
+            label: category.label.to_string(),
+            description: category.description.to_string(),
+            model_id: bindings[category.slot].clone(),
+            alternative_model_ids: vec![],
+        })
+        .collect();
+
+    // thresholds are left to the embedding model's default
+    let sentences: Vec<Sentence> = template.sentences
+        .iter()
+        .map(|sentence| Sentence {
+            text: sentence.text.to_string(),
+            exact: false,
+            use_cosine_similarity: true,
+            cosine_similarity_temperature: 0.0,
+            model_id: bindings[sentence.slot].clone(),
+        })
+        .collect();
+
+    let mut fallback_model_ids: Vec<String> = vec![];
+    for slot in template.fallback_slots.iter() {
+        if !fallback_model_ids.contains(&bindings[*slot]) {
+            fallback_model_ids.push(bindings[*slot].clone());
+        }
+    }
+
+    let router = Router {
+        id: random_string(32).await,
+        name,
+        description,
+
+        active: true,
+        deleted: false,
+
+        max_prompt_length: 512,
+
+        use_single_model: false,
+        model_id: "".to_string(),
+
+        use_prompt_calification_model: !categories.is_empty(),
+        prompt_calification_model_categories: categories,
+
+        use_sentence_matching: !sentences.is_empty(),
+        sentences,
+        embedding_model_id: "".to_string(),
+        fallback_model_ids,
+        rules: vec![],
+        use_latency_routing: false,
+        latency_slo_ms: 0,
+    };
+
+    let update = doc! {"$push": {
+            "routers": <router::Router as Into<Bson>>::into(router.clone()),
+        }
+    };
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    return Ok(ok("ok", Some(json!({
+        "router": router,
+        "template_id": template.id,
+        "bindings": bindings,
+    }))));
+}
@@ -0,0 +1,274 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use log::error;
+use tokio::task;
+
+use crate::{
+    storage::{
+        diesel_postgres::usage::{find_usage_buckets, UsageBucket, UsageGranularity, UsageGroup, UsageQuery},
+        mongo::{build_organizations_filter, find_organization},
+    },
+    types::{
+        customer::GenericResponse,
+        incoming_requests::UsageQueryParams,
+        organization::MemberRole,
+        state::AppState,
+        usage::{UsageGroupTotals, UsagePoint, UsageReport, UsageTimeSeries, UsageTotals},
+    },
+    utilities::helpers::{bad_request, internal_server_error, ok, unauthorized},
+};
+
+use super::org::extract_access_data;
+
+pub const DEFAULT_RANGE_DAYS: i64 = 30;
+pub const MAX_DAILY_RANGE_DAYS: i64 = 366;
+pub const MAX_HOURLY_RANGE_DAYS: i64 = 31;
+
+struct ParsedUsageQuery {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    kind: Option<String>,
+    group: UsageGroup,
+    group_name: String,
+    granularity: UsageGranularity,
+    csv: bool,
+}
+
+// plain dates start at midnight, end dates move to the next midnight so the day is included
+pub fn parse_date(value: &str, end: bool) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let date = match end {
+        true => date.succ_opt()?,
+        false => date,
+    };
+
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+fn parse_usage_query(params: UsageQueryParams) -> Result<ParsedUsageQuery, (StatusCode, Json<GenericResponse>)> {
+    let to = match params.to.as_deref() {
+        Some(to) => match parse_date(to, true) {
+            Some(to) => to,
+            None => return Err(bad_request("usage.to.invalid", None)),
+        },
+        None => Utc::now(),
+    };
+
+    let from = match params.from.as_deref() {
+        Some(from) => match parse_date(from, false) {
+            Some(from) => from,
+            None => return Err(bad_request("usage.from.invalid", None)),
+        },
+        None => to - Duration::days(DEFAULT_RANGE_DAYS),
+    };
+
+    let group_name = params.group_by.unwrap_or(String::from("model"));
+    let group = match UsageGroup::parse(&group_name) {
+        Some(group) => group,
+        None => return Err(bad_request("usage.group_by.invalid", None)),
+    };
+
+    let granularity = match UsageGranularity::parse(params.granularity.as_deref().unwrap_or("day")) {
+        Some(granularity) => granularity,
+        None => return Err(bad_request("usage.granularity.invalid", None)),
+    };
+
+    let max_range = match granularity {
+        UsageGranularity::Day => Duration::days(MAX_DAILY_RANGE_DAYS),
+        UsageGranularity::Hour => Duration::days(MAX_HOURLY_RANGE_DAYS),
+    };
+
+    if from >= to || to - from > max_range {
+        return Err(bad_request("usage.range.invalid", None));
+    }
+
+    let kind = match params.kind.as_deref() {
+        None | Some("") => None,
+        Some(kind) if kind == "routing" || kind == "completion" => Some(kind.to_string()),
+        Some(_) => return Err(bad_request("usage.kind.invalid", None)),
+    };
+
+    let csv = match params.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(_) => return Err(bad_request("usage.format.invalid", None)),
+    };
+
+    Ok(ParsedUsageQuery {
+        from,
+        to,
+        kind,
+        group,
+        group_name,
+        granularity,
+        csv,
+    })
+}
+
+async fn load_usage_buckets(
+    headers: &HeaderMap,
+    params: UsageQueryParams,
+    state: &Arc<AppState>,
+) -> Result<(String, ParsedUsageQuery, Vec<UsageBucket>), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(headers, state).await?;
+    let query = parse_usage_query(params)?;
+
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && (member.role == MemberRole::Owner || member.role == MemberRole::Member || member.role == MemberRole::Viewer)) {
+        return Err(unauthorized("not.org.member", None));
+    }
+
+    let pool = match &state.postgres_conn {
+        Some(pool) => pool.clone(),
+        None => return Err(bad_request("usage.metering.disabled", None)),
+    };
+
+    let usage_query = UsageQuery {
+        org_id: org.id.clone(),
+        from: query.from,
+        to: query.to,
+        kind: query.kind.clone(),
+        group: query.group,
+        granularity: query.granularity,
+    };
+
+    let result = task::spawn_blocking(move || find_usage_buckets(&pool, &usage_query)).await;
+
+    match result {
+        Ok(Ok(buckets)) => Ok((org.id, query, buckets)),
+        Ok(Err(e)) => {
+            error!("failed to load usage of org {}: {}", org.id, e);
+            Err(internal_server_error("usage.query.failed", None))
+        }
+        Err(_) => Err(internal_server_error("usage.query.failed", None)),
+    }
+}
+
+fn csv_response(org_id: &str, query: &ParsedUsageQuery, header_row: &[&str], rows: Vec<Vec<String>>) -> Response {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let _ = writer.write_record(header_row);
+    for row in rows {
+        let _ = writer.write_record(&row);
+    }
+
+    let body = match writer.into_inner() {
+        Ok(body) => body,
+        Err(_) => return internal_server_error("usage.export.failed", None).into_response(),
+    };
+
+    let filename = format!(
+        "attachment; filename=\"usage-{}-{}-{}.csv\"",
+        org_id,
+        query.from.format("%Y%m%d"),
+        query.to.format("%Y%m%d"),
+    );
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, String::from("text/csv; charset=utf-8")), (header::CONTENT_DISPOSITION, filename)],
+        body,
+    ).into_response()
+}
+
+fn totals_columns(totals: &UsageTotals) -> Vec<String> {
+    vec![
+        totals.calls.to_string(),
+        totals.errors.to_string(),
+        totals.prompt_tokens.to_string(),
+        totals.completion_tokens.to_string(),
+        format!("{:.2}", totals.average_latency_ms),
+        format!("{:.6}", totals.cost),
+    ]
+}
+
+// totals for the whole range, one entry per router, model, access token or category
+pub async fn get_usage(
+    headers: HeaderMap,
+    Query(params): Query<UsageQueryParams>,
+    state: Arc<AppState>,
+) -> Result<Response, (StatusCode, Json<GenericResponse>)> {
+    let (org_id, query, buckets) = load_usage_buckets(&headers, params, &state).await?;
+
+    let mut totals = UsageTotals::default();
+    let mut groups: BTreeMap<String, UsageTotals> = BTreeMap::new();
+    for bucket in &buckets {
+        totals.add(bucket, &state.model_catalog);
+        groups.entry(bucket.group_key.clone()).or_default().add(bucket, &state.model_catalog);
+    }
+
+    if query.csv {
+        let rows = groups
+            .iter()
+            .map(|(key, totals)| [vec![key.clone()], totals_columns(totals)].concat())
+            .collect();
+
+        return Ok(csv_response(&org_id, &query, &[query.group_name.as_str(), "calls", "errors", "prompt_tokens", "completion_tokens", "average_latency_ms", "cost_usd"], rows));
+    }
+
+    let report = UsageReport {
+        from: query.from.to_rfc3339(),
+        to: query.to.to_rfc3339(),
+        group_by: query.group_name.clone(),
+        granularity: query.granularity.as_str().to_string(),
+        totals,
+        groups: groups
+            .into_iter()
+            .map(|(key, totals)| UsageGroupTotals { key, totals })
+            .collect(),
+    };
+
+    Ok(ok("ok", Some(serde_json::to_value(report).unwrap())).into_response())
+}
+
+// one point per day or hour and group, periods without calls are left out
+pub async fn get_usage_timeseries(
+    headers: HeaderMap,
+    Query(params): Query<UsageQueryParams>,
+    state: Arc<AppState>,
+) -> Result<Response, (StatusCode, Json<GenericResponse>)> {
+    let (org_id, query, buckets) = load_usage_buckets(&headers, params, &state).await?;
+
+    let mut points: BTreeMap<(DateTime<Utc>, String), UsageTotals> = BTreeMap::new();
+    for bucket in &buckets {
+        points.entry((bucket.period, bucket.group_key.clone())).or_default().add(bucket, &state.model_catalog);
+    }
+
+    if query.csv {
+        let rows = points
+            .iter()
+            .map(|((period, key), totals)| [vec![period.to_rfc3339(), key.clone()], totals_columns(totals)].concat())
+            .collect();
+
+        return Ok(csv_response(&org_id, &query, &["period", query.group_name.as_str(), "calls", "errors", "prompt_tokens", "completion_tokens", "average_latency_ms", "cost_usd"], rows));
+    }
+
+    let series = UsageTimeSeries {
+        from: query.from.to_rfc3339(),
+        to: query.to.to_rfc3339(),
+        group_by: query.group_name.clone(),
+        granularity: query.granularity.as_str().to_string(),
+        points: points
+            .into_iter()
+            .map(|((period, key), totals)| UsagePoint {
+                period: period.to_rfc3339(),
+                key,
+                totals,
+            })
+            .collect(),
+    };
+
+    Ok(ok("ok", Some(serde_json::to_value(series).unwrap())).into_response())
+}
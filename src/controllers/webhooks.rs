@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::JsonRejection, Query},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use futures_util::StreamExt;
+use log::error;
+use mongodb::{bson::doc, options::FindOptions};
+use serde_json::json;
+
+use crate::{
+    storage::mongo::{build_organizations_filter, find_organization, get_webhook_deliveries_collection, update_organization},
+    types::{
+        customer::GenericResponse,
+        incoming_requests::{CreateWebhook, RedeliverWebhook, RemoveWebhook, WebhookDeliveryQueryParams},
+        organization::{MemberRole, Organization},
+        state::AppState,
+        webhook::{MaskedWebhook, Webhook},
+    },
+    utilities::{
+        helpers::{bad_request, internal_server_error, not_found, ok, payload_analyzer, random_string, unauthorized},
+        vault::{encrypt_secret, mask_secret},
+        webhooks::{dispatch, new_delivery, resolve_webhook_host},
+    },
+};
+
+use super::org::{extract_access_data, AccessData};
+
+const MAX_WEBHOOKS_PER_ORG: usize = 10;
+const DEFAULT_DELIVERIES_LIMIT: i64 = 50;
+const MAX_DELIVERIES_LIMIT: i64 = 200;
+
+// webhooks carry a signing secret, only owners manage them
+async fn find_owned_organization(
+    headers: &HeaderMap,
+    state: &Arc<AppState>,
+) -> Result<(AccessData, Organization), (StatusCode, Json<GenericResponse>)> {
+    let access_data = extract_access_data(headers, state).await?;
+    let filter = build_organizations_filter(&access_data.org_id).await;
+    let org = find_organization(&state.mongo_db, filter).await?;
+
+    if !org.members.iter().any(|member| member.id == access_data.customer_id && member.role == MemberRole::Owner) {
+        return Err(unauthorized("not.org.owner", None));
+    }
+
+    Ok((access_data, org))
+}
+
+// the host has to resolve to public addresses only, deliveries check it again
+async fn validate_webhook_url(url: &str) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if url.len() > 2048 {
+        return Err(bad_request("webhook.url.length.invalid", None));
+    }
+
+    let url = match reqwest::Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return Err(bad_request("webhook.url.invalid", None)),
+    };
+
+    if url.scheme() != "https" || url.host_str().is_none() {
+        return Err(bad_request("webhook.url.https.required", None));
+    }
+
+    if let Err(e) = resolve_webhook_host(&url).await {
+        return Err(bad_request(e, None));
+    }
+
+    Ok(())
+}
+
+pub async fn get_webhooks(
+    headers: HeaderMap,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let (_, org) = find_owned_organization(&headers, &state).await?;
+    let webhooks: Vec<MaskedWebhook> = org.webhooks.iter().map(MaskedWebhook::from).collect();
+
+    return Ok(ok("ok", Some(serde_json::to_value(webhooks).unwrap())));
+}
+
+pub async fn create_webhook_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<CreateWebhook>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let payload = payload_analyzer(payload_result)?;
+    let (access_data, org) = find_owned_organization(&headers, &state).await?;
+
+    let encryption_key = match &state.credentials_encryption_key {
+        Some(key) => key,
+        None => return Err(internal_server_error("credentials.vault.disabled", None)),
+    };
+
+    validate_webhook_url(&payload.url).await?;
+
+    if payload.events.is_empty() {
+        return Err(bad_request("webhook.events.required", None));
+    }
+
+    if org.webhooks.len() >= MAX_WEBHOOKS_PER_ORG {
+        return Err(bad_request("webhook.limit.reached", None));
+    }
+
+    let mut events = vec![];
+    for event in payload.events.iter() {
+        if !events.contains(event) {
+            events.push(*event);
+        }
+    }
+
+    // the secret is only shown in this response, the list masks it
+    let secret = format!("whsec_{}", random_string(32).await);
+    let encrypted_secret = match encrypt_secret(encryption_key, &secret) {
+        Ok(encrypted_secret) => encrypted_secret,
+        Err(_) => return Err(internal_server_error("webhook.secret.encryption.error", None)),
+    };
+
+    let webhook = Webhook {
+        id: random_string(32).await,
+        url: payload.url.clone(),
+        events,
+        encrypted_secret: Some(encrypted_secret),
+        masked_secret: mask_secret(&secret),
+        secret: None,
+        created_by: access_data.customer_id.clone(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let update = doc! {"$push": {
+            "webhooks": webhook.clone(),
+        }
+    };
+
+    let filter = build_organizations_filter(&org.id).await;
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    let mut created = serde_json::to_value(MaskedWebhook::from(&webhook)).unwrap();
+    created["secret"] = json!(secret);
+
+    return Ok(ok("ok", Some(created)));
+}
+
+pub async fn delete_webhook_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<RemoveWebhook>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let payload = payload_analyzer(payload_result)?;
+    let (_, org) = find_owned_organization(&headers, &state).await?;
+
+    if !org.webhooks.iter().any(|webhook| webhook.id == payload.id) {
+        return Err(bad_request("webhook.not.found", None));
+    }
+
+    let update = doc! {"$pull": {
+            "webhooks": doc!{"id": payload.id.clone()},
+        }
+    };
+
+    let filter = build_organizations_filter(&org.id).await;
+    update_organization(&state.mongo_db, filter, update).await?;
+
+    return Ok(ok("ok", None));
+}
+
+pub async fn get_webhook_deliveries(
+    headers: HeaderMap,
+    Query(params): Query<WebhookDeliveryQueryParams>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let (_, org) = find_owned_organization(&headers, &state).await?;
+
+    let limit = params.limit.unwrap_or(DEFAULT_DELIVERIES_LIMIT);
+    if limit < 1 || limit > MAX_DELIVERIES_LIMIT {
+        return Err(bad_request("webhook.deliveries.limit.invalid", None));
+    }
+
+    let mut filter = doc! {"org_id": &org.id};
+    if let Some(webhook_id) = params.webhook_id.as_ref().filter(|webhook_id| !webhook_id.is_empty()) {
+        filter.insert("webhook_id", webhook_id);
+    }
+
+    let options = FindOptions::builder()
+        .sort(doc! {"timestamp": -1})
+        .limit(limit)
+        .build();
+
+    let collection = get_webhook_deliveries_collection(&state.mongo_db).await;
+    let mut cursor = match collection.find(filter, options).await {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            error!("error listing webhook deliveries: {}", e);
+            return Err(internal_server_error("database.error", None));
+        }
+    };
+
+    let mut deliveries = vec![];
+    while let Some(delivery) = cursor.next().await {
+        match delivery {
+            Ok(delivery) => deliveries.push(delivery),
+            Err(e) => {
+                error!("error reading webhook delivery: {}", e);
+                return Err(internal_server_error("database.error", None));
+            }
+        }
+    }
+
+    return Ok(ok("ok", Some(serde_json::to_value(deliveries).unwrap())));
+}
+
+// sends the original body again as a new delivery, signed with the current secret
+pub async fn redeliver_webhook_org(
+    headers: HeaderMap,
+    payload_result: Result<Json<RedeliverWebhook>, JsonRejection>,
+    state: Arc<AppState>,
+) -> Result<(StatusCode, Json<GenericResponse>), (StatusCode, Json<GenericResponse>)> {
+    let payload = payload_analyzer(payload_result)?;
+    let (_, org) = find_owned_organization(&headers, &state).await?;
+
+    let collection = get_webhook_deliveries_collection(&state.mongo_db).await;
+    let delivery = match collection.find_one(doc! {"id": &payload.delivery_id, "org_id": &org.id}, None).await {
+        Ok(Some(delivery)) => delivery,
+        Ok(None) => return Err(not_found("webhook.delivery.not.found", None)),
+        Err(e) => {
+            error!("error finding webhook delivery: {}", e);
+            return Err(internal_server_error("database.error", None));
+        }
+    };
+
+    let webhook = match org.webhooks.iter().find(|webhook| webhook.id == delivery.webhook_id) {
+        Some(webhook) => webhook,
+        None => return Err(bad_request("webhook.not.found", None)),
+    };
+
+    let redelivery = new_delivery(&org.id, &webhook.id, delivery.event, delivery.payload, Some(delivery.id));
+    dispatch(&state, webhook.clone(), redelivery.clone());
+
+    return Ok(ok("ok", Some(serde_json::to_value(redelivery).unwrap())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_internal_webhook_hosts() {
+        for url in ["https://127.0.0.1/hook", "https://localhost/hook", "https://169.254.169.254/latest", "https://10.0.0.8/hook", "https://[::1]/hook", "https://[fd00::1]/hook"] {
+            let (status, Json(response)) = validate_webhook_url(url).await.unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(response.message, "webhook.url.address.forbidden", "{}", url);
+        }
+
+        let (_, Json(response)) = validate_webhook_url("http://example.com/hook").await.unwrap_err();
+        assert_eq!(response.message, "webhook.url.https.required");
+    }
+}
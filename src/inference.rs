@@ -0,0 +1,6 @@
+pub mod backend;
+pub mod lexical;
+pub mod registry;
+pub mod reload;
+pub mod scheduler;
+pub mod slot;
\ No newline at end of file
@@ -0,0 +1,89 @@
+//
+// rust-bert types used across the api. Builds without the torch feature don't
+// link libtorch, the models can't be loaded there and routers fall back to the
+// lexical scorer, so only development mode is available
+//
+
+#[cfg(feature = "torch")]
+pub use rust_bert::{
+    pipelines::{
+        sentence_embeddings::{SentenceEmbeddingsModel, SentenceEmbeddingsModelType},
+        sequence_classification::Label,
+        zero_shot_classification::ZeroShotClassificationModel,
+    },
+    RustBertError,
+};
+
+#[cfg(not(feature = "torch"))]
+pub use lexical_only::*;
+
+#[cfg(not(feature = "torch"))]
+mod lexical_only {
+    use std::fmt;
+
+    #[derive(Debug, Clone)]
+    pub struct Label {
+        pub text: String,
+        pub score: f64,
+        pub id: i64,
+        pub sentence: usize,
+    }
+
+    #[derive(Debug)]
+    pub enum RustBertError {
+        TchError(String),
+        ValueError(String),
+        InvalidConfigurationError(String),
+    }
+
+    impl fmt::Display for RustBertError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RustBertError::TchError(message) => write!(f, "Tch tensor error: {}", message),
+                RustBertError::ValueError(message) => write!(f, "Value error: {}", message),
+                RustBertError::InvalidConfigurationError(message) => write!(f, "Invalid configuration error: {}", message),
+            }
+        }
+    }
+
+    impl std::error::Error for RustBertError {}
+
+    // kept so model names are still validated when parsing the configuration
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum SentenceEmbeddingsModelType {
+        AllMiniLmL12V2,
+        AllMiniLmL6V2,
+        AllDistilrobertaV1,
+        DistiluseBaseMultilingualCased,
+        BertBaseNliMeanTokens,
+        ParaphraseAlbertSmallV2,
+        SentenceT5Base,
+    }
+
+    // no values exist, model slots stay empty in these builds
+    pub enum ZeroShotClassificationModel {}
+
+    impl ZeroShotClassificationModel {
+        pub fn predict_multilabel<'a, S, T>(
+            &self,
+            _inputs: S,
+            _labels: T,
+            _template: Option<Box<dyn Fn(&str) -> String>>,
+            _max_length: usize,
+        ) -> Result<Vec<Vec<Label>>, RustBertError>
+        where
+            S: AsRef<[&'a str]>,
+            T: AsRef<[&'a str]>,
+        {
+            match *self {}
+        }
+    }
+
+    pub enum SentenceEmbeddingsModel {}
+
+    impl SentenceEmbeddingsModel {
+        pub fn encode<S: AsRef<str>>(&self, _inputs: &[S]) -> Result<Vec<Vec<f32>>, RustBertError> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//
+// TF-IDF similarity used instead of the torch models in development mode, it
+// only looks at shared words so scores are rougher, but the routing flow works
+// end to end without loading libtorch models
+//
+
+use std::collections::{HashMap, HashSet};
+
+use super::backend::Label;
+
+use crate::types::router::Category;
+
+const STOP_WORDS: [&str; 32] = [
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "how", "i", "in", "is", "it", "me",
+    "my", "of", "on", "or", "please", "that", "the", "this", "to", "was", "what", "with", "you", "your", "can", "do",
+];
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.len() > 1 && !STOP_WORDS.contains(token))
+        .map(|token| token.to_string())
+        .collect()
+}
+
+struct TfIdf {
+    idf: HashMap<String, f32>,
+}
+
+impl TfIdf {
+    // the corpus is the set of candidate texts the prompt is compared against
+    fn new(corpus: &[Vec<String>]) -> TfIdf {
+        let mut document_frequency: HashMap<String, usize> = HashMap::new();
+        for document in corpus {
+            let unique: HashSet<&String> = document.iter().collect();
+            for token in unique {
+                *document_frequency.entry(token.clone()).or_insert(0) += 1;
+            }
+        }
+
+        let documents = corpus.len() as f32;
+        let idf = document_frequency
+            .into_iter()
+            .map(|(token, frequency)| (token, ((1.0 + documents) / (1.0 + frequency as f32)).ln() + 1.0))
+            .collect();
+
+        TfIdf { idf }
+    }
+
+    fn vector(&self, tokens: &[String]) -> HashMap<String, f32> {
+        let mut vector: HashMap<String, f32> = HashMap::new();
+        for token in tokens {
+            // words outside the corpus can't match anything, they only add to the norm
+            let idf = self.idf.get(token).copied().unwrap_or(1.0);
+            *vector.entry(token.clone()).or_insert(0.0) += idf;
+        }
+
+        vector
+    }
+}
+
+fn cosine_similarity(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let dot_product: f32 = a.iter().map(|(token, weight)| weight * b.get(token).unwrap_or(&0.0)).sum();
+    let norm_a = a.values().map(|weight| weight.powi(2)).sum::<f32>().sqrt();
+    let norm_b = b.values().map(|weight| weight.powi(2)).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+
+    dot_product / (norm_a * norm_b)
+}
+
+// scores the prompt against "label description" of every category, same output
+// shape as ZeroShotClassificationModel::predict_multilabel for a single input
+pub fn classify(prompt: &str, categories: &[Category]) -> Vec<Label> {
+    let documents: Vec<Vec<String>> = categories
+        .iter()
+        .map(|category| tokenize(&format!("{} {}", category.label, category.description)))
+        .collect();
+
+    let tf_idf = TfIdf::new(&documents);
+    let prompt_vector = tf_idf.vector(&tokenize(prompt));
+
+    documents
+        .iter()
+        .zip(categories)
+        .enumerate()
+        .map(|(id, (document, category))| Label {
+            text: category.label.clone(),
+            score: cosine_similarity(&prompt_vector, &tf_idf.vector(document)) as f64,
+            id: id as i64,
+            sentence: 0,
+        })
+        .collect()
+}
+
+// same contract as helpers::detect_similar_sentences, corpus is every sentence of the router
+pub fn detect_similar_sentences(corpus: &[String], sentence: &str, prompt: &str, temperature: f32) -> (bool, f32) {
+    let documents: Vec<Vec<String>> = corpus.iter().map(|text| tokenize(text)).collect();
+    let tf_idf = TfIdf::new(&documents);
+
+    let similarity = cosine_similarity(
+        &tf_idf.vector(&tokenize(sentence)),
+        &tf_idf.vector(&tokenize(prompt)),
+    );
+
+    (similarity >= temperature, similarity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(label: &str, description: &str) -> Category {
+        Category {
+            label: label.to_string(),
+            description: description.to_string(),
+            model_id: String::from("gpt-4"),
+            alternative_model_ids: vec![],
+        }
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_drops_stop_words() {
+        assert_eq!(
+            tokenize("How do I write a Rust function, please?"),
+            vec!["write", "rust", "function"],
+        );
+    }
+
+    #[test]
+    fn tokenize_drops_single_characters_and_punctuation() {
+        assert_eq!(tokenize("x + y = z... sql-query"), vec!["sql", "query"]);
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn idf_weights_rare_words_higher() {
+        let corpus = vec![
+            tokenize("rust code"),
+            tokenize("python code"),
+            tokenize("poems code"),
+        ];
+        let tf_idf = TfIdf::new(&corpus);
+
+        // ln((1 + 3) / (1 + 3)) + 1 and ln((1 + 3) / (1 + 1)) + 1
+        assert!((tf_idf.idf["code"] - 1.0).abs() < 1e-6);
+        assert!((tf_idf.idf["rust"] - (2.0f32.ln() + 1.0)).abs() < 1e-6);
+        assert!(tf_idf.idf["rust"] > tf_idf.idf["code"]);
+    }
+
+    #[test]
+    fn vector_counts_repeated_and_unknown_words() {
+        let tf_idf = TfIdf::new(&[tokenize("rust code"), tokenize("python code")]);
+        let vector = tf_idf.vector(&tokenize("rust rust unknown"));
+
+        assert!((vector["rust"] - 2.0 * tf_idf.idf["rust"]).abs() < 1e-6);
+        assert_eq!(vector["unknown"], 1.0);
+    }
+
+    #[test]
+    fn cosine_similarity_scores() {
+        let tf_idf = TfIdf::new(&[tokenize("rust code"), tokenize("python scripts")]);
+        let rust = tf_idf.vector(&tokenize("rust code"));
+        let python = tf_idf.vector(&tokenize("python scripts"));
+        let mixed = tf_idf.vector(&tokenize("rust scripts"));
+
+        assert!((cosine_similarity(&rust, &rust) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&rust, &python), 0.0);
+
+        let partial = cosine_similarity(&rust, &mixed);
+        assert!(partial > 0.0 && partial < 1.0);
+        assert!((partial - cosine_similarity(&mixed, &rust)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn empty_vocabulary_scores_zero() {
+        let tf_idf = TfIdf::new(&[]);
+        assert!(tf_idf.idf.is_empty());
+
+        let empty = tf_idf.vector(&tokenize("the a of"));
+        let words = tf_idf.vector(&tokenize("rust code"));
+        assert_eq!(cosine_similarity(&empty, &words), 0.0);
+        assert_eq!(cosine_similarity(&empty, &empty), 0.0);
+
+        assert!(classify("rust code", &[]).is_empty());
+        assert_eq!(detect_similar_sentences(&[], "rust code", "", 0.5), (false, 0.0));
+    }
+
+    #[test]
+    fn classify_ranks_the_matching_category_first() {
+        let categories = vec![
+            category("coding", "write and debug source code in rust or python"),
+            category("poetry", "write poems and song lyrics"),
+        ];
+
+        let labels = classify("debug this rust source code", &categories);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[0].text, "coding");
+        assert_eq!(labels[0].id, 0);
+        assert!(labels[0].score > labels[1].score);
+    }
+
+    #[test]
+    fn detect_similar_sentences_uses_the_threshold() {
+        let corpus = vec![String::from("translate to french"), String::from("summarize this article")];
+
+        let (similar, score) = detect_similar_sentences(&corpus, &corpus[0], "translate this to french", 0.5);
+        assert!(similar);
+        assert!(score > 0.5);
+
+        let (similar, score) = detect_similar_sentences(&corpus, &corpus[0], "summarize the article", 0.5);
+        assert!(!similar);
+        assert_eq!(score, 0.0);
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use serde::Serialize;
+
+use super::{backend::{RustBertError, SentenceEmbeddingsModel}, scheduler::EmbeddingScheduler, slot::{ModelSlot, ModelSlotInfo}};
+
+// router sentences are few and repeat on every request, prompts are never cached
+const MAX_CACHED_VECTORS: usize = 4096;
+
+struct CachedVectors {
+    generation: u64,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+#[derive(Clone)]
+pub struct EmbeddingModel {
+    pub id: String,
+    pub slot: Arc<ModelSlot<SentenceEmbeddingsModel>>,
+    pub scheduler: EmbeddingScheduler,
+    // used when a sentence doesn't set its own cosine_similarity_temperature,
+    // similarity scores aren't comparable between models
+    pub similarity_threshold: f32,
+    cache: Arc<Mutex<CachedVectors>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingModelInfo {
+    pub id: String,
+    pub default: bool,
+    pub similarity_threshold: f32,
+    pub model: ModelSlotInfo,
+}
+
+impl EmbeddingModel {
+    pub fn new(id: String, slot: Arc<ModelSlot<SentenceEmbeddingsModel>>, scheduler: EmbeddingScheduler, similarity_threshold: f32) -> EmbeddingModel {
+        EmbeddingModel {
+            id,
+            slot,
+            scheduler,
+            similarity_threshold,
+            cache: Arc::new(Mutex::new(CachedVectors {
+                generation: 0,
+                vectors: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn threshold(&self, sentence_temperature: f32) -> f32 {
+        if sentence_temperature > 0.0 {
+            return sentence_temperature;
+        }
+
+        self.similarity_threshold
+    }
+
+    pub async fn encode(&self, text: &String) -> Result<Vec<f32>, RustBertError> {
+        let mut embeddings = self.scheduler.encode(vec![text.clone()]).await?;
+        match embeddings.pop() {
+            Some(embedding) => Ok(embedding),
+            None => Err(RustBertError::ValueError(String::from("missing sentence embedding"))),
+        }
+    }
+
+    // vectors are dropped once the slot generation changes, a hot-swapped model
+    // can't be compared against vectors produced by the previous one
+    pub async fn cached_encode(&self, text: &String) -> Result<Vec<f32>, RustBertError> {
+        let generation = self.slot.info().generation;
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.generation != generation {
+                cache.generation = generation;
+                cache.vectors.clear();
+            }
+
+            if let Some(vector) = cache.vectors.get(text) {
+                return Ok(vector.clone());
+            }
+        }
+
+        let vector = self.encode(text).await?;
+
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.generation == generation {
+                if cache.vectors.len() >= MAX_CACHED_VECTORS {
+                    cache.vectors.clear();
+                }
+
+                cache.vectors.insert(text.clone(), vector.clone());
+            }
+        }
+
+        Ok(vector)
+    }
+}
+
+#[derive(Clone)]
+pub struct EmbeddingModelRegistry {
+    pub default_model_id: String,
+    pub models: HashMap<String, EmbeddingModel>,
+}
+
+impl EmbeddingModelRegistry {
+    // routers without an explicit embedding model use the default one
+    pub fn get(&self, id: &str) -> Option<&EmbeddingModel> {
+        match id {
+            "" => self.models.get(&self.default_model_id),
+            _ => self.models.get(id),
+        }
+    }
+
+    pub fn info(&self) -> Vec<EmbeddingModelInfo> {
+        let mut info: Vec<EmbeddingModelInfo> = self.models
+            .values()
+            .map(|model| EmbeddingModelInfo {
+                id: model.id.clone(),
+                default: model.id == self.default_model_id,
+                similarity_threshold: model.similarity_threshold,
+                model: model.slot.info(),
+            })
+            .collect();
+
+        info.sort_by(|a, b| a.id.cmp(&b.id));
+        info
+    }
+}
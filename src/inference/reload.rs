@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use tokio::task;
+
+use crate::server::{create_sentence_embeddings_model, zero_shot_create_local_prompt_classify_model, zero_shot_create_prompt_classify_model};
+
+use super::{backend::{RustBertError, SentenceEmbeddingsModel, ZeroShotClassificationModel}, slot::ModelSlot};
+
+// loads, warms up and only then swaps the new model in, requests keep using
+// the current model for the whole duration of the reload
+pub async fn reload_classification_model(slot: Arc<ModelSlot<ZeroShotClassificationModel>>, name: String, url: String, path: Option<String>) {
+    let model_result = match &path {
+        Some(path) => zero_shot_create_local_prompt_classify_model(&name, path).await,
+        None => zero_shot_create_prompt_classify_model(&name, &url).await,
+    };
+
+    let model = match model_result {
+        Ok(model) => model,
+        Err(e) => {
+            error!("Error reloading prompt classification model {}: {}", name, e);
+            slot.fail_reload(e.to_string());
+            return;
+        }
+    };
+
+    let warm_up = task::spawn_blocking(move || {
+        model.predict_multilabel(
+            ["warm up"],
+            ["warm", "up"],
+            Some(Box::new(|label: &str| format!("{label}"))),
+            128,
+        )?;
+
+        Ok::<ZeroShotClassificationModel, RustBertError>(model)
+    }).await.map_err(|e| RustBertError::TchError(e.to_string()));
+
+    match warm_up {
+        Ok(Ok(model)) => {
+            slot.swap(model, name.clone(), url, path);
+            info!("Prompt classification model swapped to: {}", name);
+        },
+        Ok(Err(e)) | Err(e) => {
+            error!("Error warming up prompt classification model {}: {}", name, e);
+            slot.fail_reload(e.to_string());
+        },
+    }
+}
+
+pub async fn reload_embedding_model(slot: Arc<ModelSlot<SentenceEmbeddingsModel>>, name: String, path: Option<String>) {
+    let model = match create_sentence_embeddings_model(&name, &path).await {
+        Ok(model) => model,
+        Err(e) => {
+            error!("Error reloading sentence embeddings model {}: {}", name, e);
+            slot.fail_reload(e.to_string());
+            return;
+        }
+    };
+
+    let warm_up = task::spawn_blocking(move || {
+        model.encode(&["warm up"])?;
+
+        Ok::<SentenceEmbeddingsModel, RustBertError>(model)
+    }).await.map_err(|e| RustBertError::TchError(e.to_string()));
+
+    match warm_up {
+        Ok(Ok(model)) => {
+            slot.swap(model, name.clone(), String::new(), path);
+            info!("Sentence embeddings model swapped to: {}", name);
+        },
+        Ok(Err(e)) | Err(e) => {
+            error!("Error warming up sentence embeddings model {}: {}", name, e);
+            slot.fail_reload(e.to_string());
+        },
+    }
+}
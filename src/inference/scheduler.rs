@@ -0,0 +1,226 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use log::error;
+use tokio::{sync::{mpsc, oneshot}, task, time::{timeout_at, Instant}};
+
+use super::{backend::{Label, RustBertError, SentenceEmbeddingsModel, ZeroShotClassificationModel}, slot::ModelSlot};
+
+// how many requests are collected before running a batch, and how long the
+// first request of a batch waits for others to join it
+#[derive(Debug, Clone, Copy)]
+pub struct BatchSettings {
+    pub max_batch_size: usize,
+    pub max_wait: Duration,
+}
+
+struct ClassificationJob {
+    prompt: String,
+    labels: Vec<String>,
+    respond_to: oneshot::Sender<Result<Vec<Label>, RustBertError>>,
+}
+
+struct EmbeddingJob {
+    sentences: Vec<String>,
+    respond_to: oneshot::Sender<Result<Vec<Vec<f32>>, RustBertError>>,
+}
+
+#[derive(Clone)]
+pub struct ClassificationScheduler {
+    sender: mpsc::Sender<ClassificationJob>,
+}
+
+impl ClassificationScheduler {
+    pub fn new(slot: Arc<ModelSlot<ZeroShotClassificationModel>>, settings: BatchSettings) -> ClassificationScheduler {
+        let (sender, receiver) = mpsc::channel(settings.max_batch_size * 64);
+        tokio::spawn(run_classification_worker(slot, settings, receiver));
+
+        ClassificationScheduler { sender }
+    }
+
+    pub async fn classify(&self, prompt: String, labels: Vec<String>) -> Result<Vec<Label>, RustBertError> {
+        let (respond_to, response) = oneshot::channel();
+        let job = ClassificationJob {
+            prompt,
+            labels,
+            respond_to,
+        };
+
+        if self.sender.send(job).await.is_err() {
+            return Err(RustBertError::ValueError(String::from("classification scheduler is not running")));
+        }
+
+        match response.await {
+            Ok(result) => result,
+            Err(_) => Err(RustBertError::ValueError(String::from("classification batch was dropped"))),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EmbeddingScheduler {
+    sender: mpsc::Sender<EmbeddingJob>,
+}
+
+impl EmbeddingScheduler {
+    pub fn new(slot: Arc<ModelSlot<SentenceEmbeddingsModel>>, settings: BatchSettings) -> EmbeddingScheduler {
+        let (sender, receiver) = mpsc::channel(settings.max_batch_size * 64);
+        tokio::spawn(run_embedding_worker(slot, settings, receiver));
+
+        EmbeddingScheduler { sender }
+    }
+
+    pub async fn encode(&self, sentences: Vec<String>) -> Result<Vec<Vec<f32>>, RustBertError> {
+        let (respond_to, response) = oneshot::channel();
+        let job = EmbeddingJob {
+            sentences,
+            respond_to,
+        };
+
+        if self.sender.send(job).await.is_err() {
+            return Err(RustBertError::ValueError(String::from("embedding scheduler is not running")));
+        }
+
+        match response.await {
+            Ok(result) => result,
+            Err(_) => Err(RustBertError::ValueError(String::from("embedding batch was dropped"))),
+        }
+    }
+}
+
+// waits for the first job, then keeps collecting until the window closes or the batch is full
+async fn collect_batch<T>(receiver: &mut mpsc::Receiver<T>, settings: &BatchSettings) -> Option<Vec<T>> {
+    let first = receiver.recv().await?;
+    let deadline = Instant::now() + settings.max_wait;
+
+    let mut batch = vec![first];
+    while batch.len() < settings.max_batch_size {
+        match timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(job)) => batch.push(job),
+            _ => break,
+        }
+    }
+
+    Some(batch)
+}
+
+async fn run_classification_worker(
+    slot: Arc<ModelSlot<ZeroShotClassificationModel>>,
+    settings: BatchSettings,
+    mut receiver: mpsc::Receiver<ClassificationJob>,
+) {
+    while let Some(batch) = collect_batch(&mut receiver, &settings).await {
+        // the model is picked once per batch, a hot swap only affects the next one
+        let model = match slot.current() {
+            Some(model) => model,
+            None => {
+                for job in batch {
+                    let _ = job.respond_to.send(Err(RustBertError::ValueError(String::from("classification model is not loaded"))));
+                }
+                continue;
+            }
+        };
+
+        if let Err(e) = task::spawn_blocking(move || classify_batch(&model, batch)).await {
+            error!("classification batch panicked: {}", e);
+        }
+    }
+}
+
+async fn run_embedding_worker(
+    slot: Arc<ModelSlot<SentenceEmbeddingsModel>>,
+    settings: BatchSettings,
+    mut receiver: mpsc::Receiver<EmbeddingJob>,
+) {
+    while let Some(batch) = collect_batch(&mut receiver, &settings).await {
+        let model = match slot.current() {
+            Some(model) => model,
+            None => {
+                for job in batch {
+                    let _ = job.respond_to.send(Err(RustBertError::ValueError(String::from("embedding model is not loaded"))));
+                }
+                continue;
+            }
+        };
+
+        if let Err(e) = task::spawn_blocking(move || encode_batch(&model, batch)).await {
+            error!("embedding batch panicked: {}", e);
+        }
+    }
+}
+
+fn classify_batch(model: &Mutex<ZeroShotClassificationModel>, batch: Vec<ClassificationJob>) {
+    // predict_multilabel scores every input against the same labels, so jobs
+    // coming from routers with different categories run as separate groups
+    let mut groups: Vec<(Vec<String>, Vec<ClassificationJob>)> = vec![];
+    for job in batch {
+        match groups.iter_mut().find(|(labels, _)| *labels == job.labels) {
+            Some((_, jobs)) => jobs.push(job),
+            None => groups.push((job.labels.clone(), vec![job])),
+        }
+    }
+
+    let model = match model.lock() {
+        Ok(model) => model,
+        Err(_) => {
+            for (_, jobs) in groups {
+                for job in jobs {
+                    let _ = job.respond_to.send(Err(RustBertError::ValueError(String::from("classification model is poisoned"))));
+                }
+            }
+            return;
+        }
+    };
+
+    for (labels, jobs) in groups {
+        let inputs: Vec<&str> = jobs.iter().map(|job| job.prompt.as_str()).collect();
+        let candidate_labels: Vec<&str> = labels.iter().map(|label| label.as_str()).collect();
+
+        match model.predict_multilabel(
+            inputs,
+            candidate_labels,
+            Some(Box::new(|label: &str| format!("{label}"))),
+            128,
+        ) {
+            Ok(outputs) => {
+                for (job, output) in jobs.into_iter().zip(outputs) {
+                    let _ = job.respond_to.send(Ok(output));
+                }
+            }
+            Err(e) => {
+                let message = e.to_string();
+                for job in jobs {
+                    let _ = job.respond_to.send(Err(RustBertError::ValueError(message.clone())));
+                }
+            }
+        }
+    }
+}
+
+fn encode_batch(model: &Mutex<SentenceEmbeddingsModel>, batch: Vec<EmbeddingJob>) {
+    let sentences: Vec<&str> = batch
+        .iter()
+        .flat_map(|job| job.sentences.iter().map(|sentence| sentence.as_str()))
+        .collect();
+
+    let result = match model.lock() {
+        Ok(model) => model.encode(&sentences),
+        Err(_) => Err(RustBertError::ValueError(String::from("embedding model is poisoned"))),
+    };
+
+    let mut embeddings = match result {
+        Ok(embeddings) => embeddings.into_iter(),
+        Err(e) => {
+            let message = e.to_string();
+            for job in batch {
+                let _ = job.respond_to.send(Err(RustBertError::ValueError(message.clone())));
+            }
+            return;
+        }
+    };
+
+    // hand every job back the slice of embeddings that belongs to its sentences
+    for job in batch {
+        let job_embeddings: Vec<Vec<f32>> = embeddings.by_ref().take(job.sentences.len()).collect();
+        let _ = job.respond_to.send(Ok(job_embeddings));
+    }
+}
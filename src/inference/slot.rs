@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::Utc;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadState {
+    Idle,
+    Loading,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSlotInfo {
+    pub name: String,
+    pub url: String,
+    pub path: Option<String>,
+    pub loaded: bool,
+    pub generation: u64,
+    pub loaded_at: Option<String>,
+
+    pub reload_state: ReloadState,
+    pub reload_target: Option<String>,
+    pub reload_started_at: Option<String>,
+    pub reload_error: Option<String>,
+}
+
+// Holds the model the schedulers run on. Swapping only replaces the Arc, so a
+// batch that already picked up the previous model finishes on it and the old
+// model is freed once the last of those batches drops it.
+pub struct ModelSlot<T> {
+    model: RwLock<Option<Arc<Box<Mutex<T>>>>>,
+    info: RwLock<ModelSlotInfo>,
+}
+
+impl<T> ModelSlot<T> {
+    pub fn new(model: Option<T>, name: String, url: String, path: Option<String>) -> ModelSlot<T> {
+        let loaded = model.is_some();
+        let loaded_at = match loaded {
+            true => Some(Utc::now().to_rfc3339()),
+            false => None,
+        };
+
+        ModelSlot {
+            model: RwLock::new(model.map(|model| Arc::new(Box::new(Mutex::new(model))))),
+            info: RwLock::new(ModelSlotInfo {
+                name,
+                url,
+                path,
+                loaded,
+                generation: match loaded {
+                    true => 1,
+                    false => 0,
+                },
+                loaded_at,
+                reload_state: ReloadState::Idle,
+                reload_target: None,
+                reload_started_at: None,
+                reload_error: None,
+            }),
+        }
+    }
+
+    pub fn current(&self) -> Option<Arc<Box<Mutex<T>>>> {
+        read(&self.model).clone()
+    }
+
+    pub fn info(&self) -> ModelSlotInfo {
+        read(&self.info).clone()
+    }
+
+    // returns false when another reload is still running
+    pub fn begin_reload(&self, target: &str) -> bool {
+        let mut info = write(&self.info);
+        if info.reload_state == ReloadState::Loading {
+            return false;
+        }
+
+        info.reload_state = ReloadState::Loading;
+        info.reload_target = Some(target.to_string());
+        info.reload_started_at = Some(Utc::now().to_rfc3339());
+        info.reload_error = None;
+
+        true
+    }
+
+    pub fn fail_reload(&self, error: String) {
+        let mut info = write(&self.info);
+        info.reload_state = ReloadState::Failed;
+        info.reload_error = Some(error);
+    }
+
+    pub fn swap(&self, model: T, name: String, url: String, path: Option<String>) {
+        *write(&self.model) = Some(Arc::new(Box::new(Mutex::new(model))));
+
+        let mut info = write(&self.info);
+        info.name = name;
+        info.url = url;
+        info.path = path;
+        info.loaded = true;
+        info.generation += 1;
+        info.loaded_at = Some(Utc::now().to_rfc3339());
+        info.reload_state = ReloadState::Idle;
+        info.reload_error = None;
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    match lock.read() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    match lock.write() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
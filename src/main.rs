@@ -1,7 +1,9 @@
 mod server;
 
 mod controllers;
+mod inference;
 mod lemonsqueezy;
+mod providers;
 mod storage;
 mod types;
 mod utilities;
@@ -57,6 +59,9 @@ async fn main() {
         postgres_conn = match diesel_postgres::new_connection(&postgres_uri).await {
             Ok(conn) => {
                 info!("Connected to PostgreSQL");
+                if let Err(e) = diesel_postgres::run_migrations(&conn) {
+                    panic!("Error running PostgreSQL migrations: {}", e);
+                }
                 Option::from(conn)
             },
             Err(e) => {
@@ -136,6 +141,91 @@ async fn load_env() -> String {
         }
     }
 
+    if env::var("PLATFORM_ADMIN_IDS").is_err() {
+        warn!("PLATFORM_ADMIN_IDS isn't set, platform admin endpoints will reject every customer");
+    }
+
+    match env::var("INFERENCE_MAX_BATCH_SIZE") {
+        Ok(size) => match size.parse::<usize>() {
+            Ok(size) if size > 0 => (),
+            _ => panic!("INFERENCE_MAX_BATCH_SIZE must be a positive number"),
+        },
+        Err(_) => {
+            env::set_var("INFERENCE_MAX_BATCH_SIZE", "16");
+            warn!("INFERENCE_MAX_BATCH_SIZE isn't set, using default batch size: 16");
+        },
+    };
+
+    match env::var("INFERENCE_BATCH_WINDOW_MS") {
+        Ok(window) => match window.parse::<u64>() {
+            Ok(_) => (),
+            Err(_) => panic!("INFERENCE_BATCH_WINDOW_MS must be a number"),
+        },
+        Err(_) => {
+            env::set_var("INFERENCE_BATCH_WINDOW_MS", "5");
+            warn!("INFERENCE_BATCH_WINDOW_MS isn't set, using default batch window: 5ms");
+        },
+    };
+
+    // provider base urls can point to local stand-ins in development
+    let providers = [
+        ("OPENAI", "https://api.openai.com/v1"),
+        ("ANTHROPIC", "https://api.anthropic.com/v1"),
+        ("COHERE", "https://api.cohere.ai/v1"),
+    ];
+
+    for (provider, default_base_url) in providers {
+        let base_url_var = format!("{}_API_BASE_URL", provider);
+        if env::var(&base_url_var).is_err() {
+            env::set_var(&base_url_var, default_base_url);
+            warn!("{} isn't set, using default base url: {}", base_url_var, default_base_url);
+        }
+
+        let api_key_var = format!("{}_API_KEY", provider);
+        if env::var(&api_key_var).is_err() {
+            warn!("{} isn't set, /v1/chat/completions will reject {} models", api_key_var, provider.to_lowercase());
+        }
+    }
+
+    if env::var("CREDENTIALS_ENCRYPTION_KEY").is_err() {
+        warn!("CREDENTIALS_ENCRYPTION_KEY isn't set, organizations won't be able to store provider credentials");
+    }
+
+    match env::var("PROVIDER_REQUEST_TIMEOUT_SECS") {
+        Ok(secs) => match secs.parse::<u64>() {
+            Ok(_) => (),
+            Err(_) => panic!("PROVIDER_REQUEST_TIMEOUT_SECS must be a number"),
+        },
+        Err(_) => {
+            env::set_var("PROVIDER_REQUEST_TIMEOUT_SECS", "60");
+            warn!("PROVIDER_REQUEST_TIMEOUT_SECS isn't set, using default timeout: 60s");
+        },
+    };
+
+    let numeric_defaults = [
+        ("FAILOVER_MAX_RETRIES", "1"),
+        ("FAILOVER_BACKOFF_MS", "250"),
+        ("CIRCUIT_BREAKER_FAILURE_THRESHOLD", "5"),
+        ("CIRCUIT_BREAKER_COOLDOWN_SECS", "30"),
+        ("USAGE_BUFFER_SIZE", "10000"),
+        ("USAGE_MAX_BATCH_SIZE", "500"),
+        ("USAGE_FLUSH_INTERVAL_MS", "1000"),
+        ("REQUEST_LOG_PURGE_INTERVAL_SECS", "3600"),
+    ];
+
+    for (name, default_value) in numeric_defaults {
+        match env::var(name) {
+            Ok(value) => match value.parse::<u64>() {
+                Ok(_) => (),
+                Err(_) => panic!("{} must be a number", name),
+            },
+            Err(_) => {
+                env::set_var(name, default_value);
+                warn!("{} isn't set, using default value: {}", name, default_value);
+            },
+        };
+    }
+
     env::var("GOOGLE_OAUTH_CLIENT_ID").expect("GOOGLE_OAUTH_CLIENT_ID must be set");
     env::var("GOOGLE_OAUTH_CLIENT_SECRET").expect("GOOGLE_OAUTH_CLIENT_SECRET must be set");
     env::var("GOOGLE_OAUTH_CLIENT_REDIRECT_ENDPOINT").expect("GOOGLE_CLIENT_OAUTH_REDIRECT_URL must be set");
@@ -0,0 +1,399 @@
+use std::pin::Pin;
+
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::types::{organization::ModelOwner, state::ProviderSettings};
+
+pub mod anthropic;
+pub mod circuit_breaker;
+pub mod cohere;
+pub mod openai;
+
+// vendor neutral chat request, adapters translate it to each provider's wire format
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ContentPart {
+    Text(String),
+    // an https url or a base64 data url
+    Image { url: String, detail: Option<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    // JSON encoded, same as OpenAI
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    pub name: String,
+    pub description: Option<String>,
+    // JSON schema of the arguments
+    pub parameters: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ToolChoice {
+    Auto,
+    None,
+    Required,
+    Tool(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { name: String, schema: Value },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: Vec<ContentPart>,
+    // calls requested by an assistant message
+    pub tool_calls: Vec<ToolCall>,
+    // the call a tool message answers
+    pub tool_call_id: Option<String>,
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    // text parts only, images have no text equivalent
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text(text) => Some(text.as_str()),
+                ContentPart::Image { .. } => None,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    pub fn has_images(&self) -> bool {
+        self.content.iter().any(|part| matches!(part, ContentPart::Image { .. }))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub stop: Vec<String>,
+    pub top_p: Option<f64>,
+    pub seed: Option<u64>,
+    pub frequency_penalty: Option<f64>,
+    pub presence_penalty: Option<f64>,
+    // end user id, for the provider's abuse monitoring
+    pub user: Option<String>,
+    pub tools: Vec<Tool>,
+    pub tool_choice: Option<ToolChoice>,
+    pub response_format: Option<ResponseFormat>,
+    // every option of the OpenAI request, OpenAI gets them untouched, the other
+    // adapters only map the fields above
+    pub options: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub id: String,
+    pub model: String,
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    // normalized to the OpenAI values: stop, length, tool_calls, content_filter
+    pub finish_reason: Option<String>,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProviderError {
+    // connection refused, dns, broken streams...
+    Unreachable(String),
+    Timeout,
+    // the provider answered with a non 2xx status
+    Status(u16, Value),
+    InvalidResponse(String),
+    // the request uses something the provider has no equivalent for
+    InvalidRequest(String),
+}
+
+impl ProviderError {
+    // rate limits, provider outages and timeouts are worth another try, anything
+    // else would fail the same way on every model
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            ProviderError::Unreachable(_) | ProviderError::Timeout => true,
+            ProviderError::Status(status, _) => *status == 429 || *status >= 500,
+            ProviderError::InvalidResponse(_) | ProviderError::InvalidRequest(_) => false,
+        }
+    }
+
+    // short form listed in the X-Router-Attempts header
+    pub fn outcome(&self) -> String {
+        match self {
+            ProviderError::Unreachable(_) => String::from("unreachable"),
+            ProviderError::Timeout => String::from("timeout"),
+            ProviderError::Status(status, _) => status.to_string(),
+            ProviderError::InvalidResponse(_) => String::from("invalid_response"),
+            ProviderError::InvalidRequest(_) => String::from("request_unsupported"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFraming {
+    // data: {...} lines, OpenAI and Anthropic
+    ServerSentEvents,
+    // one JSON document per line, Cohere
+    JsonLines,
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    Delta(String),
+    // a new call has an id and a name, later events of the same index append
+    // to its arguments
+    ToolCall { index: u64, id: Option<String>, name: Option<String>, arguments: String },
+    Finish(String),
+    // providers report usage at different points of the stream, the latest value wins
+    PromptTokens(u64),
+    CompletionTokens(u64),
+    Done,
+}
+
+pub type ProviderStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, ProviderError>> + Send>>;
+
+pub trait ProviderAdapter: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn chat_url(&self) -> String;
+    fn auth_headers(&self, api_key: &str) -> Vec<(&'static str, String)>;
+    fn build_request(&self, request: &ChatRequest) -> Result<Value, ProviderError>;
+    fn parse_response(&self, body: Value) -> Result<ChatResponse, ProviderError>;
+
+    fn build_stream_request(&self, request: &ChatRequest) -> Result<Value, ProviderError>;
+    fn stream_framing(&self) -> StreamFraming {
+        StreamFraming::ServerSentEvents
+    }
+    // one line of the stream, without the data: prefix
+    fn parse_stream_event(&self, data: &str) -> Result<Vec<StreamEvent>, ProviderError>;
+}
+
+impl ChatResponse {
+    // every adapter answers /v1/chat/completions in the OpenAI format
+    pub fn to_openai(&self, created: i64) -> Value {
+        let mut message = json!({
+            "role": "assistant",
+            "content": self.content,
+        });
+
+        // OpenAI answers tool calls without content
+        if !self.tool_calls.is_empty() {
+            if self.content.is_empty() {
+                message["content"] = Value::Null;
+            }
+            message["tool_calls"] = Value::Array(self.tool_calls.iter().map(ToolCall::to_openai).collect());
+        }
+
+        json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "finish_reason": self.finish_reason,
+            }],
+            "usage": self.usage,
+        })
+    }
+}
+
+impl ToolCall {
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "id": self.id,
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "arguments": self.arguments,
+            },
+        })
+    }
+}
+
+// plain text or a list of content parts, non text parts are dropped
+pub fn message_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|text| text.as_str()))
+            .collect::<Vec<&str>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+// rough token count for providers that don't report usage
+pub fn estimate_tokens(characters: usize) -> u64 {
+    ((characters + 3) / 4) as u64
+}
+
+pub fn adapter_for(owner: &ModelOwner, settings: &ProviderSettings) -> Option<Box<dyn ProviderAdapter>> {
+    match owner {
+        ModelOwner::OpenAI => Some(Box::new(openai::OpenAIAdapter::new(&settings.openai_api_base_url))),
+        ModelOwner::Anthropic => Some(Box::new(anthropic::AnthropicAdapter::new(&settings.anthropic_api_base_url))),
+        ModelOwner::Coherence => Some(Box::new(cohere::CohereAdapter::new(&settings.cohere_api_base_url))),
+        ModelOwner::OpenSource => None,
+    }
+}
+
+pub fn api_key_for(owner: &ModelOwner, settings: &ProviderSettings) -> Option<String> {
+    match owner {
+        ModelOwner::OpenAI => settings.openai_api_key.clone(),
+        ModelOwner::Anthropic => settings.anthropic_api_key.clone(),
+        ModelOwner::Coherence => settings.cohere_api_key.clone(),
+        ModelOwner::OpenSource => None,
+    }
+}
+
+// a model can be proxied when its provider has an adapter and there's a key for
+// it, the model's own credential or the platform one
+pub fn is_callable(owner: &ModelOwner, credential_id: &str, settings: &ProviderSettings) -> bool {
+    adapter_for(owner, settings).is_some() && (!credential_id.is_empty() || api_key_for(owner, settings).is_some())
+}
+
+// sends the request and turns non 2xx answers into ProviderError::Status
+async fn post(
+    client: &reqwest::Client,
+    adapter: &dyn ProviderAdapter,
+    api_key: &str,
+    body: Value,
+) -> Result<reqwest::Response, ProviderError> {
+    let mut builder = client
+        .post(adapter.chat_url())
+        .header("content-type", "application/json");
+
+    for (name, value) in adapter.auth_headers(api_key) {
+        builder = builder.header(name, value);
+    }
+
+    let response = match builder.body(body.to_string()).send().await {
+        Ok(response) => response,
+        Err(e) => return Err(ProviderError::Unreachable(e.to_string())),
+    };
+
+    let status = response.status().as_u16();
+    if status < 300 {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    match serde_json::from_str(&text) {
+        Ok(body) => Err(ProviderError::Status(status, body)),
+        Err(_) => Err(ProviderError::Status(status, Value::String(text))),
+    }
+}
+
+pub async fn send_chat(
+    client: &reqwest::Client,
+    adapter: &dyn ProviderAdapter,
+    api_key: &str,
+    request: &ChatRequest,
+) -> Result<ChatResponse, ProviderError> {
+    let response = post(client, adapter, api_key, adapter.build_request(request)?).await?;
+
+    let text = match response.text().await {
+        Ok(text) => text,
+        Err(e) => return Err(ProviderError::Unreachable(e.to_string())),
+    };
+
+    let body: Value = match serde_json::from_str(&text) {
+        Ok(body) => body,
+        Err(e) => return Err(ProviderError::InvalidResponse(e.to_string())),
+    };
+
+    let mut chat_response = adapter.parse_response(body)?;
+    if chat_response.model.is_empty() {
+        chat_response.model = request.model.clone();
+    }
+
+    Ok(chat_response)
+}
+
+// errors before the first byte are returned directly, so callers can still
+// answer with a regular error response
+pub async fn stream_chat(
+    client: &reqwest::Client,
+    adapter: Box<dyn ProviderAdapter>,
+    api_key: &str,
+    request: &ChatRequest,
+) -> Result<ProviderStream, ProviderError> {
+    let response = post(client, adapter.as_ref(), api_key, adapter.build_stream_request(request)?).await?;
+    let framing = adapter.stream_framing();
+    let mut bytes = response.bytes_stream();
+
+    Ok(Box::pin(async_stream::stream! {
+        let mut buffer: Vec<u8> = vec![];
+        while let Some(chunk) = bytes.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    yield Err(ProviderError::Unreachable(e.to_string()));
+                    return;
+                }
+            };
+
+            buffer.extend_from_slice(&chunk);
+            while let Some(position) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=position).collect();
+                let line = String::from_utf8_lossy(&line).trim().to_string();
+
+                let data = match framing {
+                    StreamFraming::ServerSentEvents => match line.strip_prefix("data:") {
+                        Some(data) => data.trim().to_string(),
+                        None => continue,
+                    },
+                    StreamFraming::JsonLines => line,
+                };
+
+                if data.is_empty() {
+                    continue;
+                }
+
+                match adapter.parse_stream_event(&data) {
+                    Ok(events) => {
+                        for event in events {
+                            let done = matches!(event, StreamEvent::Done);
+                            yield Ok(event);
+                            if done {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+        }
+
+        // the connection closed without an explicit end of stream
+        yield Ok(StreamEvent::Done);
+    }))
+}
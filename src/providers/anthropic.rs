@@ -0,0 +1,432 @@
+//
+// Anthropic messages, https://docs.anthropic.com/claude/reference/messages_post
+//
+
+use serde_json::{json, Map, Value};
+
+use super::{ChatMessage, ChatRequest, ChatResponse, ContentPart, ProviderAdapter, ProviderError, ResponseFormat, StreamEvent, ToolCall, ToolChoice, Usage};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// max_tokens is required by the messages API
+const DEFAULT_MAX_TOKENS: u64 = 1024;
+
+pub struct AnthropicAdapter {
+    base_url: String,
+}
+
+impl AnthropicAdapter {
+    pub fn new(base_url: &str) -> AnthropicAdapter {
+        AnthropicAdapter {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+fn finish_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "max_tokens" => String::from("length"),
+        "tool_use" => String::from("tool_calls"),
+        _ => String::from("stop"),
+    }
+}
+
+// images are sent inline when they come as a data url, by url otherwise
+fn image_block(url: &str) -> Value {
+    let inline = url
+        .strip_prefix("data:")
+        .and_then(|data| data.split_once(";base64,"));
+
+    match inline {
+        Some((media_type, data)) => json!({"type": "image", "source": {"type": "base64", "media_type": media_type, "data": data}}),
+        None => json!({"type": "image", "source": {"type": "url", "url": url}}),
+    }
+}
+
+// empty text blocks are rejected by the API
+fn content_blocks(message: &ChatMessage) -> Result<Vec<Value>, ProviderError> {
+    let mut blocks: Vec<Value> = message.content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text(text) if text.is_empty() => None,
+            ContentPart::Text(text) => Some(json!({"type": "text", "text": text})),
+            ContentPart::Image { url, .. } => Some(image_block(url)),
+        })
+        .collect();
+
+    for tool_call in &message.tool_calls {
+        let input: Value = match serde_json::from_str(&tool_call.arguments) {
+            Ok(input) => input,
+            Err(_) => return Err(ProviderError::InvalidRequest(format!("arguments of tool call {} aren't valid JSON", tool_call.id))),
+        };
+        blocks.push(json!({"type": "tool_use", "id": tool_call.id, "name": tool_call.name, "input": input}));
+    }
+
+    Ok(blocks)
+}
+
+fn tool_result_block(message: &ChatMessage) -> Result<Value, ProviderError> {
+    match &message.tool_call_id {
+        Some(tool_call_id) => Ok(json!({"type": "tool_result", "tool_use_id": tool_call_id, "content": message.text()})),
+        None => Err(ProviderError::InvalidRequest(String::from("tool messages need a tool_call_id"))),
+    }
+}
+
+impl ProviderAdapter for AnthropicAdapter {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn chat_url(&self) -> String {
+        format!("{}/messages", self.base_url)
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("x-api-key", api_key.to_string()),
+            ("anthropic-version", String::from(ANTHROPIC_VERSION)),
+        ]
+    }
+
+    fn build_request(&self, request: &ChatRequest) -> Result<Value, ProviderError> {
+        // system messages go in their own field, the rest must alternate user/assistant
+        // so consecutive messages of the same role are merged into one turn, tool
+        // results are user turns
+        let mut system: Vec<String> = vec![];
+        let mut messages: Vec<Value> = vec![];
+        for message in &request.messages {
+            let (role, blocks) = match message.role.as_str() {
+                "system" | "developer" => {
+                    system.push(message.text());
+                    continue;
+                }
+                "user" | "assistant" => (message.role.as_str(), content_blocks(message)?),
+                "tool" => ("user", vec![tool_result_block(message)?]),
+                role => return Err(ProviderError::InvalidRequest(format!("{} messages aren't supported by anthropic", role))),
+            };
+
+            if blocks.is_empty() {
+                continue;
+            }
+
+            match messages.last_mut() {
+                Some(last) if last["role"] == role => {
+                    if let Some(last_blocks) = last["content"].as_array_mut() {
+                        last_blocks.extend(blocks);
+                    }
+                }
+                _ => messages.push(json!({"role": role, "content": blocks})),
+            }
+        }
+
+        // there's no json mode, the format is asked for in the system prompt
+        match &request.response_format {
+            Some(ResponseFormat::JsonObject) => system.push(String::from("Respond only with a valid JSON object.")),
+            Some(ResponseFormat::JsonSchema { schema, .. }) => system.push(format!("Respond only with a valid JSON object matching this JSON schema: {}", schema)),
+            Some(ResponseFormat::Text) | None => (),
+        }
+
+        let mut body = Map::new();
+        body.insert(String::from("model"), json!(request.model));
+        body.insert(String::from("messages"), json!(messages));
+        body.insert(String::from("max_tokens"), json!(request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)));
+
+        if !system.is_empty() {
+            body.insert(String::from("system"), json!(system.join("\n")));
+        }
+
+        if !request.tools.is_empty() {
+            let tools: Vec<Value> = request.tools
+                .iter()
+                .map(|tool| {
+                    let mut definition = json!({"name": tool.name, "input_schema": tool.parameters});
+                    if let Some(description) = &tool.description {
+                        definition["description"] = json!(description);
+                    }
+                    definition
+                })
+                .collect();
+            body.insert(String::from("tools"), json!(tools));
+
+            if let Some(tool_choice) = &request.tool_choice {
+                let tool_choice = match tool_choice {
+                    ToolChoice::Auto => json!({"type": "auto"}),
+                    ToolChoice::None => json!({"type": "none"}),
+                    ToolChoice::Required => json!({"type": "any"}),
+                    ToolChoice::Tool(name) => json!({"type": "tool", "name": name}),
+                };
+                body.insert(String::from("tool_choice"), tool_choice);
+            }
+        }
+
+        if let Some(temperature) = request.temperature {
+            body.insert(String::from("temperature"), json!(temperature));
+        }
+
+        if !request.stop.is_empty() {
+            body.insert(String::from("stop_sequences"), json!(request.stop));
+        }
+
+        if let Some(top_p) = request.top_p {
+            body.insert(String::from("top_p"), json!(top_p));
+        }
+
+        if let Some(user) = &request.user {
+            body.insert(String::from("metadata"), json!({"user_id": user}));
+        }
+
+        Ok(Value::Object(body))
+    }
+
+    fn parse_response(&self, body: Value) -> Result<ChatResponse, ProviderError> {
+        let blocks = match body["content"].as_array() {
+            Some(blocks) => blocks,
+            None => return Err(ProviderError::InvalidResponse(String::from("missing content"))),
+        };
+
+        let content = blocks
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<&str>>()
+            .join("");
+
+        let tool_calls = blocks
+            .iter()
+            .filter(|block| block["type"] == "tool_use")
+            .map(|block| ToolCall {
+                id: block["id"].as_str().unwrap_or_default().to_string(),
+                name: block["name"].as_str().unwrap_or_default().to_string(),
+                arguments: block["input"].to_string(),
+            })
+            .collect();
+
+        let prompt_tokens = body["usage"]["input_tokens"].as_u64().unwrap_or(0);
+        let completion_tokens = body["usage"]["output_tokens"].as_u64().unwrap_or(0);
+
+        Ok(ChatResponse {
+            id: body["id"].as_str().unwrap_or_default().to_string(),
+            model: body["model"].as_str().unwrap_or_default().to_string(),
+            content,
+            tool_calls,
+            finish_reason: body["stop_reason"].as_str().map(finish_reason),
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            },
+        })
+    }
+
+    fn build_stream_request(&self, request: &ChatRequest) -> Result<Value, ProviderError> {
+        let mut body = self.build_request(request)?;
+        body["stream"] = json!(true);
+        Ok(body)
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<Vec<StreamEvent>, ProviderError> {
+        let event: Value = match serde_json::from_str(data) {
+            Ok(event) => event,
+            Err(e) => return Err(ProviderError::InvalidResponse(e.to_string())),
+        };
+
+        let mut events = vec![];
+        match event["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                let usage = &event["message"]["usage"];
+                if let Some(input_tokens) = usage["input_tokens"].as_u64() {
+                    events.push(StreamEvent::PromptTokens(input_tokens));
+                }
+                if let Some(output_tokens) = usage["output_tokens"].as_u64() {
+                    events.push(StreamEvent::CompletionTokens(output_tokens));
+                }
+            }
+            // tool calls are indexed by their content block
+            "content_block_start" => {
+                let block = &event["content_block"];
+                if block["type"] == "tool_use" {
+                    events.push(StreamEvent::ToolCall {
+                        index: event["index"].as_u64().unwrap_or(0),
+                        id: block["id"].as_str().map(|id| id.to_string()),
+                        name: block["name"].as_str().map(|name| name.to_string()),
+                        arguments: String::new(),
+                    });
+                }
+            }
+            "content_block_delta" => {
+                if let Some(text) = event["delta"]["text"].as_str() {
+                    events.push(StreamEvent::Delta(text.to_string()));
+                }
+                if let Some(partial_json) = event["delta"]["partial_json"].as_str() {
+                    events.push(StreamEvent::ToolCall {
+                        index: event["index"].as_u64().unwrap_or(0),
+                        id: None,
+                        name: None,
+                        arguments: partial_json.to_string(),
+                    });
+                }
+            }
+            // output_tokens here is the total for the message, not an increment
+            "message_delta" => {
+                if let Some(stop_reason) = event["delta"]["stop_reason"].as_str() {
+                    events.push(StreamEvent::Finish(finish_reason(stop_reason)));
+                }
+                if let Some(output_tokens) = event["usage"]["output_tokens"].as_u64() {
+                    events.push(StreamEvent::CompletionTokens(output_tokens));
+                }
+            }
+            "message_stop" => events.push(StreamEvent::Done),
+            "error" => {
+                let message = event["error"]["message"].as_str().unwrap_or("stream error");
+                return Err(ProviderError::InvalidResponse(message.to_string()));
+            }
+            _ => (),
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+
+    use super::*;
+    use crate::providers::Tool;
+
+    fn request(messages: &[(&str, &str)]) -> ChatRequest {
+        ChatRequest {
+            model: String::from("claude-2.1"),
+            messages: messages
+                .iter()
+                .map(|(role, content)| ChatMessage {
+                    role: role.to_string(),
+                    content: vec![ContentPart::Text(content.to_string())],
+                    tool_calls: vec![],
+                    tool_call_id: None,
+                    name: None,
+                })
+                .collect(),
+            temperature: None,
+            max_tokens: None,
+            stop: vec![],
+            top_p: None,
+            seed: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            user: None,
+            tools: vec![],
+            tool_choice: None,
+            response_format: None,
+            options: Map::new(),
+        }
+    }
+
+    #[test]
+    fn merges_consecutive_messages_of_the_same_role() {
+        let adapter = AnthropicAdapter::new("http://localhost");
+        let body = adapter.build_request(&request(&[
+            ("system", "be brief"),
+            ("user", "hello"),
+            ("user", "are you there?"),
+            ("assistant", "yes"),
+            ("developer", "answer in english"),
+            ("user", "great"),
+        ])).unwrap();
+
+        assert_eq!(body["system"], "be brief\nanswer in english");
+        assert_eq!(body["messages"], json!([
+            {"role": "user", "content": [{"type": "text", "text": "hello"}, {"type": "text", "text": "are you there?"}]},
+            {"role": "assistant", "content": [{"type": "text", "text": "yes"}]},
+            {"role": "user", "content": [{"type": "text", "text": "great"}]},
+        ]));
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn rejects_roles_without_an_equivalent() {
+        let adapter = AnthropicAdapter::new("http://localhost");
+        let result = adapter.build_request(&request(&[("user", "weather?"), ("function", "sunny")]));
+
+        assert!(matches!(result, Err(ProviderError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn rejects_tool_messages_without_a_call_id() {
+        let adapter = AnthropicAdapter::new("http://localhost");
+        let result = adapter.build_request(&request(&[("user", "weather?"), ("tool", "sunny")]));
+
+        assert!(matches!(result, Err(ProviderError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn translates_images_tools_and_tool_results() {
+        let adapter = AnthropicAdapter::new("http://localhost");
+        let mut chat_request = request(&[("user", "what's in the picture and the weather there?"), ("assistant", ""), ("tool", "sunny")]);
+        chat_request.messages[0].content.push(ContentPart::Image { url: String::from("data:image/png;base64,iVBORw0"), detail: None });
+        chat_request.messages[0].content.push(ContentPart::Image { url: String::from("https://example.com/cat.png"), detail: Some(String::from("low")) });
+        chat_request.messages[1].tool_calls.push(ToolCall {
+            id: String::from("call_1"),
+            name: String::from("weather"),
+            arguments: String::from(r#"{"city": "Paris"}"#),
+        });
+        chat_request.messages[2].tool_call_id = Some(String::from("call_1"));
+        chat_request.tools.push(Tool {
+            name: String::from("weather"),
+            description: Some(String::from("current weather")),
+            parameters: json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+        });
+        chat_request.tool_choice = Some(ToolChoice::Required);
+        chat_request.response_format = Some(ResponseFormat::JsonObject);
+
+        let body = adapter.build_request(&chat_request).unwrap();
+
+        assert_eq!(body["messages"], json!([
+            {"role": "user", "content": [
+                {"type": "text", "text": "what's in the picture and the weather there?"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0"}},
+                {"type": "image", "source": {"type": "url", "url": "https://example.com/cat.png"}},
+            ]},
+            {"role": "assistant", "content": [{"type": "tool_use", "id": "call_1", "name": "weather", "input": {"city": "Paris"}}]},
+            {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "call_1", "content": "sunny"}]},
+        ]));
+        assert_eq!(body["tools"], json!([{
+            "name": "weather",
+            "description": "current weather",
+            "input_schema": {"type": "object", "properties": {"city": {"type": "string"}}},
+        }]));
+        assert_eq!(body["tool_choice"], json!({"type": "any"}));
+        assert_eq!(body["system"], "Respond only with a valid JSON object.");
+    }
+
+    #[test]
+    fn reads_tool_use_blocks_as_tool_calls() {
+        let adapter = AnthropicAdapter::new("http://localhost");
+        let response = adapter.parse_response(json!({
+            "id": "msg_1",
+            "model": "claude-3-opus",
+            "content": [
+                {"type": "text", "text": "checking"},
+                {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {"city": "Paris"}},
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 4},
+        })).unwrap();
+
+        assert_eq!(response.content, "checking");
+        assert_eq!(response.tool_calls, vec![ToolCall {
+            id: String::from("toolu_1"),
+            name: String::from("weather"),
+            arguments: String::from(r#"{"city":"Paris"}"#),
+        }]);
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn streams_tool_use_blocks_as_tool_call_events() {
+        let adapter = AnthropicAdapter::new("http://localhost");
+        let start = adapter.parse_stream_event(r#"{"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {}}}"#).unwrap();
+        let delta = adapter.parse_stream_event(r#"{"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"city\""}}"#).unwrap();
+
+        assert!(matches!(&start[..], [StreamEvent::ToolCall { index: 1, id: Some(id), name: Some(name), arguments }] if id == "toolu_1" && name == "weather" && arguments.is_empty()));
+        assert!(matches!(&delta[..], [StreamEvent::ToolCall { index: 1, id: None, name: None, arguments }] if arguments == "{\"city\""));
+    }
+}
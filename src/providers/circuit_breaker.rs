@@ -0,0 +1,111 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+use log::warn;
+
+struct ProviderHealth {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+// an org credential failing (revoked, out of quota) says nothing about the
+// platform key or other orgs' keys of the same provider
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Credential {
+    Platform,
+    Org(String),
+}
+
+impl Credential {
+    // models without a credential use the platform key
+    pub fn of(credential_id: &str) -> Credential {
+        match credential_id.is_empty() {
+            true => Credential::Platform,
+            false => Credential::Org(credential_id.to_string()),
+        }
+    }
+}
+
+// per provider and credential, in memory, every instance keeps its own view of provider health
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    providers: Mutex<HashMap<(&'static str, Credential), ProviderHealth>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold,
+            cooldown,
+            providers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // once the cool-down is over the next call goes through, one more failure opens it again
+    pub fn is_open(&self, provider: &'static str, credential: &Credential) -> bool {
+        let providers = match self.providers.lock() {
+            Ok(providers) => providers,
+            Err(_) => return false,
+        };
+
+        match providers.get(&(provider, credential.clone())).and_then(|health| health.open_until) {
+            Some(open_until) => Instant::now() < open_until,
+            None => false,
+        }
+    }
+
+    pub fn record_success(&self, provider: &'static str, credential: &Credential) {
+        if let Ok(mut providers) = self.providers.lock() {
+            providers.remove(&(provider, credential.clone()));
+        }
+    }
+
+    pub fn record_failure(&self, provider: &'static str, credential: &Credential) {
+        let mut providers = match self.providers.lock() {
+            Ok(providers) => providers,
+            Err(_) => return,
+        };
+
+        let health = providers.entry((provider, credential.clone())).or_insert(ProviderHealth {
+            consecutive_failures: 0,
+            open_until: None,
+        });
+
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.failure_threshold {
+            warn!("{} ({:?}) failed {} times in a row, skipping it for {:?}", provider, credential, health.consecutive_failures, self.cooldown);
+            health.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_per_provider_and_credential() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let org_credential = Credential::of("credential");
+
+        breaker.record_failure("openai", &org_credential);
+        assert!(!breaker.is_open("openai", &org_credential));
+
+        breaker.record_failure("openai", &org_credential);
+        assert!(breaker.is_open("openai", &org_credential));
+        assert!(!breaker.is_open("openai", &Credential::of("")));
+        assert!(!breaker.is_open("openai", &Credential::of("other")));
+        assert!(!breaker.is_open("anthropic", &org_credential));
+    }
+
+    #[test]
+    fn closes_after_a_success() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+
+        breaker.record_failure("openai", &Credential::Platform);
+        assert!(breaker.is_open("openai", &Credential::Platform));
+
+        breaker.record_success("openai", &Credential::Platform);
+        assert!(!breaker.is_open("openai", &Credential::Platform));
+    }
+}
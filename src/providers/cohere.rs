@@ -0,0 +1,195 @@
+//
+// Cohere chat, https://docs.cohere.com/reference/chat
+//
+
+use serde_json::{json, Map, Value};
+
+use super::{ChatRequest, ChatResponse, ProviderAdapter, ProviderError, ResponseFormat, StreamEvent, StreamFraming, Usage};
+
+pub struct CohereAdapter {
+    base_url: String,
+}
+
+impl CohereAdapter {
+    pub fn new(base_url: &str) -> CohereAdapter {
+        CohereAdapter {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+fn finish_reason(reason: &str) -> String {
+    match reason {
+        "MAX_TOKENS" => String::from("length"),
+        "ERROR_TOXIC" => String::from("content_filter"),
+        _ => String::from("stop"),
+    }
+}
+
+impl ProviderAdapter for CohereAdapter {
+    fn name(&self) -> &'static str {
+        "cohere"
+    }
+
+    fn chat_url(&self) -> String {
+        format!("{}/chat", self.base_url)
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        vec![("authorization", format!("Bearer {}", api_key))]
+    }
+
+    fn build_request(&self, request: &ChatRequest) -> Result<Value, ProviderError> {
+        // rejected instead of dropped, the answer would ignore part of the request
+        if request.messages.iter().any(|message| message.has_images()) {
+            return Err(ProviderError::InvalidRequest(String::from("image inputs aren't supported by cohere")));
+        }
+
+        if !request.tools.is_empty() || request.messages.iter().any(|message| !message.tool_calls.is_empty()) {
+            return Err(ProviderError::InvalidRequest(String::from("tools aren't supported by cohere")));
+        }
+
+        // the last user message is sent on its own, everything before it is history
+        let last_user = request.messages.iter().rposition(|message| message.role == "user");
+
+        let mut preamble: Vec<String> = vec![];
+        let mut chat_history: Vec<Value> = vec![];
+        for (index, message) in request.messages.iter().enumerate() {
+            if Some(index) == last_user {
+                continue;
+            }
+
+            match message.role.as_str() {
+                "system" | "developer" => preamble.push(message.text()),
+                "assistant" => chat_history.push(json!({"role": "CHATBOT", "message": message.text()})),
+                "user" => chat_history.push(json!({"role": "USER", "message": message.text()})),
+                role => return Err(ProviderError::InvalidRequest(format!("{} messages aren't supported by cohere", role))),
+            }
+        }
+
+        let message = match last_user {
+            Some(index) => request.messages[index].text(),
+            None => String::new(),
+        };
+
+        let mut body = Map::new();
+        body.insert(String::from("model"), json!(request.model));
+        body.insert(String::from("message"), json!(message));
+
+        if !chat_history.is_empty() {
+            body.insert(String::from("chat_history"), json!(chat_history));
+        }
+
+        if !preamble.is_empty() {
+            body.insert(String::from("preamble"), json!(preamble.join("\n")));
+        }
+
+        if let Some(temperature) = request.temperature {
+            body.insert(String::from("temperature"), json!(temperature));
+        }
+
+        if let Some(max_tokens) = request.max_tokens {
+            body.insert(String::from("max_tokens"), json!(max_tokens));
+        }
+
+        if !request.stop.is_empty() {
+            body.insert(String::from("stop_sequences"), json!(request.stop));
+        }
+
+        if let Some(top_p) = request.top_p {
+            body.insert(String::from("p"), json!(top_p));
+        }
+
+        if let Some(seed) = request.seed {
+            body.insert(String::from("seed"), json!(seed));
+        }
+
+        if let Some(frequency_penalty) = request.frequency_penalty {
+            body.insert(String::from("frequency_penalty"), json!(frequency_penalty));
+        }
+
+        if let Some(presence_penalty) = request.presence_penalty {
+            body.insert(String::from("presence_penalty"), json!(presence_penalty));
+        }
+
+        match &request.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                body.insert(String::from("response_format"), json!({"type": "json_object"}));
+            }
+            Some(ResponseFormat::JsonSchema { schema, .. }) => {
+                body.insert(String::from("response_format"), json!({"type": "json_object", "schema": schema}));
+            }
+            Some(ResponseFormat::Text) | None => (),
+        }
+
+        Ok(Value::Object(body))
+    }
+
+    fn parse_response(&self, body: Value) -> Result<ChatResponse, ProviderError> {
+        let content = match body["text"].as_str() {
+            Some(text) => text.to_string(),
+            None => return Err(ProviderError::InvalidResponse(String::from("missing text"))),
+        };
+
+        let billed_units = &body["meta"]["billed_units"];
+        let prompt_tokens = billed_units["input_tokens"].as_u64().unwrap_or(0);
+        let completion_tokens = billed_units["output_tokens"].as_u64().unwrap_or(0);
+
+        Ok(ChatResponse {
+            id: body["generation_id"].as_str().unwrap_or_default().to_string(),
+            model: String::new(),
+            content,
+            tool_calls: vec![],
+            finish_reason: body["finish_reason"].as_str().map(finish_reason),
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            },
+        })
+    }
+
+    fn build_stream_request(&self, request: &ChatRequest) -> Result<Value, ProviderError> {
+        let mut body = self.build_request(request)?;
+        body["stream"] = json!(true);
+        Ok(body)
+    }
+
+    fn stream_framing(&self) -> StreamFraming {
+        StreamFraming::JsonLines
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<Vec<StreamEvent>, ProviderError> {
+        let event: Value = match serde_json::from_str(data) {
+            Ok(event) => event,
+            Err(e) => return Err(ProviderError::InvalidResponse(e.to_string())),
+        };
+
+        let mut events = vec![];
+        match event["event_type"].as_str().unwrap_or_default() {
+            "text-generation" => {
+                if let Some(text) = event["text"].as_str() {
+                    events.push(StreamEvent::Delta(text.to_string()));
+                }
+            }
+            "stream-end" => {
+                if let Some(reason) = event["finish_reason"].as_str() {
+                    events.push(StreamEvent::Finish(finish_reason(reason)));
+                }
+
+                let billed_units = &event["response"]["meta"]["billed_units"];
+                if let Some(input_tokens) = billed_units["input_tokens"].as_u64() {
+                    events.push(StreamEvent::PromptTokens(input_tokens));
+                }
+                if let Some(output_tokens) = billed_units["output_tokens"].as_u64() {
+                    events.push(StreamEvent::CompletionTokens(output_tokens));
+                }
+
+                events.push(StreamEvent::Done);
+            }
+            _ => (),
+        }
+
+        Ok(events)
+    }
+}
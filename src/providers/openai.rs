@@ -0,0 +1,160 @@
+//
+// OpenAI chat completions, https://platform.openai.com/docs/api-reference/chat
+//
+
+use serde_json::{json, Map, Value};
+
+use super::{ChatMessage, ChatRequest, ChatResponse, ContentPart, ProviderAdapter, ProviderError, StreamEvent, ToolCall, Usage};
+
+pub struct OpenAIAdapter {
+    base_url: String,
+}
+
+impl OpenAIAdapter {
+    pub fn new(base_url: &str) -> OpenAIAdapter {
+        OpenAIAdapter {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+// text only messages are sent as a string, messages with images as content parts
+fn openai_message(message: &ChatMessage) -> Value {
+    let content = match message.has_images() {
+        true => Value::Array(message.content
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text(text) => json!({"type": "text", "text": text}),
+                ContentPart::Image { url, detail } => match detail {
+                    Some(detail) => json!({"type": "image_url", "image_url": {"url": url, "detail": detail}}),
+                    None => json!({"type": "image_url", "image_url": {"url": url}}),
+                },
+            })
+            .collect()),
+        false if message.content.is_empty() && !message.tool_calls.is_empty() => Value::Null,
+        false => json!(message.text()),
+    };
+
+    let mut body = json!({"role": message.role, "content": content});
+    if !message.tool_calls.is_empty() {
+        body["tool_calls"] = Value::Array(message.tool_calls.iter().map(ToolCall::to_openai).collect());
+    }
+
+    if let Some(tool_call_id) = &message.tool_call_id {
+        body["tool_call_id"] = json!(tool_call_id);
+    }
+
+    if let Some(name) = &message.name {
+        body["name"] = json!(name);
+    }
+
+    body
+}
+
+impl ProviderAdapter for OpenAIAdapter {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn chat_url(&self) -> String {
+        format!("{}/chat/completions", self.base_url)
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        vec![("authorization", format!("Bearer {}", api_key))]
+    }
+
+    // the client's options go through as they are, tools and response_format
+    // included, only the model and the messages are replaced and streaming is
+    // set by build_stream_request
+    fn build_request(&self, request: &ChatRequest) -> Result<Value, ProviderError> {
+        let mut body: Map<String, Value> = request.options.clone();
+        body.remove("stream");
+        body.remove("stream_options");
+        body.insert(String::from("model"), json!(request.model));
+        body.insert(String::from("messages"), Value::Array(request.messages.iter().map(openai_message).collect()));
+
+        Ok(Value::Object(body))
+    }
+
+    fn parse_response(&self, body: Value) -> Result<ChatResponse, ProviderError> {
+        let choice = match body["choices"].get(0) {
+            Some(choice) => choice,
+            None => return Err(ProviderError::InvalidResponse(String::from("missing choices"))),
+        };
+
+        Ok(ChatResponse {
+            id: body["id"].as_str().unwrap_or_default().to_string(),
+            model: body["model"].as_str().unwrap_or_default().to_string(),
+            content: choice["message"]["content"].as_str().unwrap_or_default().to_string(),
+            tool_calls: choice["message"]["tool_calls"]
+                .as_array()
+                .map(|tool_calls| tool_calls
+                    .iter()
+                    .map(|tool_call| ToolCall {
+                        id: tool_call["id"].as_str().unwrap_or_default().to_string(),
+                        name: tool_call["function"]["name"].as_str().unwrap_or_default().to_string(),
+                        arguments: tool_call["function"]["arguments"].as_str().unwrap_or_default().to_string(),
+                    })
+                    .collect())
+                .unwrap_or_default(),
+            finish_reason: choice["finish_reason"].as_str().map(|reason| reason.to_string()),
+            usage: Usage {
+                prompt_tokens: body["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+                completion_tokens: body["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+                total_tokens: body["usage"]["total_tokens"].as_u64().unwrap_or(0),
+            },
+        })
+    }
+
+    fn build_stream_request(&self, request: &ChatRequest) -> Result<Value, ProviderError> {
+        let mut body = self.build_request(request)?;
+        body["stream"] = json!(true);
+        // the last chunk carries the usage of the whole completion
+        body["stream_options"] = json!({"include_usage": true});
+        Ok(body)
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<Vec<StreamEvent>, ProviderError> {
+        if data == "[DONE]" {
+            return Ok(vec![StreamEvent::Done]);
+        }
+
+        let chunk: Value = match serde_json::from_str(data) {
+            Ok(chunk) => chunk,
+            Err(e) => return Err(ProviderError::InvalidResponse(e.to_string())),
+        };
+
+        let mut events = vec![];
+        if let Some(choice) = chunk["choices"].get(0) {
+            if let Some(content) = choice["delta"]["content"].as_str() {
+                if !content.is_empty() {
+                    events.push(StreamEvent::Delta(content.to_string()));
+                }
+            }
+
+            for tool_call in choice["delta"]["tool_calls"].as_array().into_iter().flatten() {
+                events.push(StreamEvent::ToolCall {
+                    index: tool_call["index"].as_u64().unwrap_or(0),
+                    id: tool_call["id"].as_str().map(|id| id.to_string()),
+                    name: tool_call["function"]["name"].as_str().map(|name| name.to_string()),
+                    arguments: tool_call["function"]["arguments"].as_str().unwrap_or_default().to_string(),
+                });
+            }
+
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                events.push(StreamEvent::Finish(reason.to_string()));
+            }
+        }
+
+        if let Some(prompt_tokens) = chunk["usage"]["prompt_tokens"].as_u64() {
+            events.push(StreamEvent::PromptTokens(prompt_tokens));
+        }
+
+        if let Some(completion_tokens) = chunk["usage"]["completion_tokens"].as_u64() {
+            events.push(StreamEvent::CompletionTokens(completion_tokens));
+        }
+
+        Ok(events)
+    }
+}
@@ -1,3 +1,5 @@
+pub mod admin;
+pub mod completions;
 pub mod identity;
 pub mod customers;
 pub mod webhooks;
@@ -0,0 +1,66 @@
+use axum::extract::Query;
+use axum::http::HeaderMap;
+use axum::middleware;
+use axum::routing::{delete, get};
+use axum::{Router, routing::post};
+use crate::controllers::admin::reload_model;
+use crate::controllers::catalog::{get_catalog_admin, remove_catalog_model, upsert_catalog_model};
+use crate::controllers::request_logs::get_request_logs_admin;
+use crate::types::incoming_requests::RequestLogQueryParams;
+use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
+use std::{sync::Arc, time::Duration};
+
+// /api/admin
+pub async fn get_admin_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    return Router::new()
+        .route(
+            // load a classification or embedding model in the background and swap it in
+            "/models/reload",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| reload_model(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // model catalog shared by every org, including disabled models
+            "/catalog",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |headers| get_catalog_admin(headers, app_state)
+            }),
+        )
+        .route(
+            // add a model or override a bundled one
+            "/catalog",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| upsert_catalog_model(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // remove an override, bundled models go back to the shipped entry
+            "/catalog",
+            delete({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| remove_catalog_model(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // search any org's request logs, org_id is required
+            "/request.logs",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<RequestLogQueryParams>)| get_request_logs_admin(headers, query, app_state)
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "admin",
+                requests: 15,
+                window: Duration::from_secs(60),
+                openai_errors: false,
+            }),
+            rate_limit,
+        ));
+}
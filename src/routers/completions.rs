@@ -0,0 +1,28 @@
+use axum::middleware;
+use axum::{Router, routing::post};
+use crate::controllers::completions::chat_completions;
+use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
+use std::{sync::Arc, time::Duration};
+
+// /v1, OpenAI compatible so SDKs only need a different base url
+pub async fn get_completions_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
+    return Router::new()
+        .route(
+            // route the prompt and forward it to the selected model's provider
+            "/chat/completions",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| chat_completions(headers, payload, app_state)
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "completions",
+                requests: 256,
+                window: Duration::from_secs(60),
+                openai_errors: true,
+            }),
+            rate_limit,
+        ));
+}
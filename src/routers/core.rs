@@ -1,23 +1,30 @@
+use axum::extract::Query;
 use axum::routing::get;
-use axum::BoxError;
-use axum::error_handling::HandleErrorLayer;
-use axum::http::StatusCode;
+use axum::middleware;
 use axum::{Router, routing::post};
-use crate::controllers::llm::{get_models_list, process_prompt};
+use crate::controllers::llm::{get_embedding_models_list, get_models_list, process_prompt, report_model_feedback};
+use crate::types::incoming_requests::ModelCatalogQueryParams;
 use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
 use std::{sync::Arc, time::Duration};
 
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, ServiceBuilder};
-
 // /api/core
 pub async fn get_core_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     return Router::new()
         .route(
-            // suggest and model and cache it
-            // if cache is not empty, return the cached response
+            // catalog models, optionally filtered by vendor and capability
             "/models",
             get({
-                move || get_models_list()
+                let app_state = Arc::clone(&app_state);
+                move |query: Query<ModelCatalogQueryParams>| get_models_list(query, app_state)
+            }),
+        )
+        .route(
+            // sentence embeddings models routers can pick for sentence matching
+            "/embedding.models",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move || get_embedding_models_list(app_state)
             }),
         )
         .route(
@@ -29,6 +36,14 @@ pub async fn get_core_router(app_state: Arc<AppState>) -> Router<Arc<AppState>>
                 move |(headers, payload)| process_prompt(headers, payload, app_state)
             }),
         )
+        .route(
+            // latency and outcome of a provider call made with a routed model
+            "/feedback",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| report_model_feedback(headers, payload, app_state)
+            }),
+        )
         /*.route(
             // check if prompt is in cache
             "/prompt/cache",
@@ -45,15 +60,13 @@ pub async fn get_core_router(app_state: Arc<AppState>) -> Router<Arc<AppState>>
                 move |payload| process_prompt(payload, app_state)
             }),
         ) */
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Unhandled error: {}", err),
-                    )
-                }))
-                .layer(BufferLayer::new(256))
-                .layer(RateLimitLayer::new(256, Duration::from_secs(60))),
-        );
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "core",
+                requests: 256,
+                window: Duration::from_secs(60),
+                openai_errors: false,
+            }),
+            rate_limit,
+        ));
 }
\ No newline at end of file
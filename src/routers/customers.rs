@@ -1,19 +1,18 @@
 use axum::extract::rejection::JsonRejection;
 use axum::extract::Query;
 use axum::routing::{get, patch};
-use axum::{BoxError, Json};
-use axum::error_handling::HandleErrorLayer;
-use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use axum::middleware;
+use axum::http::HeaderMap;
 use axum::{Router, routing::post};
 use crate::controllers::customer::{create_customer_record, fetch_customer_record_by_id, update_name, update_password};
 use crate::controllers::email::{add_email, verify_email};
 use crate::types::incoming_requests::{CustomerAddEmail, CustomerUpdateName, CustomerUpdatePassword, FetchCustomerByID};
 use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
 
 use std::{sync::Arc, time::Duration};
 
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, ServiceBuilder};
-
 // /api/customers
 pub async fn get_customers_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     return Router::new()
@@ -73,15 +72,13 @@ pub async fn get_customers_router(app_state: Arc<AppState>) -> Router<Arc<AppSta
                 }
             }),
         )
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Unhandled error: {}", err),
-                    )
-                }))
-                .layer(BufferLayer::new(32))
-                .layer(RateLimitLayer::new(15, Duration::from_secs(60))),
-        );
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "customers",
+                requests: 15,
+                window: Duration::from_secs(60),
+                openai_errors: false,
+            }),
+            rate_limit,
+        ));
 }
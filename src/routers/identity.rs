@@ -1,14 +1,11 @@
-use axum::BoxError;
-use axum::error_handling::HandleErrorLayer;
-use axum::http::StatusCode;
+use axum::middleware;
 use axum::{Router, routing::{get, post, patch}};
 use crate::controllers::identity::{get_session, gooogle_authentication, legacy_authentication, renew_session};
 use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
 
 use std::{sync::Arc, time::Duration};
 
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, ServiceBuilder};
-
 // /api/identity
 pub async fn get_identity_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     return Router::new()
@@ -40,15 +37,13 @@ pub async fn get_identity_router(app_state: Arc<AppState>) -> Router<Arc<AppStat
                 move |headers| gooogle_authentication(headers, app_state)
             }),
         )
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Unhandled error: {}", err),
-                    )
-                }))
-                .layer(BufferLayer::new(256))
-                .layer(RateLimitLayer::new(15, Duration::from_secs(60))),
-        );
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "identity",
+                requests: 15,
+                window: Duration::from_secs(60),
+                openai_errors: false,
+            }),
+            rate_limit,
+        ));
 }
\ No newline at end of file
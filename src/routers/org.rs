@@ -1,14 +1,20 @@
 use axum::routing::{delete, get, patch};
-use axum::BoxError;
-use axum::error_handling::HandleErrorLayer;
-use axum::http::StatusCode;
+use axum::middleware;
+use axum::extract::Query;
+use axum::http::HeaderMap;
 use axum::{Router, routing::post};
-use crate::controllers::org::{create_model_org, create_org, create_router_org, delete_model_org, delete_org, edit_model_org, edit_org, edit_router_org, edit_router_prompt_classification_org, edit_router_sentence_matching_org, edit_router_single_model_org, get_models, get_org, get_routers};
+use crate::controllers::analytics::get_router_analytics;
+use crate::controllers::org_config::{export_org_config, import_org_config};
+use crate::controllers::request_logs::{edit_request_log_settings_org, get_request_logs};
+use crate::controllers::router_templates::{create_router_from_template_org, get_router_templates};
+use crate::controllers::usage::{get_usage, get_usage_timeseries};
+use crate::controllers::webhooks::{create_webhook_org, delete_webhook_org, get_webhook_deliveries, get_webhooks, redeliver_webhook_org};
+use crate::controllers::org::{create_credential_org, delete_credential_org, get_credentials, rotate_credential_org, create_model_org, create_org, create_router_org, delete_model_org, delete_org, edit_model_org, edit_org, edit_router_org, edit_router_fallback_models_org, edit_router_latency_org, edit_router_prompt_classification_org, edit_router_rules_org, edit_router_sentence_matching_org, edit_router_single_model_org, get_models, get_models_health, get_org, get_routers};
+use crate::types::incoming_requests::{OrgConfigQueryParams, RequestLogQueryParams, RouterAnalyticsQueryParams, UsageQueryParams, WebhookDeliveryQueryParams};
 use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
 use std::{sync::Arc, time::Duration};
 
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, ServiceBuilder};
-
 // /api/org
 pub async fn get_org_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     return Router::new()
@@ -44,6 +50,22 @@ pub async fn get_org_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
                 move |(headers, payload)| edit_org(headers, payload, app_state)
             }),
         )
+        .route(
+            // models and routers as a versioned json or yaml document
+            "/config",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<OrgConfigQueryParams>)| export_org_config(headers, query, app_state)
+            }),
+        )
+        .route(
+            // plan and apply a config document, dry_run returns the plan only
+            "/config",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query, body): (HeaderMap, Query<OrgConfigQueryParams>, String)| import_org_config(headers, query, body, app_state)
+            }),
+        )
         .route(
             // fetch model
             "/models",
@@ -76,6 +98,46 @@ pub async fn get_org_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
                 move |(headers, payload)| edit_model_org(headers, payload, app_state)
             }),
         )
+        .route(
+            // rolling latency and error rate of every model
+            "/models/health",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |headers| get_models_health(headers, app_state)
+            }),
+        )
+        .route(
+            // list provider credentials, keys are masked
+            "/credentials",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |headers| get_credentials(headers, app_state)
+            }),
+        )
+        .route(
+            // store an encrypted provider api key
+            "/credentials",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| create_credential_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // replace the api key of a credential
+            "/credentials",
+            patch({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| rotate_credential_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // delete credential
+            "/credentials",
+            delete({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| delete_credential_org(headers, payload, app_state)
+            }),
+        )
         .route(
             // fetch routers
             "/routers",
@@ -99,6 +161,36 @@ pub async fn get_org_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
             let app_state = Arc::clone(&app_state);
             move |(headers, payload)| edit_router_org(headers, payload, app_state)
         }))
+        .route(
+            // built in routers with categories, sentences and model slots
+            "/routers/templates",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |headers| get_router_templates(headers, app_state)
+            }),
+        )
+        .route(
+            // create a router from a template, slots are bound to org models
+            "/routers/templates",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| create_router_from_template_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // edit the time window and spend rules checked before other strategies
+            "/routers/rules", 
+            patch({
+            let app_state = Arc::clone(&app_state);
+            move |(headers, payload)| edit_router_rules_org(headers, payload, app_state)
+        }))
+        .route(
+            // latency routing of prompt classification categories
+            "/routers/latency", 
+            patch({
+            let app_state = Arc::clone(&app_state);
+            move |(headers, payload)| edit_router_latency_org(headers, payload, app_state)
+        }))
         .route(
             // edit routers prompt classification model
             "/routers/single.model", 
@@ -106,6 +198,13 @@ pub async fn get_org_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
             let app_state = Arc::clone(&app_state);
             move |(headers, payload)| edit_router_single_model_org(headers, payload, app_state)
         }))
+        .route(
+            // edit the models /v1/chat/completions falls back to
+            "/routers/fallback.models", 
+            patch({
+            let app_state = Arc::clone(&app_state);
+            move |(headers, payload)| edit_router_fallback_models_org(headers, payload, app_state)
+        }))
         .route(
             // edit routers prompt classification model
             "/routers/prompt.classification.model", 
@@ -120,15 +219,93 @@ pub async fn get_org_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
             let app_state = Arc::clone(&app_state);
             move |(headers, payload)| edit_router_sentence_matching_org(headers, payload, app_state)
         }))
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Unhandled error: {}", err),
-                    )
-                }))
-                .layer(BufferLayer::new(256))
-                .layer(RateLimitLayer::new(256, Duration::from_secs(60))),
-        );
+        .route(
+            // strategy hit rates, category distribution and latency of a router
+            "/routers/analytics",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<RouterAnalyticsQueryParams>)| get_router_analytics(headers, query, app_state)
+            }),
+        )
+        .route(
+            // list webhooks, secrets are masked
+            "/webhooks",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |headers| get_webhooks(headers, app_state)
+            }),
+        )
+        .route(
+            // register a webhook endpoint and its events
+            "/webhooks",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| create_webhook_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // delete webhook
+            "/webhooks",
+            delete({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| delete_webhook_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // delivery log, newest first
+            "/webhooks/deliveries",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<WebhookDeliveryQueryParams>)| get_webhook_deliveries(headers, query, app_state)
+            }),
+        )
+        .route(
+            // send a past delivery again
+            "/webhooks/redeliver",
+            post({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| redeliver_webhook_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // opt in prompt and decision log, sampling, redaction and retention
+            "/request.log",
+            patch({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, payload)| edit_request_log_settings_org(headers, payload, app_state)
+            }),
+        )
+        .route(
+            // search logged prompts and routing decisions
+            "/request.logs",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<RequestLogQueryParams>)| get_request_logs(headers, query, app_state)
+            }),
+        )
+        .route(
+            // usage and cost totals per router, model, access token or category
+            "/usage",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<UsageQueryParams>)| get_usage(headers, query, app_state)
+            }),
+        )
+        .route(
+            // usage and cost per day or hour
+            "/usage/timeseries",
+            get({
+                let app_state = Arc::clone(&app_state);
+                move |(headers, query): (HeaderMap, Query<UsageQueryParams>)| get_usage_timeseries(headers, query, app_state)
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "org",
+                requests: 256,
+                window: Duration::from_secs(60),
+                openai_errors: false,
+            }),
+            rate_limit,
+        ));
 }
@@ -1,16 +1,15 @@
-use axum::{BoxError, Json};
-use axum::error_handling::HandleErrorLayer;
+use axum::Json;
+use axum::middleware;
 use axum::extract::rejection::JsonRejection;
-use axum::http::{StatusCode, HeaderMap};
+use axum::http::HeaderMap;
 use axum::{Router, routing::post};
 
 use crate::lemonsqueezy::webhook::{orders_webhook_events_listener, subscription_webhook_events_listener};
 use crate::types::lemonsqueezy::{SubscriptionEvent, OrderEvent};
 use crate::types::state::AppState;
+use crate::utilities::rate_limit::{rate_limit, RateLimitPolicy, RateLimiter};
 use std::{sync::Arc, time::Duration};
 
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, ServiceBuilder};
-
 // /api/webhooks
 pub async fn get_webhooks_router(app_state: Arc<AppState>) -> Router<Arc<AppState>> {
     return Router::new()
@@ -35,15 +34,13 @@ pub async fn get_webhooks_router(app_state: Arc<AppState>) -> Router<Arc<AppStat
                 }
             }),
         )
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Unhandled error: {}", err),
-                    )
-                }))
-                .layer(BufferLayer::new(1024))
-                .layer(RateLimitLayer::new(120, Duration::from_secs(60))),
-        );
+        .layer(middleware::from_fn_with_state(
+            RateLimiter::new(Arc::clone(&app_state), RateLimitPolicy {
+                name: "webhooks",
+                requests: 120,
+                window: Duration::from_secs(60),
+                openai_errors: false,
+            }),
+            rate_limit,
+        ));
 }
\ No newline at end of file
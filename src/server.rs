@@ -1,8 +1,11 @@
 use crate::{
-    routers::{
-        core::get_core_router, customers::get_customers_router, identity::get_identity_router, org::get_org_router, webhooks::get_webhooks_router
-    }, types::{lemonsqueezy::Products, state::{AppState, EmailProviderSettings, GoogleAuth, MasterEmailEntity}}, utilities::helpers::fallback
+    inference::{registry::{EmbeddingModel, EmbeddingModelRegistry}, scheduler::{BatchSettings, ClassificationScheduler, EmbeddingScheduler}, slot::ModelSlot}, routers::{
+        admin::get_admin_router, completions::get_completions_router, core::get_core_router, customers::get_customers_router, identity::get_identity_router, org::get_org_router, webhooks::get_webhooks_router
+    }, types::{catalog::ModelCatalog, lemonsqueezy::Products, state::{AppState, EmailProviderSettings, FailoverSettings, GoogleAuth, MasterEmailEntity, ProviderSettings}}, utilities::{catalog::{load_catalog_overrides, start_catalog_refresh}, helpers::fallback, request_log::{create_request_log_indexes, start_request_log_purge}, vault::parse_encryption_key, webhooks::{migrate_webhooks, start_webhook_retries}}
 };
+use crate::controllers::health::get_health;
+use crate::providers::circuit_breaker::CircuitBreaker;
+use crate::storage::diesel_postgres::usage::{UsageWriter, UsageWriterSettings, MAX_USAGE_BATCH_SIZE};
 use axum::{
     routing::get,
     Router,
@@ -11,9 +14,14 @@ use diesel::{r2d2::ConnectionManager, PgConnection};
 use mongodb::Client as MongoClient;
 use r2d2::Pool;
 use redis::Client as RedisClient;
-use rust_bert::{pipelines::{common::{ModelResource, ModelType}, sentence_embeddings::{SentenceEmbeddingsBuilder, SentenceEmbeddingsModelType}, zero_shot_classification::{self, ZeroShotClassificationConfig, ZeroShotClassificationModel}}, resources::RemoteResource, RustBertError};
-use tokio::task;
-use std::{env, sync::{Arc, Mutex}, time::Duration};
+use crate::inference::backend::{RustBertError, SentenceEmbeddingsModel, SentenceEmbeddingsModelType, ZeroShotClassificationModel};
+#[cfg(feature = "torch")]
+use rust_bert::{pipelines::{common::{ModelResource, ModelType}, sentence_embeddings::SentenceEmbeddingsBuilder, zero_shot_classification::{self, ZeroShotClassificationConfig}}, resources::{LocalResource, RemoteResource}};
+#[cfg(feature = "torch")]
+use tokio::{sync::OnceCell, task};
+#[cfg(feature = "torch")]
+use std::path::PathBuf;
+use std::{collections::HashMap, env, net::SocketAddr, sync::Arc, time::Duration};
 
 use tower_http::timeout::TimeoutLayer;
 use tower_http::{
@@ -21,11 +29,28 @@ use tower_http::{
     cors::CorsLayer,
 };
 
-use log::info;
+use log::{error, info, warn};
 
 pub async fn init(mongodb_client: MongoClient, redis_connection: RedisClient, postgres_conn: Option<Pool<ConnectionManager<PgConnection>>>) {
     let app_state = set_app_state(mongodb_client, redis_connection, postgres_conn).await;
 
+    let request_log_purge_interval = match env::var("REQUEST_LOG_PURGE_INTERVAL_SECS") {
+        Ok(interval) => match interval.parse::<u64>() {
+            Ok(interval) if interval > 0 => Duration::from_secs(interval),
+            _ => panic!("REQUEST_LOG_PURGE_INTERVAL_SECS must be a positive number"),
+        },
+        Err(_) => panic!("REQUEST_LOG_PURGE_INTERVAL_SECS not found"),
+    };
+    if let Err(e) = create_request_log_indexes(&app_state.mongo_db).await {
+        error!("failed to create request log indexes: {}", e);
+    }
+    start_request_log_purge(app_state.clone(), request_log_purge_interval);
+    if let Err(e) = migrate_webhooks(&app_state).await {
+        error!("failed to migrate webhooks: {}", e);
+    }
+    start_webhook_retries(app_state.clone());
+    start_catalog_refresh(app_state.clone());
+
     // /api/org
     let org = get_org_router(app_state.clone()).await;
     // /api/customers
@@ -40,22 +65,35 @@ pub async fn init(mongodb_client: MongoClient, redis_connection: RedisClient, po
     // /api/llm/routers
     let core = get_core_router(app_state.clone()).await;
     info!("Core router loaded");
+    // /api/admin
+    let admin = get_admin_router(app_state.clone()).await;
+    info!("Admin router loaded");
     // /api
+    // /v1 proxies long provider calls and streams, so the timeout only applies to /api
     let api = Router::new()
         .nest("/org", org)
         .nest("/customers", customers)
         .nest("/identity", identity)
         .nest("/webhooks", webhooks)
-        .nest("/core", core);
+        .nest("/core", core)
+        .nest("/admin", admin)
+        .layer(TimeoutLayer::new(Duration::from_secs(10)));
 
     info!("API router loaded");
 
+    // /v1
+    let completions = get_completions_router(app_state.clone()).await;
+    info!("Completions router loaded");
+
     let app = Router::new()
-        .route("/service/health", get(|| async { "OK" }))
+        .route("/service/health", get({
+            let app_state = Arc::clone(&app_state);
+            move || get_health(app_state)
+        }))
         .nest("/api", api)
+        .nest("/v1", completions)
         .layer(CorsLayer::permissive())
         .layer(CompressionLayer::new())
-        .layer(TimeoutLayer::new(Duration::from_secs(10)),)
         .fallback(fallback)
         .with_state(app_state);
 
@@ -70,7 +108,8 @@ pub async fn init(mongodb_client: MongoClient, redis_connection: RedisClient, po
         Err(e) => panic!("Error binding to address: {}", e),
     };
 
-    match axum::serve(listener, app).await {
+    // the rate limiter falls back to the peer address when no proxy header is set
+    match axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await {
         Ok(_) => info!("Server started"),
         Err(e) => panic!("Error starting server: {}", e),
     };
@@ -82,6 +121,10 @@ pub async fn set_app_state(mongodb_client: MongoClient, redis_connection: RedisC
         Err(_) => false,
     };
 
+    if production && !cfg!(feature = "torch") {
+        panic!("PRODUCTION requires a build with the torch feature");
+    }
+
     let api_url = match env::var("API_URL") {
         Ok(url) => url,
         Err(_) => panic!("api_url not found"),
@@ -183,57 +226,254 @@ pub async fn set_app_state(mongodb_client: MongoClient, redis_connection: RedisC
         redirect_url: google_oauth_redirect_url,
     };
 
+    // customers allowed to use /api/admin, comma separated customer ids
+    let platform_admins: Vec<String> = match env::var("PLATFORM_ADMIN_IDS") {
+        Ok(ids) => ids
+            .split(",")
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    // only behind a proxy that sets Fly-Client-IP or X-Forwarded-For, clients could
+    // pick their own rate limit address otherwise
+    let trust_proxy_headers = match env::var("TRUST_PROXY_HEADERS") {
+        Ok(val) => val.parse::<bool>().unwrap_or(false),
+        Err(_) => false,
+    };
+
     let prompt_classification_model_name = match env::var("PROMPT_CLASSIFICATION_MODEL_NAME") {
         Ok(name) => name,
         Err(_) => panic!("PROMPT_CLASSIFICATION_MODEL_NAME not found"),
     };
 
+    // a local directory takes precedence over the remote url, so air-gapped
+    // deployments never reach out to Hugging Face
+    let prompt_classification_model_path = match env::var("PROMPT_CLASSIFICATION_MODEL_PATH") {
+        Ok(path) if !path.is_empty() => Some(path),
+        _ => None,
+    };
+
     let prompt_classification_model_url = match env::var("PROMPT_CLASSIFICATION_MODEL_URL") {
         Ok(url) => url,
-        Err(_) => panic!("PROMPT_CLASSIFICATION_MODEL_URL not found"),
+        Err(_) => match prompt_classification_model_path {
+            Some(_) => String::new(),
+            None => panic!("PROMPT_CLASSIFICATION_MODEL_URL not found"),
+        },
+    };
+
+    let embedding_model_path = match env::var("SENTENCE_EMBEDDINGS_MODEL_PATH") {
+        Ok(path) if !path.is_empty() => Some(path),
+        _ => None,
+    };
+
+    // the first model listed is the default one for routers that don't pick any
+    let embedding_models_config = match env::var("SENTENCE_EMBEDDINGS_MODELS") {
+        Ok(raw) if !raw.is_empty() => match parse_sentence_embeddings_models(&raw) {
+            Ok(config) => config,
+            Err(e) => panic!("SENTENCE_EMBEDDINGS_MODELS is invalid: {}", e),
+        },
+        _ => vec![SentenceEmbeddingsModelConfig {
+            id: String::from("default"),
+            name: String::from(DEFAULT_SENTENCE_EMBEDDINGS_MODEL),
+            path: embedding_model_path,
+            similarity_threshold: DEFAULT_SIMILARITY_THRESHOLD,
+        }],
+    };
+
+    let inference_max_batch_size = match env::var("INFERENCE_MAX_BATCH_SIZE") {
+        Ok(size) => match size.parse::<usize>() {
+            Ok(size) if size > 0 => size,
+            _ => panic!("INFERENCE_MAX_BATCH_SIZE must be a positive number"),
+        },
+        Err(_) => panic!("INFERENCE_MAX_BATCH_SIZE not found"),
+    };
+
+    let inference_batch_window_ms = match env::var("INFERENCE_BATCH_WINDOW_MS") {
+        Ok(window) => match window.parse::<u64>() {
+            Ok(window) => window,
+            Err(_) => panic!("INFERENCE_BATCH_WINDOW_MS must be a number"),
+        },
+        Err(_) => panic!("INFERENCE_BATCH_WINDOW_MS not found"),
+    };
+
+    let batch_settings = BatchSettings {
+        max_batch_size: inference_max_batch_size,
+        max_wait: Duration::from_millis(inference_batch_window_ms),
     };
 
     if !production {
-        info!("Running in development mode, skipping loading of models");
+        info!("Running in development mode, skipping loading of models, prompts are routed with lexical similarity");
     }
 
     let mut zero_shot_prompt_classification_model = None;
     if production {
-        zero_shot_prompt_classification_model = match zero_shot_create_prompt_classify_model(&prompt_classification_model_name, &prompt_classification_model_url).await {
-            Ok(model) => Some(Arc::new(Box::new(Mutex::new(model)))),
+        let model_result = match &prompt_classification_model_path {
+            Some(path) => zero_shot_create_local_prompt_classify_model(&prompt_classification_model_name, path).await,
+            None => zero_shot_create_prompt_classify_model(&prompt_classification_model_name, &prompt_classification_model_url).await,
+        };
+
+        zero_shot_prompt_classification_model = match model_result {
+            Ok(model) => Some(model),
             Err(e) => panic!("Error creating prompt classification model: {}", e),
         };
     }
 
-    let mut embedding_model = None;
-    
-    if production {
-        let embedding_model_result = match task::spawn_blocking(move || {
-            SentenceEmbeddingsBuilder::remote(SentenceEmbeddingsModelType::AllMiniLmL12V2).create_model()
-        }).await.map_err(|e| RustBertError::TchError(e.to_string())) {
-            Ok(model) => model,
-            Err(e) => panic!("Error creating sentence embedding model: {}", e),
-        };
+    let mut embedding_models = vec![];
+    for config in embedding_models_config {
+        let mut model = None;
+        if production {
+            model = match create_sentence_embeddings_model(&config.name, &config.path).await {
+                Ok(model) => Some(model),
+                Err(e) => panic!("Error creating sentence embedding model {}: {}", config.id, e),
+            };
+        }
+
+        embedding_models.push((config, model));
+    }
 
-        embedding_model = match embedding_model_result {
-            Ok(model) => Some(Arc::new(Box::new(Mutex::new(model)))),
-            Err(e) => panic!("Error creating sentence embedding model: {}", e),
-        };
+    // slots and schedulers exist even without a loaded model, so a model can be
+    // hot-swapped in later through /api/admin/models/reload
+    let prompt_classification_slot = Arc::new(ModelSlot::new(
+        zero_shot_prompt_classification_model,
+        prompt_classification_model_name,
+        prompt_classification_model_url,
+        prompt_classification_model_path,
+    ));
+
+    let mut embedding_models_registry = EmbeddingModelRegistry {
+        default_model_id: embedding_models[0].0.id.clone(),
+        models: HashMap::new(),
+    };
+
+    for (config, model) in embedding_models {
+        let slot = Arc::new(ModelSlot::new(model, config.name, String::new(), config.path));
+        let scheduler = EmbeddingScheduler::new(Arc::clone(&slot), batch_settings);
+        embedding_models_registry.models.insert(
+            config.id.clone(),
+            EmbeddingModel::new(config.id, slot, scheduler, config.similarity_threshold),
+        );
+    }
+
+    let provider_settings = ProviderSettings {
+        openai_api_key: env::var("OPENAI_API_KEY").ok().filter(|key| !key.is_empty()),
+        openai_api_base_url: match env::var("OPENAI_API_BASE_URL") {
+            Ok(url) => url.trim_end_matches('/').to_string(),
+            Err(_) => panic!("OPENAI_API_BASE_URL not found"),
+        },
+        anthropic_api_key: env::var("ANTHROPIC_API_KEY").ok().filter(|key| !key.is_empty()),
+        anthropic_api_base_url: match env::var("ANTHROPIC_API_BASE_URL") {
+            Ok(url) => url.trim_end_matches('/').to_string(),
+            Err(_) => panic!("ANTHROPIC_API_BASE_URL not found"),
+        },
+        cohere_api_key: env::var("COHERE_API_KEY").ok().filter(|key| !key.is_empty()),
+        cohere_api_base_url: match env::var("COHERE_API_BASE_URL") {
+            Ok(url) => url.trim_end_matches('/').to_string(),
+            Err(_) => panic!("COHERE_API_BASE_URL not found"),
+        },
+        request_timeout: match env::var("PROVIDER_REQUEST_TIMEOUT_SECS") {
+            Ok(secs) => match secs.parse::<u64>() {
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => panic!("PROVIDER_REQUEST_TIMEOUT_SECS must be a number"),
+            },
+            Err(_) => panic!("PROVIDER_REQUEST_TIMEOUT_SECS not found"),
+        },
+    };
+
+    let failover_settings = FailoverSettings {
+        max_retries: match env::var("FAILOVER_MAX_RETRIES") {
+            Ok(retries) => match retries.parse::<u32>() {
+                Ok(retries) => retries,
+                Err(_) => panic!("FAILOVER_MAX_RETRIES must be a number"),
+            },
+            Err(_) => panic!("FAILOVER_MAX_RETRIES not found"),
+        },
+        backoff: match env::var("FAILOVER_BACKOFF_MS") {
+            Ok(backoff) => match backoff.parse::<u64>() {
+                Ok(backoff) => Duration::from_millis(backoff),
+                Err(_) => panic!("FAILOVER_BACKOFF_MS must be a number"),
+            },
+            Err(_) => panic!("FAILOVER_BACKOFF_MS not found"),
+        },
+    };
+
+    let circuit_breaker_threshold = match env::var("CIRCUIT_BREAKER_FAILURE_THRESHOLD") {
+        Ok(threshold) => match threshold.parse::<u32>() {
+            Ok(threshold) if threshold > 0 => threshold,
+            _ => panic!("CIRCUIT_BREAKER_FAILURE_THRESHOLD must be a positive number"),
+        },
+        Err(_) => panic!("CIRCUIT_BREAKER_FAILURE_THRESHOLD not found"),
+    };
+
+    let circuit_breaker_cooldown = match env::var("CIRCUIT_BREAKER_COOLDOWN_SECS") {
+        Ok(cooldown) => match cooldown.parse::<u64>() {
+            Ok(cooldown) => Duration::from_secs(cooldown),
+            Err(_) => panic!("CIRCUIT_BREAKER_COOLDOWN_SECS must be a number"),
+        },
+        Err(_) => panic!("CIRCUIT_BREAKER_COOLDOWN_SECS not found"),
+    };
+
+    let usage_writer_settings = UsageWriterSettings {
+        buffer_size: match env::var("USAGE_BUFFER_SIZE") {
+            Ok(size) => match size.parse::<usize>() {
+                Ok(size) if size > 0 => size,
+                _ => panic!("USAGE_BUFFER_SIZE must be a positive number"),
+            },
+            Err(_) => panic!("USAGE_BUFFER_SIZE not found"),
+        },
+        max_batch_size: match env::var("USAGE_MAX_BATCH_SIZE") {
+            Ok(size) => match size.parse::<usize>() {
+                Ok(size) if size > MAX_USAGE_BATCH_SIZE => panic!("USAGE_MAX_BATCH_SIZE can't be over {}", MAX_USAGE_BATCH_SIZE),
+                Ok(size) if size > 0 => size,
+                _ => panic!("USAGE_MAX_BATCH_SIZE must be a positive number"),
+            },
+            Err(_) => panic!("USAGE_MAX_BATCH_SIZE not found"),
+        },
+        flush_interval: match env::var("USAGE_FLUSH_INTERVAL_MS") {
+            Ok(interval) => match interval.parse::<u64>() {
+                Ok(interval) => Duration::from_millis(interval),
+                Err(_) => panic!("USAGE_FLUSH_INTERVAL_MS must be a number"),
+            },
+            Err(_) => panic!("USAGE_FLUSH_INTERVAL_MS not found"),
+        },
+    };
+
+    // usage metering is off without PostgreSQL
+    let usage_writer = UsageWriter::new(postgres_conn.clone(), usage_writer_settings);
+
+    let credentials_encryption_key = match env::var("CREDENTIALS_ENCRYPTION_KEY") {
+        Ok(key) => match parse_encryption_key(&key) {
+            Ok(key) => Some(key),
+            Err(e) => panic!("CREDENTIALS_ENCRYPTION_KEY is invalid: {}", e),
+        },
+        Err(_) => None,
+    };
+
+    let model_catalog = match ModelCatalog::bundled() {
+        Ok(catalog) => Arc::new(catalog),
+        Err(e) => panic!("Bundled model catalog is invalid: {}", e),
+    };
+
+    // the bundled catalog is enough to serve requests, overrides are retried by the refresh
+    match load_catalog_overrides(&mongo_db, &model_catalog).await {
+        Ok(loaded) => info!("Model catalog loaded with {} overrides", loaded),
+        Err(e) => warn!("Error loading model catalog overrides: {}", e),
     }
 
     let llm_resources = crate::types::state::LLMResources {
         prompt_classification_model: crate::types::state::PromptClassificationModel {
-            model: zero_shot_prompt_classification_model,
-            name: prompt_classification_model_name,
-            url: prompt_classification_model_url,
+            slot: Arc::clone(&prompt_classification_slot),
+            scheduler: ClassificationScheduler::new(prompt_classification_slot, batch_settings),
         },
-        embedding_model,
+        embedding_models: embedding_models_registry,
     };
 
     let app_state = Arc::new(AppState {
         production,
         mongodb_client,
         redis_connection,
+        redis_manager: Arc::new(OnceCell::new()),
         postgres_conn,
         mongo_db,
         lemonsqueezy_webhook_signature_key,
@@ -244,13 +484,118 @@ pub async fn set_app_state(mongodb_client: MongoClient, redis_connection: RedisC
         master_email_entity,
         email_provider_settings,
         google_auth,
+        platform_admins,
+        trust_proxy_headers,
         llm_resources,
+        http_client: reqwest::Client::new(),
+        provider_settings,
+        failover_settings,
+        circuit_breaker: Arc::new(CircuitBreaker::new(circuit_breaker_threshold, circuit_breaker_cooldown)),
+        credentials_encryption_key,
+        usage_writer,
+        model_catalog,
     });
 
     return app_state;
 }
 
+pub const DEFAULT_SENTENCE_EMBEDDINGS_MODEL: &str = "all-minilm-l12-v2";
+pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.5;
+
+pub struct SentenceEmbeddingsModelConfig {
+    pub id: String,
+    pub name: String,
+    pub path: Option<String>,
+    pub similarity_threshold: f32,
+}
+
+// id=source[;threshold] entries separated by commas, the source is either a known
+// model name or a local directory, e.g.
+// default=all-minilm-l12-v2;0.5,multilingual=distiluse-base-multilingual-cased,mpnet=/models/all-mpnet-base-v2;0.6
+pub fn parse_sentence_embeddings_models(raw: &str) -> Result<Vec<SentenceEmbeddingsModelConfig>, String> {
+    let mut models: Vec<SentenceEmbeddingsModelConfig> = vec![];
+    for entry in raw.split(",").map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+        let (id, value) = match entry.split_once("=") {
+            Some((id, value)) => (id.trim(), value.trim()),
+            None => return Err(format!("missing '=' in {}", entry)),
+        };
+
+        if id.is_empty() || models.iter().any(|model| model.id == id) {
+            return Err(format!("invalid or duplicated id in {}", entry));
+        }
+
+        let (source, similarity_threshold) = match value.split_once(";") {
+            Some((source, threshold)) => match threshold.trim().parse::<f32>() {
+                Ok(threshold) if threshold > 0.0 && threshold <= 1.0 => (source.trim(), threshold),
+                _ => return Err(format!("invalid threshold in {}", entry)),
+            },
+            None => (value, DEFAULT_SIMILARITY_THRESHOLD),
+        };
+
+        let (name, path) = match sentence_embeddings_model_type(source) {
+            Some(_) => (source.to_string(), None),
+            None if source.starts_with("/") || source.starts_with(".") => {
+                let name = source.trim_end_matches("/").rsplit("/").next().unwrap_or(source);
+                (name.to_string(), Some(source.to_string()))
+            },
+            None => return Err(format!("unknown model in {}", entry)),
+        };
+
+        models.push(SentenceEmbeddingsModelConfig {
+            id: id.to_string(),
+            name,
+            path,
+            similarity_threshold,
+        });
+    }
+
+    if models.is_empty() {
+        return Err(String::from("no models"));
+    }
+
+    Ok(models)
+}
+
+pub fn sentence_embeddings_model_type(name: &str) -> Option<SentenceEmbeddingsModelType> {
+    match name {
+        "all-minilm-l12-v2" => Some(SentenceEmbeddingsModelType::AllMiniLmL12V2),
+        "all-minilm-l6-v2" => Some(SentenceEmbeddingsModelType::AllMiniLmL6V2),
+        "all-distilroberta-v1" => Some(SentenceEmbeddingsModelType::AllDistilrobertaV1),
+        "distiluse-base-multilingual-cased" => Some(SentenceEmbeddingsModelType::DistiluseBaseMultilingualCased),
+        "bert-base-nli-mean-tokens" => Some(SentenceEmbeddingsModelType::BertBaseNliMeanTokens),
+        "paraphrase-albert-small-v2" => Some(SentenceEmbeddingsModelType::ParaphraseAlbertSmallV2),
+        "sentence-t5-base" => Some(SentenceEmbeddingsModelType::SentenceT5Base),
+        _ => None,
+    }
+}
+
+// a local directory wins over the named Hugging Face model
+#[cfg(feature = "torch")]
+pub async fn create_sentence_embeddings_model(name: &String, path: &Option<String>) -> Result<SentenceEmbeddingsModel, RustBertError> {
+    let model_type = sentence_embeddings_model_type(name);
+    if path.is_none() && model_type.is_none() {
+        return Err(RustBertError::InvalidConfigurationError(format!("unknown sentence embeddings model: {}", name)));
+    }
+
+    let path = path.clone();
+    let name = name.clone();
+    task::spawn_blocking(move || {
+        match (path, model_type) {
+            (Some(path), _) => {
+                info!("Loading Sentence Embeddings Model: {} from: {}", name, path);
+                SentenceEmbeddingsBuilder::local(path).create_model()
+            },
+            (None, Some(model_type)) => {
+                info!("Loading Sentence Embeddings Model: {}", name);
+                SentenceEmbeddingsBuilder::remote(model_type).create_model()
+            },
+            (None, None) => Err(RustBertError::InvalidConfigurationError(format!("unknown sentence embeddings model: {}", name))),
+        }
+    }).await.map_err(|e| RustBertError::TchError(e.to_string()))?
+}
+
 // test function to test with another different model
+#[cfg(feature = "torch")]
 pub async fn zero_shot_create_prompt_classify_model(name: &String, url: &String) -> Result<ZeroShotClassificationModel, RustBertError> {
     info!("Loading Prompt Classification Model: {} from: {}", name, url);                 
     let config_resource = Box::new(RemoteResource::from_pretrained((
@@ -287,4 +632,53 @@ pub async fn zero_shot_create_prompt_classify_model(name: &String, url: &String)
     }).await.map_err(|e| RustBertError::TchError(e.to_string()))?;
 
     return model_result;
-}
\ No newline at end of file
+}
+
+// same files as the remote model (config.json, vocab.json, merges.txt, rust_model.ot),
+// read from a directory on disk instead of being downloaded
+#[cfg(feature = "torch")]
+pub async fn zero_shot_create_local_prompt_classify_model(name: &String, path: &String) -> Result<ZeroShotClassificationModel, RustBertError> {
+    info!("Loading Prompt Classification Model: {} from: {}", name, path);
+    let directory = PathBuf::from(path);
+
+    for file in ["config.json", "vocab.json", "merges.txt", "rust_model.ot"] {
+        if !directory.join(file).is_file() {
+            return Err(RustBertError::IOError(format!("{} not found in {}", file, path)));
+        }
+    }
+
+    let config_resource = Box::new(LocalResource::from(directory.join("config.json")));
+    let vocab_resource = Box::new(LocalResource::from(directory.join("vocab.json")));
+    let merges_resource = Box::new(LocalResource::from(directory.join("merges.txt")));
+    let model_resource = ModelResource::Torch(Box::new(LocalResource::from(directory.join("rust_model.ot"))));
+
+    let config = ZeroShotClassificationConfig {
+        model_type: ModelType::Bart,
+        model_resource,
+        config_resource,
+        vocab_resource,
+        merges_resource: Some(merges_resource),
+        ..Default::default()
+    };
+
+    let model_result = task::spawn_blocking(move || {
+        ZeroShotClassificationModel::new(config)
+    }).await.map_err(|e| RustBertError::TchError(e.to_string()))?;
+
+    return model_result;
+}
+
+#[cfg(not(feature = "torch"))]
+pub async fn create_sentence_embeddings_model(name: &String, _path: &Option<String>) -> Result<SentenceEmbeddingsModel, RustBertError> {
+    Err(RustBertError::InvalidConfigurationError(format!("{} can't be loaded, the api was built without the torch feature", name)))
+}
+
+#[cfg(not(feature = "torch"))]
+pub async fn zero_shot_create_prompt_classify_model(name: &String, _url: &String) -> Result<ZeroShotClassificationModel, RustBertError> {
+    Err(RustBertError::InvalidConfigurationError(format!("{} can't be loaded, the api was built without the torch feature", name)))
+}
+
+#[cfg(not(feature = "torch"))]
+pub async fn zero_shot_create_local_prompt_classify_model(name: &String, _path: &String) -> Result<ZeroShotClassificationModel, RustBertError> {
+    Err(RustBertError::InvalidConfigurationError(format!("{} can't be loaded, the api was built without the torch feature", name)))
+}
@@ -1,9 +1,20 @@
+pub mod analytics;
+pub mod schema;
+pub mod usage;
+
 use r2d2 as original_r2d2;
 use diesel::{
+    connection::SimpleConnection,
     prelude::*,
     r2d2::{ConnectionManager, Pool},
 };
 
+// applied in order, each one has to be safe to run again
+const MIGRATIONS: [&str; 2] = [
+    include_str!("diesel_postgres/migrations/usage_ledger.sql"),
+    include_str!("diesel_postgres/migrations/routing_analytics.sql"),
+];
+
 pub async fn new_connection(uri: &str) -> Result<Pool<ConnectionManager<PgConnection>>, original_r2d2::Error> {
     let manager = ConnectionManager::<PgConnection>::new(uri);
     let pool = match Pool::builder().build(manager) {
@@ -12,4 +23,20 @@ pub async fn new_connection(uri: &str) -> Result<Pool<ConnectionManager<PgConnec
     };
 
     Ok(pool)
+}
+
+// migrations only create what is missing, so they run on every start
+pub fn run_migrations(pool: &Pool<ConnectionManager<PgConnection>>) -> Result<(), String> {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    for migration in MIGRATIONS {
+        if let Err(e) = conn.batch_execute(migration) {
+            return Err(e.to_string());
+        }
+    }
+
+    Ok(())
 }
\ No newline at end of file
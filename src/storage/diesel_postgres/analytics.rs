@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::*,
+    r2d2::{ConnectionManager, Pool},
+    sql_types::{BigInt, Double, Int4, Text, Timestamptz},
+};
+
+use super::usage::{UsageGranularity, ROUTING_KIND};
+
+// number of equal width score buckets between 0 and 1
+pub const SCORE_BUCKETS: i32 = 10;
+
+#[derive(Debug, Clone)]
+pub struct RouterAnalyticsQuery {
+    pub org_id: String,
+    pub router_id: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub granularity: UsageGranularity,
+}
+
+#[derive(Debug, Clone, QueryableByName)]
+pub struct DecisionCount {
+    #[diesel(sql_type = Text)]
+    pub strategy: String,
+    #[diesel(sql_type = Text)]
+    pub category: String,
+    #[diesel(sql_type = Text)]
+    pub model_id: String,
+    #[diesel(sql_type = Text)]
+    pub status: String,
+    #[diesel(sql_type = BigInt)]
+    pub decisions: i64,
+}
+
+#[derive(Debug, Clone, QueryableByName)]
+pub struct ScoreBucket {
+    #[diesel(sql_type = Text)]
+    pub strategy: String,
+    // 1 based, scores of exactly 1 land in SCORE_BUCKETS + 1
+    #[diesel(sql_type = Int4)]
+    pub bucket: i32,
+    #[diesel(sql_type = BigInt)]
+    pub decisions: i64,
+}
+
+#[derive(Debug, Clone, QueryableByName)]
+pub struct LatencyPoint {
+    #[diesel(sql_type = Timestamptz)]
+    pub period: DateTime<Utc>,
+    #[diesel(sql_type = BigInt)]
+    pub decisions: i64,
+    #[diesel(sql_type = Double)]
+    pub p50_latency_ms: f64,
+    #[diesel(sql_type = Double)]
+    pub p95_latency_ms: f64,
+}
+
+pub struct RouterAnalyticsRows {
+    pub decisions: Vec<DecisionCount>,
+    pub scores: Vec<ScoreBucket>,
+    pub latency: Vec<LatencyPoint>,
+}
+
+const ROUTER_DECISIONS_FILTER: &str = "org_id = $1 AND router_id = $2 AND created_at >= $3 AND created_at < $4 AND kind = $5";
+// decisions that picked a model, errors return before most of the routing work
+const ROUTED_FILTER: &str = "status IN ('ok', 'no_match')";
+
+pub fn find_router_analytics(pool: &Pool<ConnectionManager<PgConnection>>, query: &RouterAnalyticsQuery) -> Result<RouterAnalyticsRows, String> {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let decisions_sql = format!(
+        "SELECT strategy, COALESCE(category, '') AS category, model_id, status, COUNT(*) AS decisions \
+        FROM usage_ledger WHERE {filter} \
+        GROUP BY strategy, category, model_id, status",
+        filter = ROUTER_DECISIONS_FILTER,
+    );
+
+    let scores_sql = format!(
+        "SELECT strategy, width_bucket(score, 0, 1, {buckets}) AS bucket, COUNT(*) AS decisions \
+        FROM usage_ledger WHERE {filter} AND score IS NOT NULL \
+        GROUP BY strategy, bucket ORDER BY strategy, bucket",
+        buckets = SCORE_BUCKETS,
+        filter = ROUTER_DECISIONS_FILTER,
+    );
+
+    let latency_sql = format!(
+        "SELECT date_trunc('{granularity}', created_at) AS period, COUNT(*) AS decisions, \
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY latency_ms) AS p50_latency_ms, \
+            percentile_cont(0.95) WITHIN GROUP (ORDER BY latency_ms) AS p95_latency_ms \
+        FROM usage_ledger WHERE {filter} AND {routed} \
+        GROUP BY period ORDER BY period",
+        granularity = query.granularity.as_str(),
+        filter = ROUTER_DECISIONS_FILTER,
+        routed = ROUTED_FILTER,
+    );
+
+    let decisions = diesel::sql_query(decisions_sql)
+        .bind::<Text, _>(&query.org_id)
+        .bind::<Text, _>(&query.router_id)
+        .bind::<Timestamptz, _>(query.from)
+        .bind::<Timestamptz, _>(query.to)
+        .bind::<Text, _>(ROUTING_KIND)
+        .load::<DecisionCount>(&mut conn);
+
+    let decisions = match decisions {
+        Ok(decisions) => decisions,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let scores = diesel::sql_query(scores_sql)
+        .bind::<Text, _>(&query.org_id)
+        .bind::<Text, _>(&query.router_id)
+        .bind::<Timestamptz, _>(query.from)
+        .bind::<Timestamptz, _>(query.to)
+        .bind::<Text, _>(ROUTING_KIND)
+        .load::<ScoreBucket>(&mut conn);
+
+    let scores = match scores {
+        Ok(scores) => scores,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let latency = diesel::sql_query(latency_sql)
+        .bind::<Text, _>(&query.org_id)
+        .bind::<Text, _>(&query.router_id)
+        .bind::<Timestamptz, _>(query.from)
+        .bind::<Timestamptz, _>(query.to)
+        .bind::<Text, _>(ROUTING_KIND)
+        .load::<LatencyPoint>(&mut conn);
+
+    let latency = match latency {
+        Ok(latency) => latency,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    Ok(RouterAnalyticsRows {
+        decisions,
+        scores,
+        latency,
+    })
+}
@@ -0,0 +1,3 @@
+ALTER TABLE usage_ledger ADD COLUMN IF NOT EXISTS score DOUBLE PRECISION;
+
+CREATE INDEX IF NOT EXISTS usage_ledger_router_id_created_at_idx ON usage_ledger (router_id, created_at);
\ No newline at end of file
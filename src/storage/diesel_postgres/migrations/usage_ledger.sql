@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS usage_ledger (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    kind TEXT NOT NULL,
+    org_id TEXT NOT NULL,
+    router_id TEXT NOT NULL,
+    access_token TEXT NOT NULL,
+    model_id TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    category TEXT,
+    status TEXT NOT NULL,
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    latency_ms BIGINT NOT NULL DEFAULT 0,
+    cost DOUBLE PRECISION NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS usage_ledger_org_id_created_at_idx ON usage_ledger (org_id, created_at);
\ No newline at end of file
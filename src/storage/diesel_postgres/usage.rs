@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::*,
+    r2d2::{ConnectionManager, Pool},
+    sql_types::{BigInt, Nullable, Text, Timestamptz},
+};
+use log::{error, warn};
+use tokio::{sync::mpsc, task, time::{timeout_at, Instant}};
+
+use super::schema::usage_ledger;
+
+pub const ROUTING_KIND: &str = "routing";
+pub const COMPLETION_KIND: &str = "completion";
+
+// postgres takes at most 65535 bind parameters per statement, one per column of each record
+const USAGE_RECORD_COLUMNS: usize = 14;
+pub const MAX_USAGE_BATCH_SIZE: usize = u16::MAX as usize / USAGE_RECORD_COLUMNS;
+
+// one routing decision (/api/llm/prompt) or one proxied provider call (/v1/chat/completions)
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = usage_ledger)]
+pub struct UsageRecord {
+    pub created_at: DateTime<Utc>,
+    pub kind: String,
+    pub org_id: String,
+    pub router_id: String,
+    // sha256 fingerprint, access tokens are never stored
+    pub access_token: String,
+    pub model_id: String,
+    pub strategy: String,
+    pub category: Option<String>,
+    pub status: String,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub latency_ms: i64,
+    // USD from catalog pricing, 0 for custom models
+    pub cost: f64,
+    // confidence of the routing decision, label or similarity score
+    pub score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageGroup {
+    Router,
+    Model,
+    AccessToken,
+    Category,
+}
+
+impl UsageGroup {
+    pub fn parse(value: &str) -> Option<UsageGroup> {
+        match value {
+            "router" => Some(UsageGroup::Router),
+            "model" => Some(UsageGroup::Model),
+            "access_token" => Some(UsageGroup::AccessToken),
+            "category" => Some(UsageGroup::Category),
+            _ => None,
+        }
+    }
+
+    // only these fixed expressions are ever interpolated into the query
+    fn column(&self) -> &'static str {
+        match self {
+            UsageGroup::Router => "router_id",
+            UsageGroup::Model => "model_id",
+            UsageGroup::AccessToken => "access_token",
+            UsageGroup::Category => "COALESCE(category, '')",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageGranularity {
+    Day,
+    Hour,
+}
+
+impl UsageGranularity {
+    pub fn parse(value: &str) -> Option<UsageGranularity> {
+        match value {
+            "day" => Some(UsageGranularity::Day),
+            "hour" => Some(UsageGranularity::Hour),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageGranularity::Day => "day",
+            UsageGranularity::Hour => "hour",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageQuery {
+    pub org_id: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    // routing or completion, both when empty
+    pub kind: Option<String>,
+    pub group: UsageGroup,
+    pub granularity: UsageGranularity,
+}
+
+// rows stay split by model so cost can be priced per model afterwards
+#[derive(Debug, Clone, QueryableByName)]
+pub struct UsageBucket {
+    #[diesel(sql_type = Timestamptz)]
+    pub period: DateTime<Utc>,
+    #[diesel(sql_type = Text)]
+    pub group_key: String,
+    #[diesel(sql_type = Text)]
+    pub model_id: String,
+    #[diesel(sql_type = BigInt)]
+    pub calls: i64,
+    #[diesel(sql_type = BigInt)]
+    pub errors: i64,
+    #[diesel(sql_type = BigInt)]
+    pub prompt_tokens: i64,
+    #[diesel(sql_type = BigInt)]
+    pub completion_tokens: i64,
+    #[diesel(sql_type = BigInt)]
+    pub latency_ms: i64,
+}
+
+pub fn find_usage_buckets(pool: &Pool<ConnectionManager<PgConnection>>, query: &UsageQuery) -> Result<Vec<UsageBucket>, String> {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let sql = format!(
+        "SELECT date_trunc('{granularity}', created_at) AS period, {group} AS group_key, model_id, \
+            COUNT(*) AS calls, \
+            COUNT(*) FILTER (WHERE status <> 'ok') AS errors, \
+            SUM(prompt_tokens)::BIGINT AS prompt_tokens, \
+            SUM(completion_tokens)::BIGINT AS completion_tokens, \
+            SUM(latency_ms)::BIGINT AS latency_ms \
+        FROM usage_ledger \
+        WHERE org_id = $1 AND created_at >= $2 AND created_at < $3 AND ($4 IS NULL OR kind = $4) \
+        GROUP BY period, group_key, model_id \
+        ORDER BY period, group_key",
+        granularity = query.granularity.as_str(),
+        group = query.group.column(),
+    );
+
+    let result = diesel::sql_query(sql)
+        .bind::<Text, _>(&query.org_id)
+        .bind::<Timestamptz, _>(query.from)
+        .bind::<Timestamptz, _>(query.to)
+        .bind::<Nullable<Text>, _>(query.kind.clone())
+        .load::<UsageBucket>(&mut conn);
+
+    match result {
+        Ok(buckets) => Ok(buckets),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct ModelTokens {
+    #[diesel(sql_type = Text)]
+    pub model_id: String,
+    #[diesel(sql_type = BigInt)]
+    pub prompt_tokens: i64,
+    #[diesel(sql_type = BigInt)]
+    pub completion_tokens: i64,
+}
+
+// tokens of the provider calls an org made since the given time, per model.
+// Priced with the catalog like usage reports, routing decisions aren't spend
+pub fn find_org_spend(pool: &Pool<ConnectionManager<PgConnection>>, org_id: &str, from: DateTime<Utc>) -> Result<Vec<ModelTokens>, String> {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let result = diesel::sql_query(
+        "SELECT model_id, COALESCE(SUM(prompt_tokens), 0)::BIGINT AS prompt_tokens, COALESCE(SUM(completion_tokens), 0)::BIGINT AS completion_tokens \
+        FROM usage_ledger WHERE org_id = $1 AND created_at >= $2 AND kind = $3 \
+        GROUP BY model_id",
+    )
+        .bind::<Text, _>(org_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Text, _>(COMPLETION_KIND)
+        .load::<ModelTokens>(&mut conn);
+
+    match result {
+        Ok(models) => Ok(models),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UsageWriterSettings {
+    pub buffer_size: usize,
+    pub max_batch_size: usize,
+    pub flush_interval: Duration,
+}
+
+// the request path only pushes into a bounded channel, records are inserted in
+// batches by a background task and dropped when the buffer is full
+#[derive(Clone)]
+pub struct UsageWriter {
+    sender: Option<mpsc::Sender<UsageRecord>>,
+}
+
+impl UsageWriter {
+    pub fn new(pool: Option<Pool<ConnectionManager<PgConnection>>>, settings: UsageWriterSettings) -> UsageWriter {
+        let pool = match pool {
+            Some(pool) => pool,
+            None => return UsageWriter { sender: None },
+        };
+
+        let (sender, receiver) = mpsc::channel(settings.buffer_size);
+        tokio::spawn(run_usage_writer(pool, settings, receiver));
+
+        UsageWriter { sender: Some(sender) }
+    }
+
+    pub fn record(&self, record: UsageRecord) {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        if let Err(mpsc::error::TrySendError::Full(record)) = sender.try_send(record) {
+            warn!("usage buffer is full, dropping {} record of org {}", record.kind, record.org_id);
+        }
+    }
+}
+
+async fn run_usage_writer(
+    pool: Pool<ConnectionManager<PgConnection>>,
+    settings: UsageWriterSettings,
+    mut receiver: mpsc::Receiver<UsageRecord>,
+) {
+    while let Some(first) = receiver.recv().await {
+        let deadline = Instant::now() + settings.flush_interval;
+
+        let mut batch = vec![first];
+        while batch.len() < settings.max_batch_size {
+            match timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(record)) => batch.push(record),
+                _ => break,
+            }
+        }
+
+        let pool = pool.clone();
+        let size = batch.len();
+        let result = task::spawn_blocking(move || {
+            let mut conn = match pool.get() {
+                Ok(conn) => conn,
+                Err(e) => return Err(e.to_string()),
+            };
+
+            match diesel::insert_into(usage_ledger::table).values(&batch).execute(&mut conn) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            }
+        }).await;
+
+        match result {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => error!("failed to write {} usage records: {}", size, e),
+            Err(e) => error!("usage batch panicked: {}", e),
+        }
+    }
+}
@@ -7,7 +7,7 @@ use serde_json::json;
 
 use std::env;
 
-use crate::{types::{customer::{Customer, GenericResponse}, organization::Organization}, utilities::helpers::{internal_server_error, not_found}};
+use crate::{types::{catalog::CatalogModel, customer::{Customer, GenericResponse}, organization::Organization, request_log::RequestLogEntry, webhook::WebhookDelivery}, utilities::helpers::{internal_server_error, not_found}};
 
 pub async fn init_connection() -> mongodb::error::Result<Client> {
     let uri = match env::var("MONGO_URI") {
@@ -53,6 +53,16 @@ pub async fn build_organizations_filter(id: &str) -> Document {
     return customer_filter
 }
 
+// organizations are looked up by access token when the caller only sends a bearer token
+pub async fn build_organizations_access_token_filter(token: &str) -> Document {
+    let organization_filter = doc! {
+        "access_tokens.token": token,
+        "deleted": false,
+    };
+
+    return organization_filter
+}
+
 pub async fn get_customers_collection(db: &Database) -> Collection<Customer> {
     return db.collection("customers");
 }
@@ -104,6 +114,37 @@ pub async fn find_organization(db: &Database, filter: Document) -> Result<Organi
     }
 }
 
+// the org with the document it was read from, updates that must not overwrite a
+// concurrent change filter on the stored arrays of the snapshot
+pub async fn find_organization_snapshot(db: &Database, filter: Document) -> Result<(Organization, Document), (StatusCode, Json<GenericResponse>)> {
+    let collection = get_organizations_collection(db).await.clone_with_type::<Document>();
+    let snapshot = match collection.find_one(filter, None).await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => return Err(not_found("org.not.found", None)),
+        Err(e) => {
+            error!("error fetching organization: {}", e);
+            return Err(internal_server_error("database.error", None));
+        }
+    };
+
+    match mongodb::bson::from_document(snapshot.clone()) {
+        Ok(org) => Ok((org, snapshot)),
+        Err(e) => {
+            error!("error reading organization: {}", e);
+            Err(internal_server_error("database.error", None))
+        }
+    }
+}
+
+// false when the filter matched nothing, e.g. the snapshot it names is stale
+pub async fn update_organization_if(db: &Database, filter: Document, update: Document) -> Result<bool, (StatusCode, Json<GenericResponse>)> {
+    let collection = get_organizations_collection(db).await;
+    match collection.update_one(filter, update, None).await {
+        Ok(result) => Ok(result.matched_count > 0),
+        Err(_) => Err(internal_server_error("database.error", None)),
+    }
+}
+
 pub async fn update_customer(db: &Database, filter: Document, update: Document) -> Result<(), (StatusCode, Json<GenericResponse>)> {
     let collection = get_customers_collection(db).await;
     match collection.update_one(filter, update, None).await {
@@ -118,4 +159,15 @@ pub async fn update_organization(db: &Database, filter: Document, update: Docume
         Ok(_) => Ok(()),
         Err(_) => return Err(internal_server_error("database.error", None)),
     }
-}
\ No newline at end of file
+}
+pub async fn get_request_logs_collection(db: &Database) -> Collection<RequestLogEntry> {
+    return db.collection("request_logs");
+}
+
+pub async fn get_webhook_deliveries_collection(db: &Database) -> Collection<WebhookDelivery> {
+    return db.collection("webhook_deliveries");
+}
+
+pub async fn get_model_catalog_collection(db: &Database) -> Collection<CatalogModel> {
+    return db.collection("model_catalog");
+}
@@ -4,11 +4,13 @@ pub mod incoming_requests;
 pub mod subscription;
 pub mod email;
 
-pub mod openai_models;
-pub mod anthropic_models;
-pub mod coherence_models;
-pub mod llms;
+pub mod catalog;
 pub mod router;
+pub mod router_template;
 
 pub mod state;
-pub mod organization;
\ No newline at end of file
+pub mod organization;
+pub mod org_config;
+pub mod usage;
+pub mod request_log;
+pub mod webhook;
\ No newline at end of file
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::organization::ModelOwner;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Modality {
+    Text,
+    Image,
+}
+
+impl Modality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Modality::Text => "text",
+            Modality::Image => "image",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CatalogStatus {
+    #[default]
+    Active,
+    // still routed to, shown as deprecated so orgs can move away from it
+    Deprecated,
+    // hidden from the public list and can't be added to an org
+    Disabled,
+}
+
+// vision is read from the image modality
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    #[serde(default)]
+    pub tools: bool,
+    #[serde(default)]
+    pub json_mode: bool,
+    #[serde(default)]
+    pub streaming: bool,
+    // 0 when unknown, it isn't checked then
+    #[serde(default)]
+    pub max_output_tokens: u32,
+}
+
+// what a routing request needs from the model, models outside the catalog have
+// unknown capabilities and never satisfy a requirement
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequiredCapabilities {
+    #[serde(default)]
+    pub vision: bool,
+    #[serde(default)]
+    pub tools: bool,
+    #[serde(default)]
+    pub json_mode: bool,
+    #[serde(default)]
+    pub streaming: bool,
+    pub max_output_tokens: Option<u32>,
+}
+
+impl RequiredCapabilities {
+    pub fn is_empty(&self) -> bool {
+        !self.vision && !self.tools && !self.json_mode && !self.streaming && self.max_output_tokens.is_none()
+    }
+
+    // None is a model outside the catalog, it misses everything that's required
+    pub fn missing(&self, model: Option<&CatalogModel>) -> Vec<String> {
+        let mut missing = vec![];
+
+        for (required, capability) in [(self.vision, "vision"), (self.tools, "tools"), (self.json_mode, "json_mode"), (self.streaming, "streaming")] {
+            if required && !model.map_or(false, |model| model.has_capability(capability)) {
+                missing.push(capability.to_string());
+            }
+        }
+
+        if let Some(max_output_tokens) = self.max_output_tokens {
+            let limit = model.map_or(Some(0), |model| match model.capabilities.max_output_tokens {
+                0 => None,
+                limit => Some(limit),
+            });
+
+            if limit.map_or(false, |limit| max_output_tokens > limit) {
+                missing.push(String::from("max_output_tokens"));
+            }
+        }
+
+        missing
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogModel {
+    pub id: String,
+    pub vendor: ModelOwner,
+    // model id expected by the vendor API, empty when it's the same as ours
+    #[serde(default)]
+    pub api_model_id: String,
+    pub context_window: u32,
+    pub knowledge_cutoff: String,
+    // USD per million tokens, used to rank otherwise equal candidates and for usage costs
+    pub input_cost_per_million_tokens: f64,
+    pub output_cost_per_million_tokens: f64,
+    #[serde(default)]
+    pub modalities: Vec<Modality>,
+    #[serde(default)]
+    pub capabilities: ModelCapabilities,
+    #[serde(default)]
+    pub status: CatalogStatus,
+}
+
+impl CatalogModel {
+    pub fn api_model_id(&self) -> String {
+        match self.api_model_id.is_empty() {
+            true => self.id.clone(),
+            false => self.api_model_id.clone(),
+        }
+    }
+
+    // USD
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input_cost_per_million_tokens + completion_tokens as f64 * self.output_cost_per_million_tokens) / 1_000_000.0
+    }
+
+    // a modality or one of vision, tools, json_mode and streaming
+    pub fn has_capability(&self, capability: &str) -> bool {
+        match capability {
+            "vision" => self.modalities.contains(&Modality::Image),
+            "tools" => self.capabilities.tools,
+            "json_mode" => self.capabilities.json_mode,
+            "streaming" => self.capabilities.streaming,
+            _ => self.modalities.iter().any(|modality| modality.as_str() == capability),
+        }
+    }
+}
+
+// shape of the public model list, the original fields keep their names and the
+// catalog fields are added next to them
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelInfo {
+    pub company: Option<String>,
+    pub model: String,
+    pub context_window: u32,
+    pub training_data: String,
+    pub input_cost_per_million_tokens: f64,
+    pub output_cost_per_million_tokens: f64,
+    pub vendor: ModelOwner,
+    pub modalities: Vec<Modality>,
+    pub capabilities: ModelCapabilities,
+    pub status: CatalogStatus,
+}
+
+impl From<CatalogModel> for ModelInfo {
+    fn from(model: CatalogModel) -> Self {
+        let company = match model.vendor {
+            ModelOwner::OpenAI => Some(String::from("OpenAI")),
+            ModelOwner::Anthropic => Some(String::from("Anthropic")),
+            ModelOwner::Coherence => Some(String::from("Coherence")),
+            ModelOwner::OpenSource => None,
+        };
+
+        ModelInfo {
+            company,
+            model: model.id,
+            context_window: model.context_window,
+            training_data: model.knowledge_cutoff,
+            input_cost_per_million_tokens: model.input_cost_per_million_tokens,
+            output_cost_per_million_tokens: model.output_cost_per_million_tokens,
+            vendor: model.vendor,
+            modalities: model.modalities,
+            capabilities: model.capabilities,
+            status: model.status,
+        }
+    }
+}
+
+// models shipped with the api, platform admins override or add entries from mongo
+const BUNDLED_MODELS: &str = include_str!("catalog/models.json");
+
+pub struct ModelCatalog {
+    bundled: Vec<CatalogModel>,
+    overrides: RwLock<HashMap<String, CatalogModel>>,
+}
+
+impl ModelCatalog {
+    pub fn bundled() -> Result<ModelCatalog, serde_json::Error> {
+        Ok(ModelCatalog {
+            bundled: serde_json::from_str(BUNDLED_MODELS)?,
+            overrides: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn set_overrides(&self, models: Vec<CatalogModel>) {
+        if let Ok(mut overrides) = self.overrides.write() {
+            *overrides = models.into_iter().map(|model| (model.id.clone(), model)).collect();
+        }
+    }
+
+    pub fn is_bundled(&self, id: &str) -> bool {
+        self.bundled.iter().any(|model| model.id == id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<CatalogModel> {
+        if let Ok(overrides) = self.overrides.read() {
+            if let Some(model) = overrides.get(id) {
+                return Some(model.clone());
+            }
+        }
+
+        self.bundled.iter().find(|model| model.id == id).cloned()
+    }
+
+    pub fn owner(&self, id: &str) -> Option<ModelOwner> {
+        self.get(id).map(|model| model.vendor)
+    }
+
+    pub fn supports(&self, id: &str, required: &RequiredCapabilities) -> bool {
+        required.is_empty() || required.missing(self.get(id).as_ref()).is_empty()
+    }
+
+    // None for models outside the catalog, custom models have no known pricing
+    pub fn cost(&self, id: &str, prompt_tokens: u64, completion_tokens: u64) -> Option<f64> {
+        self.get(id).map(|model| model.cost(prompt_tokens, completion_tokens))
+    }
+
+    // bundled order first, models only known from mongo are appended sorted by id
+    pub fn all(&self) -> Vec<CatalogModel> {
+        let overrides = match self.overrides.read() {
+            Ok(overrides) => overrides.clone(),
+            Err(_) => HashMap::new(),
+        };
+
+        let mut models: Vec<CatalogModel> = self.bundled
+            .iter()
+            .map(|model| overrides.get(&model.id).unwrap_or(model).clone())
+            .collect();
+
+        let mut added: Vec<CatalogModel> = overrides
+            .into_values()
+            .filter(|model| !self.is_bundled(&model.id))
+            .collect();
+        added.sort_by(|a, b| a.id.cmp(&b.id));
+
+        models.extend(added);
+        models
+    }
+
+    pub fn overrides(&self) -> Vec<CatalogModel> {
+        let mut models: Vec<CatalogModel> = match self.overrides.read() {
+            Ok(overrides) => overrides.values().cloned().collect(),
+            Err(_) => vec![],
+        };
+
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        models
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vision_required() -> RequiredCapabilities {
+        RequiredCapabilities {
+            vision: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unknown_models_miss_every_requirement() {
+        let required = RequiredCapabilities {
+            vision: true,
+            tools: true,
+            json_mode: true,
+            streaming: true,
+            max_output_tokens: Some(1),
+        };
+
+        assert_eq!(required.missing(None), vec!["vision", "tools", "json_mode", "streaming", "max_output_tokens"]);
+        assert!(RequiredCapabilities::default().missing(None).is_empty());
+    }
+
+    #[test]
+    fn lists_only_the_missing_capabilities() {
+        let catalog = ModelCatalog::bundled().unwrap();
+        let required = RequiredCapabilities {
+            vision: true,
+            tools: true,
+            max_output_tokens: Some(8192),
+            ..Default::default()
+        };
+
+        assert_eq!(required.missing(catalog.get("gpt-4").as_ref()), vec!["vision"]);
+        assert_eq!(required.missing(catalog.get("gpt-4-vision").as_ref()), vec!["tools", "max_output_tokens"]);
+    }
+
+    #[test]
+    fn unknown_output_limits_are_not_checked() {
+        let catalog = ModelCatalog::bundled().unwrap();
+        let mut model = catalog.get("gpt-4").unwrap();
+        model.capabilities.max_output_tokens = 0;
+
+        let required = RequiredCapabilities {
+            max_output_tokens: Some(u32::MAX),
+            ..Default::default()
+        };
+
+        assert!(required.missing(Some(&model)).is_empty());
+        assert_eq!(required.missing(catalog.get("gpt-4").as_ref()), vec!["max_output_tokens"]);
+    }
+
+    #[test]
+    fn supports_reads_overrides_and_unknown_models() {
+        let catalog = ModelCatalog::bundled().unwrap();
+
+        assert!(catalog.supports("custom-model", &RequiredCapabilities::default()));
+        assert!(!catalog.supports("custom-model", &vision_required()));
+        assert!(catalog.supports("gpt-4-vision", &vision_required()));
+        assert!(!catalog.supports("gpt-4", &vision_required()));
+
+        let mut model = catalog.get("gpt-4").unwrap();
+        model.modalities.push(Modality::Image);
+        catalog.set_overrides(vec![model]);
+
+        assert!(catalog.supports("gpt-4", &vision_required()));
+    }
+}
@@ -1,27 +0,0 @@
-//
-// Coherence
-//  
-
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub enum CoherenceModels {
-    CommandLight,
-    CommandLightNightly,
-    Command,
-    CommandNightly,
-}
-
-// Constants providing additional details about each Anthropic model
-impl CoherenceModels {
-    pub const fn context_window(&self) -> usize {
-        match *self {
-            CoherenceModels::CommandLight | CoherenceModels::Command => 4096,
-            CoherenceModels::CommandLightNightly | CoherenceModels::CommandNightly => 8192,
-        }
-    }
-
-    pub const fn training_data(&self) -> &'static str {
-        "Up to Date"
-    }
-}
\ No newline at end of file
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-use super::router::{Category, Sentence};
+use super::{catalog::RequiredCapabilities, organization::ModelOwner, router::{Category, RoutingRule, Sentence}, webhook::WebhookEvent};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignIn {
@@ -50,6 +53,45 @@ pub struct VerifyEmailQueryParams {
 #[derive(Debug, Deserialize)]
 pub struct ProcessPrompt {
     pub prompt: Option<String>,
+    // returns every candidate model ordered by score instead of a single pick
+    pub ranked: Option<bool>,
+    pub max_candidates: Option<usize>,
+    // models missing any of them are skipped or swapped for a compatible one
+    #[serde(default)]
+    pub capabilities: RequiredCapabilities,
+}
+
+// timing of a provider call the client made itself with a routed model
+#[derive(Debug, Deserialize)]
+pub struct ModelFeedback {
+    pub model_id: String,
+    pub latency_ms: u64,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    // plain text or a list of content parts, null on assistant tool calls
+    #[serde(default)]
+    pub content: Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+// OpenAI chat completions request, the model field carries the router id
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletion {
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub stream: Option<bool>,
+    // temperature, max_tokens, stop... are forwarded untouched
+    #[serde(flatten)]
+    pub options: Map<String, Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,11 +108,14 @@ pub struct EditOrg {
 pub struct CreateModel {
     pub id: String,
     pub display_name: String,
+    pub credential_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RemoveModel {
     pub id: String,
+    // rewrites every router reference to this model instead of rejecting the delete
+    pub replace_with: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -78,6 +123,26 @@ pub struct EditModel {
     pub id: String,
     pub display_name: Option<String>,
     pub description: Option<String>,
+    // an empty string goes back to the platform key
+    pub credential_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCredential {
+    pub provider: ModelOwner,
+    pub name: String,
+    pub api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateCredential {
+    pub id: String,
+    pub api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoveCredential {
+    pub id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -86,6 +151,17 @@ pub struct CreateRouter {
     pub description: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateRouterFromTemplate {
+    pub template_id: String,
+    // the template's name and description when missing
+    pub name: Option<String>,
+    pub description: Option<String>,
+    // template slot id to org model id
+    #[serde(default)]
+    pub bindings: HashMap<String, String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DeleteRouter {
     pub id: String,
@@ -105,6 +181,25 @@ pub struct EditRouter {
     pub max_prompt_length: i32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct EditRouterFallbackModels {
+    pub id: String,
+    pub fallback_model_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditRouterLatency {
+    pub id: String,
+    pub use_latency_routing: bool,
+    pub latency_slo_ms: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditRouterRules {
+    pub id: String,
+    pub rules: Vec<RoutingRule>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EditRouterSingleModel {
     pub id: String,
@@ -123,6 +218,7 @@ pub struct EditRouterPromptClassification {
 pub struct EditRouterSentenceMatching {
     pub id: String,
     pub use_sentence_matching: bool,
+    pub embedding_model_id: Option<String>,
     pub sentence_matching_sentences: Vec<Sentence>,
 }
 
@@ -139,4 +235,107 @@ pub struct RemoveMember {
 #[derive(Debug, Deserialize)]
 pub struct EditMember {
     pub role: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReloadModel {
+    pub kind: String, // prompt_classification | sentence_embeddings
+    pub id: Option<String>, // sentence embeddings registry id, the default model when empty
+    pub name: String,
+    pub url: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModelCatalogQueryParams {
+    pub vendor: Option<ModelOwner>,
+    // a modality such as image, or vision, tools, json_mode or streaming
+    pub capability: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoveCatalogModel {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQueryParams {
+    // YYYY-MM-DD or RFC 3339, a plain date for to includes the whole day
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // router, model, access_token or category
+    pub group_by: Option<String>,
+    // day or hour
+    pub granularity: Option<String>,
+    // routing or completion
+    pub kind: Option<String>,
+    // json or csv
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RouterAnalyticsQueryParams {
+    pub router_id: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // day or hour
+    pub granularity: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditRequestLogSettings {
+    pub enabled: bool,
+    pub sample_rate: f64,
+    pub redact_prompts: bool,
+    pub retention_days: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrgConfigQueryParams {
+    // json or yaml, imports fall back to the content type
+    pub format: Option<String>,
+    // returns the plan without applying it
+    pub dry_run: Option<bool>,
+    // deletes models and routers missing from the document
+    pub prune: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoveWebhook {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedeliverWebhook {
+    pub delivery_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveryQueryParams {
+    pub webhook_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestLogQueryParams {
+    // only read by /api/admin/request.logs, orgs search their own logs
+    pub org_id: Option<String>,
+    pub router_id: Option<String>,
+    pub model_id: Option<String>,
+    pub label: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // case insensitive match on the stored prompt
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+    // unix millis and id of the last entry of the previous page, entries of
+    // the same millisecond are told apart by id
+    pub before: Option<i64>,
+    pub before_id: Option<String>,
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    organization::ModelObject,
+    router::{Category, Router, RoutingRule, Sentence},
+};
+
+// bumped whenever a field changes meaning, older documents are rejected
+pub const ORG_CONFIG_VERSION: u32 = 1;
+
+// models and routers of an org as kept in git, routers are keyed by name so a
+// document can be promoted between orgs, credentials stay in each org and
+// models only reference one by id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrgConfig {
+    pub version: u32,
+    #[serde(default)]
+    pub models: Vec<ModelConfig>,
+    #[serde(default)]
+    pub routers: Vec<RouterConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelConfig {
+    pub id: String,
+    pub display_name: String,
+    // empty keeps the model's current credential
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub credential_id: String,
+}
+
+impl From<&ModelObject> for ModelConfig {
+    fn from(model: &ModelObject) -> Self {
+        ModelConfig {
+            id: model.id.clone(),
+            display_name: model.display_name.clone(),
+            credential_id: model.credential_id.clone(),
+        }
+    }
+}
+
+fn default_active() -> bool {
+    true
+}
+
+fn default_max_prompt_length() -> i32 {
+    512
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouterConfig {
+    pub name: String,
+    pub description: String,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default = "default_max_prompt_length")]
+    pub max_prompt_length: i32,
+
+    #[serde(default)]
+    pub use_single_model: bool,
+    #[serde(default)]
+    pub model_id: String,
+
+    #[serde(default)]
+    pub use_prompt_classification: bool,
+    #[serde(default)]
+    pub categories: Vec<Category>,
+
+    #[serde(default)]
+    pub use_sentence_matching: bool,
+    #[serde(default)]
+    pub sentences: Vec<Sentence>,
+    #[serde(default)]
+    pub embedding_model_id: String,
+
+    #[serde(default)]
+    pub fallback_model_ids: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+    #[serde(default)]
+    pub use_latency_routing: bool,
+    #[serde(default)]
+    pub latency_slo_ms: u32,
+}
+
+impl From<&Router> for RouterConfig {
+    fn from(router: &Router) -> Self {
+        RouterConfig {
+            name: router.name.clone(),
+            description: router.description.clone(),
+            active: router.active,
+            max_prompt_length: router.max_prompt_length,
+            use_single_model: router.use_single_model,
+            model_id: router.model_id.clone(),
+            use_prompt_classification: router.use_prompt_calification_model,
+            categories: router.prompt_calification_model_categories.clone(),
+            use_sentence_matching: router.use_sentence_matching,
+            sentences: router.sentences.clone(),
+            embedding_model_id: router.embedding_model_id.clone(),
+            fallback_model_ids: router.fallback_model_ids.clone(),
+            rules: router.rules.clone(),
+            use_latency_routing: router.use_latency_routing,
+            latency_slo_ms: router.latency_slo_ms,
+        }
+    }
+}
+
+impl RouterConfig {
+    pub fn into_router(self, id: String) -> Router {
+        Router {
+            id,
+            name: self.name,
+            description: self.description,
+            active: self.active,
+            deleted: false,
+            max_prompt_length: self.max_prompt_length,
+            use_single_model: self.use_single_model,
+            model_id: self.model_id,
+            use_prompt_calification_model: self.use_prompt_classification,
+            prompt_calification_model_categories: self.categories,
+            use_sentence_matching: self.use_sentence_matching,
+            sentences: self.sentences,
+            embedding_model_id: self.embedding_model_id,
+            fallback_model_ids: self.fallback_model_ids,
+            rules: self.rules,
+            use_latency_routing: self.use_latency_routing,
+            latency_slo_ms: self.latency_slo_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlannedAction {
+    Create,
+    Update,
+    Delete,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedChange {
+    pub action: PlannedAction,
+    // model id or router name
+    pub key: String,
+    // top level fields that differ, empty for creations and deletions
+    pub changed_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPlan {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub models: Vec<PlannedChange>,
+    pub routers: Vec<PlannedChange>,
+}
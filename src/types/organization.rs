@@ -1,20 +1,32 @@
-use mongodb::bson::Bson;
+use mongodb::bson::{doc, Bson};
 use serde::{Deserialize, Serialize};
 
-use super::{customer::CustomerID, router::Router};
+use super::{customer::CustomerID, request_log::RequestLogSettings, router::Router, webhook::Webhook};
 
 pub type OrganizationID = String;
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ModelOwner {
+    #[serde(rename = "openai")]
     OpenAI,
     Anthropic,
     Coherence,
     OpenSource,
 }
 
+impl From<ModelOwner> for Bson {
+    fn from(model_owner: ModelOwner) -> Self {
+        match model_owner {
+            ModelOwner::OpenAI => Bson::String("openai".to_string()),
+            ModelOwner::Anthropic => Bson::String("anthropic".to_string()),
+            ModelOwner::Coherence => Bson::String("coherence".to_string()),
+            ModelOwner::OpenSource => Bson::String("open_source".to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ModelType {
@@ -37,6 +49,22 @@ pub struct ModelObject {
     pub r#type: ModelType,
     pub display_name: String,
     pub registered_by: CustomerID,
+    // provider credential used when proxying, empty uses the platform key
+    #[serde(default)]
+    pub credential_id: String,
+}
+
+impl Into<Bson> for ModelObject {
+    fn into(self) -> Bson {
+        doc! {
+            "id": self.id,
+            "type": self.r#type,
+            "display_name": self.display_name,
+            "registered_by": self.registered_by,
+            "credential_id": self.credential_id,
+        }
+        .into()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -72,6 +100,82 @@ pub struct AccessToken {
     pub scopes: Vec<AccessTokenScopes>,
 }
 
+// api key encrypted with a random data key, the data key is encrypted with
+// CREDENTIALS_ENCRYPTION_KEY, every value is hex encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedSecret {
+    pub ciphertext: String,
+    pub nonce: String,
+    pub wrapped_data_key: String,
+    pub data_key_nonce: String,
+}
+
+impl Into<Bson> for EncryptedSecret {
+    fn into(self) -> Bson {
+        doc! {
+            "ciphertext": self.ciphertext,
+            "nonce": self.nonce,
+            "wrapped_data_key": self.wrapped_data_key,
+            "data_key_nonce": self.data_key_nonce,
+        }
+        .into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCredential {
+    pub id: String,
+    pub provider: ModelOwner,
+    pub name: String,
+    pub masked_key: String,
+    pub encrypted_key: EncryptedSecret,
+    pub created_by: CustomerID,
+    pub created_at: String,
+    pub rotated_at: Option<String>,
+}
+
+impl Into<Bson> for ProviderCredential {
+    fn into(self) -> Bson {
+        doc! {
+            "id": self.id,
+            "provider": self.provider,
+            "name": self.name,
+            "masked_key": self.masked_key,
+            "encrypted_key": self.encrypted_key,
+            "created_by": self.created_by,
+            "created_at": self.created_at,
+            "rotated_at": self.rotated_at,
+        }
+        .into()
+    }
+}
+
+// what members get to see of a credential
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskedCredential {
+    pub id: String,
+    pub provider: ModelOwner,
+    pub name: String,
+    pub masked_key: String,
+    pub created_by: CustomerID,
+    pub created_at: String,
+    pub rotated_at: Option<String>,
+}
+
+impl From<&ProviderCredential> for MaskedCredential {
+    fn from(credential: &ProviderCredential) -> Self {
+        MaskedCredential {
+            id: credential.id.clone(),
+            provider: credential.provider.clone(),
+            name: credential.name.clone(),
+            masked_key: credential.masked_key.clone(),
+            created_by: credential.created_by.clone(),
+            created_at: credential.created_at.clone(),
+            rotated_at: credential.rotated_at.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Organization {
     pub id: OrganizationID,
@@ -80,5 +184,11 @@ pub struct Organization {
     pub routers: Vec<Router>,
     pub members: Vec<OrgMember>,
     pub access_tokens: Vec<AccessToken>,
+    #[serde(default)]
+    pub credentials: Vec<ProviderCredential>,
+    #[serde(default)]
+    pub request_log: RequestLogSettings,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
     pub deleted: bool,
 }
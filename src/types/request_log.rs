@@ -0,0 +1,68 @@
+use mongodb::bson::{doc, Bson};
+use serde::{Deserialize, Serialize};
+
+// off by default, prompts are customer data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogSettings {
+    pub enabled: bool,
+    // share of routing decisions that are logged, 0 to 1
+    pub sample_rate: f64,
+    // emails, long numbers and secret looking tokens are masked before storing
+    pub redact_prompts: bool,
+    pub retention_days: u32,
+}
+
+impl Default for RequestLogSettings {
+    fn default() -> Self {
+        RequestLogSettings {
+            enabled: false,
+            sample_rate: 1.0,
+            redact_prompts: false,
+            retention_days: 30,
+        }
+    }
+}
+
+impl Into<Bson> for RequestLogSettings {
+    fn into(self) -> Bson {
+        doc! {
+            "enabled": self.enabled,
+            "sample_rate": self.sample_rate,
+            "redact_prompts": self.redact_prompts,
+            "retention_days": self.retention_days as i64,
+        }
+        .into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogCandidate {
+    pub model_id: String,
+    pub strategy: String,
+    pub score: f64,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogEntry {
+    pub id: String,
+    pub org_id: String,
+    pub router_id: String,
+    // routing or completion
+    pub kind: String,
+    pub created_at: String,
+    // unix millis, used for range filters and retention
+    pub timestamp: i64,
+    pub prompt: String,
+    pub prompt_redacted: bool,
+    pub prompt_size: i64,
+    // empty when the decision ended in an error
+    pub model_id: String,
+    pub strategy: String,
+    pub label: Option<String>,
+    pub score: Option<f64>,
+    pub status: String,
+    pub latency_ms: i64,
+    // every model the router considered, when ranking was used
+    pub candidates: Vec<RequestLogCandidate>,
+}
@@ -1,4 +1,4 @@
-use mongodb::bson::{doc, Bson};
+use mongodb::bson::{doc, Bson, Document};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -6,6 +6,9 @@ pub struct Category {
     pub label: String,
     pub description: String,
     pub model_id: String,
+    // other models allowed for the category, latency routing picks among all of them
+    #[serde(default)]
+    pub alternative_model_ids: Vec<String>,
 }
 
 impl Into<Bson> for Category {
@@ -14,6 +17,7 @@ impl Into<Bson> for Category {
             "label": self.label,
             "description": self.description,
             "model_id": self.model_id,
+            "alternative_model_ids": self.alternative_model_ids,
         }
         .into()
     }
@@ -41,6 +45,58 @@ impl Into<Bson> for Sentence {
     }
 }
 
+// every set condition has to hold for the rule to apply
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleConditions {
+    // IANA zone hours and weekdays are read in, e.g. America/New_York, empty is UTC
+    #[serde(default)]
+    pub timezone: String,
+    // fixed offset of rules saved before time zones, only read when timezone is empty
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    // local hours [start_hour, end_hour), a start after the end wraps past midnight
+    pub start_hour: Option<u32>,
+    pub end_hour: Option<u32>,
+    // 0 is monday, empty means every day
+    #[serde(default)]
+    pub weekdays: Vec<u32>,
+    // org spend in USD since the start of the month, from the usage ledger
+    pub min_monthly_spend: Option<f64>,
+}
+
+impl Into<Bson> for RuleConditions {
+    fn into(self) -> Bson {
+        doc! {
+            "timezone": self.timezone,
+            "utc_offset_minutes": self.utc_offset_minutes,
+            "start_hour": self.start_hour.map(|hour| hour as i64),
+            "end_hour": self.end_hour.map(|hour| hour as i64),
+            "weekdays": self.weekdays.iter().map(|weekday| *weekday as i64).collect::<Vec<i64>>(),
+            "min_monthly_spend": self.min_monthly_spend,
+        }
+        .into()
+    }
+}
+
+// evaluated in order before any other strategy, the first rule that applies picks the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub name: String,
+    pub conditions: RuleConditions,
+    pub model_id: String,
+}
+
+impl Into<Bson> for RoutingRule {
+    fn into(self) -> Bson {
+        doc! {
+            "name": self.name,
+            "conditions": self.conditions,
+            "model_id": self.model_id,
+        }
+        .into()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Router {
     // info
@@ -80,6 +136,128 @@ pub struct Router {
     // }]
     pub use_sentence_matching: bool,
     pub sentences: Vec<Sentence>,
+    // sentence embeddings registry id, empty means the server default
+    #[serde(default)]
+    pub embedding_model_id: String,
+
+    // tried in order by /v1/chat/completions when the routed model's provider fails
+    #[serde(default)]
+    pub fallback_model_ids: Vec<String>,
+
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+
+    // categories pick their fastest healthy model from observed latencies
+    #[serde(default)]
+    pub use_latency_routing: bool,
+    #[serde(default)]
+    pub latency_slo_ms: u32,
+}
+
+impl Router {
+    // settings of the router that point at the model
+    pub fn model_references(&self, model_id: &str) -> Vec<&'static str> {
+        let mut references = vec![];
+
+        if self.model_id == model_id {
+            references.push("model_id");
+        }
+
+        if self.prompt_calification_model_categories.iter().any(|category| category.model_id == model_id || category.alternative_model_ids.iter().any(|id| id == model_id)) {
+            references.push("prompt_calification_model_categories");
+        }
+
+        if self.sentences.iter().any(|sentence| sentence.model_id == model_id) {
+            references.push("sentences");
+        }
+
+        if self.fallback_model_ids.iter().any(|id| id == model_id) {
+            references.push("fallback_model_ids");
+        }
+
+        if self.rules.iter().any(|rule| rule.model_id == model_id) {
+            references.push("rules");
+        }
+
+        references
+    }
+
+    // every model the router points at, duplicates included
+    pub fn referenced_model_ids(&self) -> Vec<&str> {
+        let mut model_ids = vec![];
+
+        if !self.model_id.is_empty() {
+            model_ids.push(self.model_id.as_str());
+        }
+
+        for category in self.prompt_calification_model_categories.iter() {
+            model_ids.push(category.model_id.as_str());
+            model_ids.extend(category.alternative_model_ids.iter().map(|id| id.as_str()));
+        }
+
+        model_ids.extend(self.sentences.iter().map(|sentence| sentence.model_id.as_str()));
+        model_ids.extend(self.fallback_model_ids.iter().map(|id| id.as_str()));
+        model_ids.extend(self.rules.iter().map(|rule| rule.model_id.as_str()));
+
+        model_ids
+    }
+
+    // matches a stored router that isn't deleted and names the model in any of
+    // the places model_references looks at
+    pub fn model_reference_filter(model_id: &str) -> Document {
+        doc! {
+            "deleted": false,
+            "$or": [
+                {"model_id": model_id},
+                {"prompt_calification_model_categories.model_id": model_id},
+                {"prompt_calification_model_categories.alternative_model_ids": model_id},
+                {"sentences.model_id": model_id},
+                {"fallback_model_ids": model_id},
+                {"rules.model_id": model_id},
+            ],
+        }
+    }
+
+    // lists that end up naming the replacement twice keep its first position
+    pub fn replace_model(&mut self, model_id: &str, replacement: &str) {
+        let replace = |id: &mut String| {
+            if id == model_id {
+                *id = replacement.to_string();
+            }
+        };
+
+        replace(&mut self.model_id);
+
+        for category in self.prompt_calification_model_categories.iter_mut() {
+            replace(&mut category.model_id);
+            category.alternative_model_ids.iter_mut().for_each(replace);
+
+            let primary = category.model_id.clone();
+            let mut seen = vec![];
+            category.alternative_model_ids.retain(|id| {
+                if id == &primary || seen.contains(id) {
+                    return false;
+                }
+
+                seen.push(id.clone());
+                true
+            });
+        }
+
+        self.sentences.iter_mut().for_each(|sentence| replace(&mut sentence.model_id));
+        self.rules.iter_mut().for_each(|rule| replace(&mut rule.model_id));
+
+        self.fallback_model_ids.iter_mut().for_each(replace);
+        let mut seen = vec![];
+        self.fallback_model_ids.retain(|id| {
+            if seen.contains(id) {
+                return false;
+            }
+
+            seen.push(id.clone());
+            true
+        });
+    }
 }
 
 impl Into<Bson> for Router {
@@ -98,6 +276,11 @@ impl Into<Bson> for Router {
             "prompt_calification_model_categories": self.prompt_calification_model_categories,
             "use_sentence_matching": self.use_sentence_matching,
             "sentences": self.sentences,
+            "embedding_model_id": self.embedding_model_id,
+            "fallback_model_ids": self.fallback_model_ids,
+            "rules": self.rules,
+            "use_latency_routing": self.use_latency_routing,
+            "latency_slo_ms": self.latency_slo_ms as i64,
         }
         .into() // Convert the document into a Bson value
     }
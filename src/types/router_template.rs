@@ -0,0 +1,183 @@
+use serde::Serialize;
+
+// a model the template needs, bound to one of the org's registered models when
+// the template is instantiated
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateModelSlot {
+    pub id: &'static str,
+    pub description: &'static str,
+    // tried in order when the request doesn't bind the slot itself
+    pub suggested_model_ids: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateCategory {
+    pub label: &'static str,
+    pub description: &'static str,
+    pub slot: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateSentence {
+    pub text: &'static str,
+    pub slot: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub slots: Vec<TemplateModelSlot>,
+    pub categories: Vec<TemplateCategory>,
+    pub sentences: Vec<TemplateSentence>,
+    // become the router's fallback chain, in order
+    pub fallback_slots: Vec<&'static str>,
+}
+
+impl RouterTemplate {
+    pub fn find(id: &str) -> Option<RouterTemplate> {
+        RouterTemplate::all().into_iter().find(|template| template.id == id)
+    }
+
+    pub fn all() -> Vec<RouterTemplate> {
+        vec![
+            RouterTemplate {
+                id: "coding-chat-summarization",
+                name: "Coding, chat and summarization",
+                description: "Sends programming prompts to a strong model, summaries to a long context model and everything else to a cheap chat model",
+                slots: vec![
+                    TemplateModelSlot {
+                        id: "coding",
+                        description: "writes and explains code",
+                        suggested_model_ids: vec!["gpt-4-turbo", "gpt-4", "claude-2.1"],
+                    },
+                    TemplateModelSlot {
+                        id: "chat",
+                        description: "cheap general conversation",
+                        suggested_model_ids: vec!["gpt-3.5-turbo", "claude-instant-1.2", "command-light"],
+                    },
+                    TemplateModelSlot {
+                        id: "summarization",
+                        description: "long inputs condensed into short outputs",
+                        suggested_model_ids: vec!["claude-2.1", "gpt-3.5-turbo-16k", "gpt-4-turbo"],
+                    },
+                ],
+                categories: vec![
+                    TemplateCategory {
+                        label: "coding",
+                        description: "programming, code, debugging, software errors, functions and scripts",
+                        slot: "coding",
+                    },
+                    TemplateCategory {
+                        label: "summarization",
+                        description: "summarize, shorten or extract the key points of a text or document",
+                        slot: "summarization",
+                    },
+                    TemplateCategory {
+                        label: "chat",
+                        description: "casual conversation, questions, advice and general knowledge",
+                        slot: "chat",
+                    },
+                ],
+                sentences: vec![
+                    TemplateSentence { text: "write a python function that", slot: "coding" },
+                    TemplateSentence { text: "why does my code throw this error", slot: "coding" },
+                    TemplateSentence { text: "summarize this article in a few sentences", slot: "summarization" },
+                    TemplateSentence { text: "give me the key points of this document", slot: "summarization" },
+                    TemplateSentence { text: "what do you think about", slot: "chat" },
+                ],
+                fallback_slots: vec!["chat"],
+            },
+            RouterTemplate {
+                id: "support-triage",
+                name: "Support triage",
+                description: "Routes customer support messages by intent, billing and account issues get the careful model",
+                slots: vec![
+                    TemplateModelSlot {
+                        id: "careful",
+                        description: "handles money and account access, accuracy matters more than cost",
+                        suggested_model_ids: vec!["gpt-4-turbo", "gpt-4", "claude-2.1"],
+                    },
+                    TemplateModelSlot {
+                        id: "technical",
+                        description: "troubleshooting and product questions",
+                        suggested_model_ids: vec!["gpt-3.5-turbo", "claude-2", "command"],
+                    },
+                    TemplateModelSlot {
+                        id: "general",
+                        description: "greetings, feedback and everything else",
+                        suggested_model_ids: vec!["gpt-3.5-turbo", "claude-instant-1.2", "command-light"],
+                    },
+                ],
+                categories: vec![
+                    TemplateCategory {
+                        label: "billing",
+                        description: "payments, invoices, refunds, charges, subscriptions and pricing",
+                        slot: "careful",
+                    },
+                    TemplateCategory {
+                        label: "account",
+                        description: "login problems, password reset, account access and security",
+                        slot: "careful",
+                    },
+                    TemplateCategory {
+                        label: "technical",
+                        description: "bugs, errors, something not working, setup and integration help",
+                        slot: "technical",
+                    },
+                    TemplateCategory {
+                        label: "general",
+                        description: "greetings, feedback, feature requests and other questions",
+                        slot: "general",
+                    },
+                ],
+                sentences: vec![
+                    TemplateSentence { text: "i was charged twice", slot: "careful" },
+                    TemplateSentence { text: "i want a refund", slot: "careful" },
+                    TemplateSentence { text: "i can't log in to my account", slot: "careful" },
+                    TemplateSentence { text: "the app keeps crashing", slot: "technical" },
+                    TemplateSentence { text: "how do i set up the integration", slot: "technical" },
+                    TemplateSentence { text: "i have a suggestion", slot: "general" },
+                ],
+                fallback_slots: vec!["general"],
+            },
+            RouterTemplate {
+                id: "cheap-first-escalation",
+                name: "Cheap first with escalation",
+                description: "Everyday prompts go to a cheap model, complex ones and provider failures escalate to a strong model",
+                slots: vec![
+                    TemplateModelSlot {
+                        id: "cheap",
+                        description: "answers most prompts for a fraction of the cost",
+                        suggested_model_ids: vec!["gpt-3.5-turbo", "claude-instant-1.2", "command-light"],
+                    },
+                    TemplateModelSlot {
+                        id: "strong",
+                        description: "reasoning, long or high stakes prompts",
+                        suggested_model_ids: vec!["gpt-4-turbo", "gpt-4", "claude-2.1"],
+                    },
+                ],
+                categories: vec![
+                    TemplateCategory {
+                        label: "simple",
+                        description: "short questions, lookups, rewording, translation and small talk",
+                        slot: "cheap",
+                    },
+                    TemplateCategory {
+                        label: "complex",
+                        description: "multi step reasoning, analysis, planning, math and detailed explanations",
+                        slot: "strong",
+                    },
+                ],
+                sentences: vec![
+                    TemplateSentence { text: "translate this to spanish", slot: "cheap" },
+                    TemplateSentence { text: "rewrite this sentence", slot: "cheap" },
+                    TemplateSentence { text: "analyze the pros and cons step by step", slot: "strong" },
+                    TemplateSentence { text: "solve this math problem", slot: "strong" },
+                ],
+                fallback_slots: vec!["strong"],
+            },
+        ]
+    }
+}
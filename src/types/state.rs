@@ -1,11 +1,13 @@
-use std::sync::{Arc, Mutex};
+use std::{sync::Arc, time::Duration};
 
 use diesel::{r2d2::ConnectionManager, PgConnection};
 use r2d2::Pool;
 
 use mongodb::{Client as MongoClient, Database};
-use redis::Client as RedisClient;
-use rust_bert::pipelines::{sentence_embeddings::SentenceEmbeddingsModel, zero_shot_classification::ZeroShotClassificationModel};
+use redis::{aio::ConnectionManager as RedisConnectionManager, Client as RedisClient, RedisResult};
+use tokio::sync::OnceCell;
+
+use crate::{providers::circuit_breaker::CircuitBreaker, storage::diesel_postgres::usage::UsageWriter, inference::{backend::ZeroShotClassificationModel, registry::EmbeddingModelRegistry, scheduler::ClassificationScheduler, slot::ModelSlot}, types::{catalog::ModelCatalog, customer::CustomerID}};
 
 use super::lemonsqueezy::Products;
 
@@ -27,17 +29,35 @@ pub struct GoogleAuth {
     pub redirect_url: String,
 }
 
+// upstream providers /v1/chat/completions forwards prompts to
+#[derive(Clone)]
+pub struct ProviderSettings {
+    pub openai_api_key: Option<String>,
+    pub openai_api_base_url: String,
+    pub anthropic_api_key: Option<String>,
+    pub anthropic_api_base_url: String,
+    pub cohere_api_key: Option<String>,
+    pub cohere_api_base_url: String,
+    pub request_timeout: Duration,
+}
+
+#[derive(Clone, Copy)]
+pub struct FailoverSettings {
+    // retries of the same model before moving down the fallback chain
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
 #[derive(Clone)]
 pub struct PromptClassificationModel {
-    pub model: Option<Arc<Box<Mutex<ZeroShotClassificationModel>>>>,
-    pub name: String,
-    pub url: String,
+    pub slot: Arc<ModelSlot<ZeroShotClassificationModel>>,
+    pub scheduler: ClassificationScheduler,
 }
 
 #[derive(Clone)]
 pub struct LLMResources {
     pub prompt_classification_model: PromptClassificationModel,
-    pub embedding_model: Option<Arc<Box<Mutex<SentenceEmbeddingsModel>>>>,
+    pub embedding_models: EmbeddingModelRegistry,
 }
 
 #[derive(Clone)]
@@ -51,6 +71,9 @@ pub struct AppState {
     pub mongo_db: Database,
 
     pub redis_connection: RedisClient,
+    // one multiplexed connection shared by every request, opened on first use
+    // and reconnected by the manager when redis drops it
+    pub redis_manager: Arc<OnceCell<RedisConnectionManager>>,
     pub postgres_conn: Option<Pool<ConnectionManager<PgConnection>>>,
 
     pub lemonsqueezy_webhook_signature_key: String,
@@ -61,5 +84,28 @@ pub struct AppState {
     pub email_provider_settings: EmailProviderSettings,
 
     pub google_auth: GoogleAuth,
+    pub platform_admins: Vec<CustomerID>,
+    // client ips are read from Fly-Client-IP/X-Forwarded-For instead of the peer
+    pub trust_proxy_headers: bool,
     pub llm_resources: LLMResources,
-}
\ No newline at end of file
+
+    pub http_client: reqwest::Client,
+    pub provider_settings: ProviderSettings,
+    pub failover_settings: FailoverSettings,
+    pub circuit_breaker: Arc<CircuitBreaker>,
+    // org provider credentials can't be stored or used without it
+    pub credentials_encryption_key: Option<[u8; 32]>,
+    pub usage_writer: UsageWriter,
+    // bundled models merged with the overrides stored in mongo
+    pub model_catalog: Arc<ModelCatalog>,
+}
+impl AppState {
+    // async handlers go through this, the blocking client would hold a worker thread
+    pub async fn redis(&self) -> RedisResult<RedisConnectionManager> {
+        let client = self.redis_connection.clone();
+        // a single retry, requests wait on it while redis is unreachable
+        let conn = self.redis_manager.get_or_try_init(|| RedisConnectionManager::new_with_backoff(client, 2, 100, 1)).await?;
+
+        Ok(conn.clone())
+    }
+}
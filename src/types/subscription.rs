@@ -16,6 +16,41 @@ impl Slug {
     }
 }
 
+// per plan ceilings enforced by utilities::quotas
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PlanLimits {
+    pub orgs: u64,
+    pub routers_per_org: u64,
+    pub models_per_org: u64,
+    pub sentences_per_router: u64,
+    pub monthly_routing_calls: u64,
+    // scales the per router request rate of utilities::rate_limit
+    pub rate_limit_multiplier: u64,
+}
+
+impl Slug {
+    pub const fn limits(&self) -> PlanLimits {
+        match self {
+            Slug::FREE => PlanLimits {
+                orgs: 1,
+                routers_per_org: 3,
+                models_per_org: 5,
+                sentences_per_router: 20,
+                monthly_routing_calls: 10_000,
+                rate_limit_multiplier: 1,
+            },
+            Slug::PRO => PlanLimits {
+                orgs: 10,
+                routers_per_org: 50,
+                models_per_org: 50,
+                sentences_per_router: 500,
+                monthly_routing_calls: 1_000_000,
+                rate_limit_multiplier: 10,
+            },
+        }
+    }
+}
+
 impl FromStr for Slug {
     type Err = ();
 
@@ -87,4 +122,15 @@ pub struct Subscription {
     pub renews_at: String,
 
     pub history_logs: Vec<SubscriptionHistoryLog>,
+}
+
+impl Subscription {
+    // cancelled subscriptions keep the plan until they expire, lemonsqueezy
+    // moves them to expired at ends_at
+    pub fn plan(&self) -> Slug {
+        match self.status.as_str() {
+            "expired" | "unpaid" | "paused" => Slug::FREE,
+            _ => self.slug.parse::<Slug>().unwrap_or(Slug::FREE),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::storage::diesel_postgres::usage::UsageBucket;
+
+use super::catalog::ModelCatalog;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotals {
+    pub calls: i64,
+    pub errors: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub average_latency_ms: f64,
+    // USD from the current catalog pricing, custom models count as 0
+    pub cost: f64,
+    #[serde(skip)]
+    latency_ms: i64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, bucket: &UsageBucket, catalog: &ModelCatalog) {
+        self.calls += bucket.calls;
+        self.errors += bucket.errors;
+        self.prompt_tokens += bucket.prompt_tokens;
+        self.completion_tokens += bucket.completion_tokens;
+        self.latency_ms += bucket.latency_ms;
+        self.average_latency_ms = match self.calls {
+            0 => 0.0,
+            calls => self.latency_ms as f64 / calls as f64,
+        };
+        self.cost += catalog
+            .cost(&bucket.model_id, bucket.prompt_tokens as u64, bucket.completion_tokens as u64)
+            .unwrap_or(0.0);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageGroupTotals {
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsagePoint {
+    pub period: String,
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub from: String,
+    pub to: String,
+    pub group_by: String,
+    pub granularity: String,
+    pub totals: UsageTotals,
+    pub groups: Vec<UsageGroupTotals>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageTimeSeries {
+    pub from: String,
+    pub to: String,
+    pub group_by: String,
+    pub granularity: String,
+    pub points: Vec<UsagePoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreHistogramBucket {
+    pub min: f64,
+    pub max: f64,
+    pub decisions: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyPercentiles {
+    pub period: String,
+    // routed decisions only, early errors would pull the percentiles down
+    pub decisions: i64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterAnalytics {
+    pub router_id: String,
+    pub from: String,
+    pub to: String,
+    pub granularity: String,
+    pub decisions: i64,
+    // decisions that ended in an error, e.g. prompt.calification.error
+    pub errors: i64,
+    // sentence matching without an appropiate match
+    pub abstentions: i64,
+    pub error_rate: f64,
+    pub abstention_rate: f64,
+    pub strategies: BTreeMap<String, i64>,
+    pub categories: BTreeMap<String, i64>,
+    pub models: BTreeMap<String, i64>,
+    pub statuses: BTreeMap<String, i64>,
+    // per strategy, each one scores on its own scale
+    pub score_histogram: BTreeMap<String, Vec<ScoreHistogramBucket>>,
+    pub latency: Vec<LatencyPercentiles>,
+}
@@ -0,0 +1,145 @@
+use mongodb::bson::{doc, Bson};
+use serde::{Deserialize, Serialize};
+
+use crate::utilities::vault::mask_secret;
+
+use super::{customer::CustomerID, organization::EncryptedSecret};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum WebhookEvent {
+    #[serde(rename = "router.updated")]
+    RouterUpdated,
+    #[serde(rename = "model.added")]
+    ModelAdded,
+    #[serde(rename = "model.removed")]
+    ModelRemoved,
+    // sent wherever membership is set, org creation for now
+    #[serde(rename = "member.changed")]
+    MemberChanged,
+    #[serde(rename = "quota.threshold_reached")]
+    QuotaThresholdReached,
+    #[serde(rename = "provider.failover")]
+    ProviderFailover,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::RouterUpdated => "router.updated",
+            WebhookEvent::ModelAdded => "model.added",
+            WebhookEvent::ModelRemoved => "model.removed",
+            WebhookEvent::MemberChanged => "member.changed",
+            WebhookEvent::QuotaThresholdReached => "quota.threshold_reached",
+            WebhookEvent::ProviderFailover => "provider.failover",
+        }
+    }
+}
+
+impl From<WebhookEvent> for Bson {
+    fn from(event: WebhookEvent) -> Self {
+        Bson::String(event.as_str().to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    // https only
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    // hmac-sha256 key of the X-Signature header, only returned on creation and
+    // stored with the credentials vault
+    #[serde(default)]
+    pub encrypted_secret: Option<EncryptedSecret>,
+    #[serde(default)]
+    pub masked_secret: String,
+    // plain secret of webhooks created before the vault, encrypted at startup
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    pub created_by: CustomerID,
+    pub created_at: String,
+}
+
+impl Into<Bson> for Webhook {
+    fn into(self) -> Bson {
+        doc! {
+            "id": self.id,
+            "url": self.url,
+            "events": self.events,
+            "encrypted_secret": self.encrypted_secret,
+            "masked_secret": self.masked_secret,
+            "created_by": self.created_by,
+            "created_at": self.created_at,
+        }
+        .into()
+    }
+}
+
+// what the webhook list shows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskedWebhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub masked_secret: String,
+    pub created_by: CustomerID,
+    pub created_at: String,
+}
+
+impl From<&Webhook> for MaskedWebhook {
+    fn from(webhook: &Webhook) -> Self {
+        MaskedWebhook {
+            id: webhook.id.clone(),
+            url: webhook.url.clone(),
+            events: webhook.events.clone(),
+            masked_secret: match &webhook.secret {
+                Some(secret) if webhook.masked_secret.is_empty() => mask_secret(secret),
+                _ => webhook.masked_secret.clone(),
+            },
+            created_by: webhook.created_by.clone(),
+            created_at: webhook.created_at.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl From<DeliveryStatus> for Bson {
+    fn from(status: DeliveryStatus) -> Self {
+        match status {
+            DeliveryStatus::Pending => Bson::String("pending".to_string()),
+            DeliveryStatus::Delivered => Bson::String("delivered".to_string()),
+            DeliveryStatus::Failed => Bson::String("failed".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub org_id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    // exact body that was signed and sent, redeliveries send it again
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    // unix millis, pending deliveries are sent once it has passed. An attempt
+    // pushes it past its timeout first so a restart retries it and nobody else does
+    #[serde(default)]
+    pub next_attempt_at: Option<i64>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub created_at: String,
+    // unix millis, used to sort the delivery log
+    pub timestamp: i64,
+    pub last_attempt_at: Option<String>,
+    // id of the delivery this one was sent again from
+    pub redelivery_of: Option<String>,
+}
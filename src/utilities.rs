@@ -1,4 +1,12 @@
 pub mod helpers;
 pub mod token;
 pub mod email;
-pub mod api_messages;
\ No newline at end of file
+pub mod api_messages;
+pub mod catalog;
+pub mod vault;
+pub mod quotas;
+pub mod model_stats;
+pub mod rate_limit;
+pub mod request_log;
+pub mod routing_rules;
+pub mod webhooks;
\ No newline at end of file
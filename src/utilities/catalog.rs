@@ -0,0 +1,40 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use log::error;
+use mongodb::{bson::doc, Database};
+
+use crate::{
+    storage::mongo::get_model_catalog_collection,
+    types::{catalog::{CatalogModel, ModelCatalog}, state::AppState},
+};
+
+// admin edits are applied right away on the instance that received them, the
+// others pick them up on the next refresh
+const CATALOG_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+pub async fn load_catalog_overrides(db: &Database, catalog: &ModelCatalog) -> Result<usize, mongodb::error::Error> {
+    let collection = get_model_catalog_collection(db).await;
+    let mut models: Vec<CatalogModel> = vec![];
+
+    let mut cursor = collection.find(doc! {}, None).await?;
+    while let Some(model) = cursor.next().await {
+        models.push(model?);
+    }
+
+    let loaded = models.len();
+    catalog.set_overrides(models);
+    Ok(loaded)
+}
+
+pub fn start_catalog_refresh(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CATALOG_REFRESH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = load_catalog_overrides(&state.mongo_db, &state.model_catalog).await {
+                error!("model catalog refresh failed: {}", e);
+            }
+        }
+    });
+}
@@ -1,5 +1,4 @@
-use std::sync::{Arc, Mutex};
-
+use crate::inference::{backend::RustBertError, registry::EmbeddingModel};
 use crate::types::{customer::{GenericResponse, CustomerType}, subscription::SubscriptionHistoryLog};
 use axum::{
     extract::rejection::JsonRejection,
@@ -10,8 +9,8 @@ use mongodb::bson::{to_document, Document};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use regex::Regex;
-use rust_bert::{pipelines::sentence_embeddings::SentenceEmbeddingsModel, RustBertError};
 use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 
 use super::api_messages::{APIMessages, CustomerMessages, EmailMessages, InputMessages};
 
@@ -138,6 +137,12 @@ pub async fn add_subscription_history_log_and_to_bson(mut history_logs: Vec<Subs
 
 // LLM
 
+// stable id for an access token that can be stored and shown without leaking it
+pub fn token_fingerprint(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    hex::encode(&digest[..8])
+}
+
 pub fn calculate_cosine_similarity(embedding1: Vec<f32>, embedding2: Vec<f32>) -> f32 {
     let dot_product = embedding1.iter().zip(embedding2.iter()).map(|(a, b)| a * b).sum::<f32>();
     let norm1 = embedding1.iter().map(|x| x.powi(2)).sum::<f32>().sqrt();
@@ -146,13 +151,12 @@ pub fn calculate_cosine_similarity(embedding1: Vec<f32>, embedding2: Vec<f32>) -
     dot_product / (norm1 * norm2)
 }
 
-pub async fn detect_similar_sentences(model: &Arc<Box<Mutex<SentenceEmbeddingsModel>>>, sentence_one: String, sentence_two: String, temperature: f32) -> Result<(bool, f32), RustBertError> {
-    let model = model.lock().unwrap();
-    let sentences = [sentence_one, sentence_two];
-    let embeddings = model.encode(&sentences)?;
-    drop(model);
+pub async fn detect_similar_sentences(model: &EmbeddingModel, sentence: &String, prompt_embedding: &Vec<f32>, temperature: f32) -> Result<(bool, f32), RustBertError> {
+    // router sentences repeat on every request, their vectors are cached per model
+    let sentence_embedding = model.cached_encode(sentence).await?;
+
     // Calculate cosine similarity
-    let similarity = calculate_cosine_similarity(embeddings[0].clone(), embeddings[1].clone());
+    let similarity = calculate_cosine_similarity(sentence_embedding, prompt_embedding.clone());
     if similarity.is_nan() {
         return Ok((false, similarity));
     }
@@ -275,6 +279,27 @@ pub fn unauthorized(message: &str, data: Option<Value>) -> (StatusCode, Json<Gen
     )
 }
 
+pub fn too_many_requests(message: &str, data: Option<Value>) -> (StatusCode, Json<GenericResponse>) {
+    let data = match data {
+        Some(data) => data,
+        None => json!({}),
+    };
+
+    let message = match message {
+        "" => "too.many.requests",
+        _ => message,
+    };
+    
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(GenericResponse {
+            message: message.to_string(),
+            data,
+            exit_code: 1,
+        }),
+    )
+}
+
 #[allow(dead_code)]
 pub fn forbidden(message: &str, data: Option<Value>) -> (StatusCode, Json<GenericResponse>) {
     let data = match data {
@@ -296,3 +321,24 @@ pub fn forbidden(message: &str, data: Option<Value>) -> (StatusCode, Json<Generi
         }),
     )
 }
+
+pub fn conflict(message: &str, data: Option<Value>) -> (StatusCode, Json<GenericResponse>) {
+    let data = match data {
+        Some(data) => data,
+        None => json!({}),
+    };
+
+    let message = match message {
+        "" => "conflict",
+        _ => message,
+    };
+
+    (
+        StatusCode::CONFLICT,
+        Json(GenericResponse {
+            message: message.to_string(),
+            data,
+            exit_code: 1,
+        }),
+    )
+}
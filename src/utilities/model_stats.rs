@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use log::error;
+use redis::RedisResult;
+use serde::{Deserialize, Serialize};
+
+use crate::types::state::AppState;
+
+// stats cover the last STATS_WINDOW_MINUTES minutes, one redis hash per minute
+const STATS_WINDOW_MINUTES: i64 = 10;
+// a model needs this many calls in the window before its stats are trusted
+const MIN_CALLS: u64 = 10;
+const UNHEALTHY_ERROR_RATE: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelHealth {
+    pub model_id: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    // successful calls only, timeouts would drag it to the provider timeout
+    pub avg_latency_ms: Option<f64>,
+    // average latency inflated by the error rate, a failed call costs about one more call
+    pub expected_latency_ms: Option<f64>,
+    pub healthy: bool,
+}
+
+fn stats_key(org_id: &str, model_id: &str, minute: i64) -> String {
+    format!("model_stats:{}:{}:{}", org_id, model_id, minute)
+}
+
+// proxied calls and client reported timings both end up here, failed calls
+// only count towards the error rate
+pub async fn record_model_call(state: &AppState, org_id: &str, model_id: &str, latency_ms: u64, success: bool) {
+    let key = stats_key(org_id, model_id, Utc::now().timestamp() / 60);
+    let mut redis_conn = match state.redis().await {
+        Ok(redis_conn) => redis_conn,
+        Err(e) => {
+            error!("failed to record stats of model {}: {}", model_id, e);
+            return;
+        }
+    };
+
+    let mut pipe = redis::pipe();
+    pipe.hincr(&key, "calls", 1).ignore();
+    match success {
+        true => pipe.hincr(&key, "latency_ms", latency_ms).ignore(),
+        false => pipe.hincr(&key, "errors", 1).ignore(),
+    };
+    pipe.expire(&key, (STATS_WINDOW_MINUTES + 1) * 60).ignore();
+
+    let result: RedisResult<()> = pipe.query_async(&mut redis_conn).await;
+    if let Err(e) = result {
+        error!("failed to record stats of model {}: {}", model_id, e);
+    }
+}
+
+fn health_from_counters(model_id: &str, calls: u64, errors: u64, latency_ms: u64) -> ModelHealth {
+    let successes = calls.saturating_sub(errors);
+    let error_rate = match calls {
+        0 => 0.0,
+        _ => errors as f64 / calls as f64,
+    };
+
+    let avg_latency_ms = match successes {
+        0 => None,
+        _ => Some(latency_ms as f64 / successes as f64),
+    };
+
+    ModelHealth {
+        model_id: model_id.to_string(),
+        calls,
+        errors,
+        error_rate,
+        avg_latency_ms,
+        expected_latency_ms: avg_latency_ms.map(|latency| latency * (1.0 + error_rate)),
+        healthy: calls < MIN_CALLS || error_rate < UNHEALTHY_ERROR_RATE,
+    }
+}
+
+// models without stats come back healthy with no latency, redis errors too
+pub async fn model_health(state: &AppState, org_id: &str, model_ids: &[String]) -> HashMap<String, ModelHealth> {
+    let current_minute = Utc::now().timestamp() / 60;
+    let mut pipe = redis::pipe();
+    for model_id in model_ids {
+        for minute in (current_minute - STATS_WINDOW_MINUTES + 1)..=current_minute {
+            pipe.hgetall(stats_key(org_id, model_id, minute));
+        }
+    }
+
+    let buckets: Vec<HashMap<String, u64>> = match state.redis().await {
+        Ok(mut redis_conn) => match pipe.query_async(&mut redis_conn).await {
+            Ok(buckets) => buckets,
+            Err(e) => {
+                error!("failed to load model stats of org {}: {}", org_id, e);
+                vec![]
+            }
+        },
+        Err(e) => {
+            error!("failed to load model stats of org {}: {}", org_id, e);
+            vec![]
+        }
+    };
+
+    model_ids
+        .iter()
+        .enumerate()
+        .map(|(index, model_id)| {
+            let start = index * STATS_WINDOW_MINUTES as usize;
+            let (mut calls, mut errors, mut latency_ms) = (0, 0, 0);
+            for bucket in buckets.iter().skip(start).take(STATS_WINDOW_MINUTES as usize) {
+                calls += bucket.get("calls").copied().unwrap_or(0);
+                errors += bucket.get("errors").copied().unwrap_or(0);
+                latency_ms += bucket.get("latency_ms").copied().unwrap_or(0);
+            }
+
+            (model_id.clone(), health_from_counters(model_id, calls, errors, latency_ms))
+        })
+        .collect()
+}
+
+// healthy models expected under the slo win, fastest first, then models without
+// enough data in the order given, then the fastest healthy model over the slo,
+// none when every allowed model is unhealthy
+pub fn pick_by_latency<'a>(health: &HashMap<String, ModelHealth>, allowed: &'a [String], slo_ms: u32) -> Option<&'a String> {
+    let expected = |model_id: &String| health.get(model_id).and_then(|model| match model.healthy && model.calls >= MIN_CALLS {
+        true => model.expected_latency_ms,
+        false => None,
+    });
+
+    let fastest = |under_slo: bool| {
+        allowed
+            .iter()
+            .filter_map(|model_id| expected(model_id).map(|latency| (model_id, latency)))
+            .filter(|(_, latency)| !under_slo || *latency <= slo_ms as f64)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(model_id, _)| model_id)
+    };
+
+    if let Some(model_id) = fastest(true) {
+        return Some(model_id);
+    }
+
+    let unknown = allowed.iter().find(|model_id| match health.get(*model_id) {
+        Some(model) => model.calls < MIN_CALLS,
+        None => true,
+    });
+
+    if unknown.is_some() {
+        return unknown;
+    }
+
+    fastest(false)
+}
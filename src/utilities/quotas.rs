@@ -0,0 +1,122 @@
+use axum::{http::StatusCode, Json};
+use chrono::{Datelike, Utc};
+use log::error;
+use mongodb::{bson::doc, Database};
+use redis::AsyncCommands;
+use serde_json::json;
+
+use crate::{
+    storage::mongo::{build_customer_filter, find_customer, get_organizations_collection},
+    types::{
+        customer::GenericResponse,
+        organization::{MemberRole, Organization},
+        state::AppState,
+        subscription::Slug,
+        webhook::WebhookEvent,
+    },
+};
+
+use super::{helpers::{forbidden, internal_server_error}, webhooks::emit_event};
+
+// a bit more than a month, the key of the next month takes over anyway
+const MONTHLY_COUNTER_TTL_SECS: i64 = 32 * 24 * 60 * 60;
+// share of a quota at which webhooks are told about it
+const QUOTA_THRESHOLD_PERCENTS: [u64; 2] = [80, 100];
+
+pub fn quota_exceeded(plan: Slug, resource: &str, usage: u64, limit: u64) -> (StatusCode, Json<GenericResponse>) {
+    forbidden("quota.exceeded", Some(json!({
+        "plan": plan.to_string(),
+        "resource": resource,
+        "usage": usage,
+        "limit": limit,
+    })))
+}
+
+pub fn check_quota(plan: Slug, resource: &str, usage: u64, limit: u64) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    if usage >= limit {
+        return Err(quota_exceeded(plan, resource, usage, limit));
+    }
+
+    Ok(())
+}
+
+pub async fn customer_plan(db: &Database, customer_id: &str) -> Result<Slug, (StatusCode, Json<GenericResponse>)> {
+    let filter = build_customer_filter(customer_id, "").await;
+    let customer = find_customer(db, filter).await?;
+
+    Ok(customer.subscription.plan())
+}
+
+// orgs are billed to their owner
+pub async fn org_plan(db: &Database, org: &Organization) -> Result<Slug, (StatusCode, Json<GenericResponse>)> {
+    let owner = match org.members.iter().find(|member| member.role == MemberRole::Owner) {
+        Some(owner) => owner,
+        None => return Ok(Slug::FREE),
+    };
+
+    customer_plan(db, &owner.id).await
+}
+
+pub async fn count_owned_orgs(db: &Database, customer_id: &str) -> Result<u64, (StatusCode, Json<GenericResponse>)> {
+    let filter = doc! {
+        "members": {"$elemMatch": {"id": customer_id, "role": "owner"}},
+        "deleted": false,
+    };
+
+    let collection = get_organizations_collection(db).await;
+    match collection.count_documents(filter, None).await {
+        Ok(count) => Ok(count),
+        Err(_) => Err(internal_server_error("database.error", None)),
+    }
+}
+
+// counts the call before routing it, calls over the limit are taken back so
+// the counter never goes past it. When redis can't be reached the call is let
+// through unmetered, routing stays available and only the quota goes unenforced
+pub async fn consume_monthly_routing_call(state: &AppState, org: &Organization, plan: Slug) -> Result<(), (StatusCode, Json<GenericResponse>)> {
+    let org_id = &org.id;
+    let limit = plan.limits().monthly_routing_calls;
+    let now = Utc::now();
+    let key = format!("routing_calls:{}:{}{:02}", org_id, now.year(), now.month());
+
+    let mut redis_conn = match state.redis().await {
+        Ok(redis_conn) => redis_conn,
+        Err(e) => {
+            error!("failed to count routing call of org {}, letting it through: {}", org_id, e);
+            return Ok(());
+        }
+    };
+
+    let usage: u64 = match redis_conn.incr(&key, 1).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            error!("failed to count routing call of org {}, letting it through: {}", org_id, e);
+            return Ok(());
+        }
+    };
+
+    if usage == 1 {
+        let _: Result<bool, redis::RedisError> = redis_conn.expire(&key, MONTHLY_COUNTER_TTL_SECS).await;
+    }
+
+    if usage > limit {
+        // the counter can be past the limit after a downgrade, report what it holds
+        let usage = redis_conn.decr(&key, 1).await.unwrap_or(usage - 1);
+        return Err(quota_exceeded(plan, "monthly_routing_calls", usage, limit));
+    }
+
+    // the counter hits each threshold exactly once a month
+    for percent in QUOTA_THRESHOLD_PERCENTS {
+        if usage == limit * percent / 100 {
+            emit_event(state, org, WebhookEvent::QuotaThresholdReached, json!({
+                "plan": plan.to_string(),
+                "resource": "monthly_routing_calls",
+                "usage": usage,
+                "limit": limit,
+                "threshold_percent": percent,
+            }));
+        }
+    }
+
+    Ok(())
+}
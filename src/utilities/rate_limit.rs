@@ -0,0 +1,400 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::error;
+use rand::Rng;
+use redis::{aio::ConnectionManager, AsyncCommands, RedisResult};
+use serde_json::json;
+
+use crate::{
+    storage::mongo::{build_organizations_access_token_filter, build_organizations_filter, find_organization},
+    types::{state::AppState, subscription::Slug},
+};
+
+use super::{
+    helpers::{token_fingerprint, too_many_requests},
+    quotas::{customer_plan, org_plan},
+    token::validate_token,
+};
+
+// a plan change takes at most this long to reach the limiter
+const PLAN_CACHE_TTL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    // every router keeps its own windows
+    pub name: &'static str,
+    // requests per window of a free plan caller, scaled by the plan's multiplier
+    pub requests: u64,
+    pub window: Duration,
+    // /v1 answers with the OpenAI error shape
+    pub openai_errors: bool,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: Arc<AppState>,
+    policy: RateLimitPolicy,
+}
+
+impl RateLimiter {
+    pub fn new(state: Arc<AppState>, policy: RateLimitPolicy) -> RateLimiter {
+        RateLimiter { state, policy }
+    }
+}
+
+struct WindowState {
+    limit: u64,
+    remaining: u64,
+    // seconds until the oldest request in the window leaves it
+    reset: u64,
+    exceeded: bool,
+}
+
+// proxy headers are only read behind a proxy that sets them (TRUST_PROXY_HEADERS),
+// anyone can send them otherwise
+fn client_ip(headers: &HeaderMap, request: &Request, trust_proxy_headers: bool) -> String {
+    // fly.io sets Fly-Client-IP, other proxies append the peer they saw to
+    // X-Forwarded-For, only that last hop is theirs, the rest comes from the client
+    if trust_proxy_headers {
+        if let Some(ip) = headers.get("Fly-Client-IP").and_then(|value| value.to_str().ok()) {
+            return ip.trim().to_string();
+        }
+
+        if let Some(ip) = headers
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+        {
+            return ip.to_string();
+        }
+    }
+
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(address)) => address.ip().to_string(),
+        None => String::from("unknown"),
+    }
+}
+
+// one window per address whoever calls from it, at the rate of the highest plan
+// so a paying caller behind a shared address isn't held to the free rate
+fn ip_limit(policy: RateLimitPolicy) -> u64 {
+    policy.requests * Slug::PRO.limits().rate_limit_multiplier
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let token = headers.get("Authorization")?.to_str().ok()?;
+    let token = token.trim_start_matches("Bearer ").trim();
+
+    match token.is_empty() {
+        true => None,
+        false => Some(token.to_string()),
+    }
+}
+
+// who a request is counted for, only known once its session or access token is
+// valid so org and plan can't be picked by an anonymous caller
+#[derive(Debug, Clone)]
+struct Caller {
+    plan: Slug,
+    org_id: Option<String>,
+}
+
+impl Caller {
+    fn to_cache(caller: &Option<Caller>) -> String {
+        match caller {
+            Some(caller) => format!("{} {}", caller.plan.to_string(), caller.org_id.as_deref().unwrap_or("")),
+            None => String::from("-"),
+        }
+    }
+
+    fn from_cache(value: &str) -> Option<Caller> {
+        let (plan, org_id) = value.split_once(' ')?;
+
+        Some(Caller {
+            plan: plan.parse::<Slug>().unwrap_or(Slug::FREE),
+            org_id: Some(org_id.to_string()).filter(|org_id| !org_id.is_empty()),
+        })
+    }
+}
+
+// a customer session, counted for the OrganizationID org when they're a member
+// of it, or an org access token. Err when the database couldn't tell
+async fn lookup_caller(state: &AppState, conn: &mut ConnectionManager, org_id: Option<&String>, token: &str) -> Result<Option<Caller>, ()> {
+    if let Ok(token_data) = validate_token(token) {
+        let customer_id = token_data.claims.sub;
+        // signed sessions are only valid while redis holds them
+        match conn.get::<_, Option<String>>(token).await {
+            Ok(Some(session)) if session == customer_id => {}
+            Ok(_) => return Ok(None),
+            Err(_) => return Err(()),
+        }
+
+        if let Some(org_id) = org_id {
+            let filter = build_organizations_filter(org_id).await;
+            match find_organization(&state.mongo_db, filter).await {
+                Ok(org) if org.members.iter().any(|member| member.id == customer_id) => {
+                    let plan = org_plan(&state.mongo_db, &org).await.unwrap_or(Slug::FREE);
+                    return Ok(Some(Caller { plan, org_id: Some(org.id) }));
+                }
+                Ok(_) | Err((StatusCode::NOT_FOUND, _)) => {}
+                Err(_) => return Err(()),
+            }
+        }
+
+        let plan = customer_plan(&state.mongo_db, &customer_id).await.unwrap_or(Slug::FREE);
+        return Ok(Some(Caller { plan, org_id: None }));
+    }
+
+    let filter = build_organizations_access_token_filter(token).await;
+    match find_organization(&state.mongo_db, filter).await {
+        Ok(org) => {
+            let plan = org_plan(&state.mongo_db, &org).await.unwrap_or(Slug::FREE);
+            Ok(Some(Caller { plan, org_id: Some(org.id) }))
+        }
+        Err((StatusCode::NOT_FOUND, _)) => Ok(None),
+        Err(_) => Err(()),
+    }
+}
+
+// the org header only matters to sessions, access tokens belong to a single org
+fn caller_cache_key(org_id: Option<&String>, token: &str) -> String {
+    match validate_token(token).is_ok() {
+        true => format!("rate_limit:caller:{}:{}", token_fingerprint(token), org_id.map(|org_id| org_id.as_str()).unwrap_or("")),
+        false => format!("rate_limit:caller:{}", token_fingerprint(token)),
+    }
+}
+
+// sliding window log, one sorted set member per request scored by its time
+async fn hit(conn: &mut ConnectionManager, key: &str, limit: u64, window: Duration) -> RedisResult<WindowState> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let window_ms = window.as_millis() as i64;
+    let member = format!("{}-{}", now, rand::thread_rng().gen::<u32>());
+
+    let (count, oldest): (u64, Vec<(String, i64)>) = redis::pipe()
+        .atomic()
+        .zrembyscore(key, 0, now - window_ms).ignore()
+        .zadd(key, &member, now).ignore()
+        .zcard(key)
+        .zrange_withscores(key, 0, 0)
+        .pexpire(key, window_ms).ignore()
+        .query_async(conn)
+        .await?;
+
+    let exceeded = count > limit;
+    if exceeded {
+        // rejected requests don't take a slot of the window
+        let _: RedisResult<()> = conn.zrem(key, &member).await;
+    }
+
+    let oldest = oldest.first().map(|(_, score)| *score).unwrap_or(now);
+    let reset = ((oldest + window_ms - now).max(0) as u64 + 999) / 1000;
+
+    Ok(WindowState {
+        limit,
+        remaining: limit.saturating_sub(count),
+        reset,
+        exceeded,
+    })
+}
+
+fn insert_header(headers: &mut HeaderMap, name: &'static str, value: u64) {
+    if let Ok(value) = HeaderValue::from_str(&value.to_string()) {
+        headers.insert(name, value);
+    }
+}
+
+// every caller is limited by client ip, access token and org, the tightest window
+// decides and is the one reported in the RateLimit-* headers
+pub async fn rate_limit(State(limiter): State<RateLimiter>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let policy = limiter.policy;
+
+    let ip = client_ip(headers, &request, limiter.state.trust_proxy_headers);
+    let token = bearer_token(headers);
+    let org_id = headers
+        .get("OrganizationID")
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.to_string());
+
+    let mut conn = match limiter.state.redis().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            // a redis outage shouldn't take the api down with it
+            error!("rate limiter can't reach redis: {}", e);
+            return next.run(request).await;
+        }
+    };
+
+    let ip_key = format!("rate_limit:{}:ip:{}", policy.name, ip);
+    let mut tightest: Option<WindowState> = None;
+
+    let caller = match &token {
+        Some(token) => {
+            let cache_key = caller_cache_key(org_id.as_ref(), token);
+            match conn.get::<_, Option<String>>(&cache_key).await {
+                Ok(Some(cached)) => Caller::from_cache(&cached),
+                _ => {
+                    // unknown tokens are only looked up while the ip has room, random
+                    // tokens can't flood the databases
+                    match hit(&mut conn, &ip_key, ip_limit(policy), policy.window).await {
+                        Ok(window) if window.exceeded => return limited(policy, window, None),
+                        Ok(window) => tightest = Some(window),
+                        Err(e) => error!("rate limiter failed to update {}: {}", ip_key, e),
+                    }
+
+                    match lookup_caller(&limiter.state, &mut conn, org_id.as_ref(), token).await {
+                        Ok(caller) => {
+                            let _: RedisResult<()> = conn.set_ex(&cache_key, Caller::to_cache(&caller), PLAN_CACHE_TTL_SECS).await;
+                            caller
+                        }
+                        Err(_) => None,
+                    }
+                }
+            }
+        }
+        None => None,
+    };
+
+    let plan = caller.as_ref().map(|caller| caller.plan).unwrap_or(Slug::FREE);
+    let limit = policy.requests * plan.limits().rate_limit_multiplier;
+
+    // the ip window was already counted when the caller had to be looked up
+    let mut keys = vec![];
+    if tightest.is_none() {
+        keys.push((ip_key, ip_limit(policy)));
+    }
+    if let (Some(caller), Some(token)) = (&caller, &token) {
+        keys.push((format!("rate_limit:{}:token:{}", policy.name, token_fingerprint(token)), limit));
+        if let Some(org_id) = &caller.org_id {
+            keys.push((format!("rate_limit:{}:org:{}", policy.name, org_id), limit));
+        }
+    }
+
+    for (key, limit) in keys {
+        let window = match hit(&mut conn, &key, limit, policy.window).await {
+            Ok(window) => window,
+            Err(e) => {
+                error!("rate limiter failed to update {}: {}", key, e);
+                continue;
+            }
+        };
+
+        let tighter = match &tightest {
+            Some(current) => window.exceeded || (!current.exceeded && window.remaining < current.remaining),
+            None => true,
+        };
+
+        if tighter {
+            let exceeded = window.exceeded;
+            tightest = Some(window);
+            if exceeded {
+                break;
+            }
+        }
+    }
+
+    let window = match tightest {
+        Some(window) => window,
+        None => return next.run(request).await,
+    };
+
+    match window.exceeded {
+        true => limited(policy, window, None),
+        false => {
+            let response = next.run(request).await;
+            limited(policy, window, Some(response))
+        }
+    }
+}
+
+// adds the RateLimit-* headers to the response, or answers 429 when there's none
+fn limited(policy: RateLimitPolicy, window: WindowState, response: Option<Response>) -> Response {
+    let mut response = match response {
+        Some(response) => response,
+        None if policy.openai_errors => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(json!({
+                "error": {
+                    "message": "rate.limit.exceeded",
+                    "type": "rate_limit_error",
+                    "code": "rate.limit.exceeded",
+                }
+            })),
+        ).into_response(),
+        None => too_many_requests("rate.limit.exceeded", None).into_response(),
+    };
+
+    let headers = response.headers_mut();
+    insert_header(headers, "RateLimit-Limit", window.limit);
+    insert_header(headers, "RateLimit-Remaining", window.remaining);
+    insert_header(headers, "RateLimit-Reset", window.reset);
+    if window.exceeded {
+        insert_header(headers, "Retry-After", window.reset.max(1));
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_callers_and_anonymous_requests() {
+        let org = Some(Caller { plan: Slug::PRO, org_id: Some(String::from("org")) });
+        let cached = Caller::from_cache(&Caller::to_cache(&org)).unwrap();
+        assert_eq!(cached.plan.to_string(), "pro");
+        assert_eq!(cached.org_id.as_deref(), Some("org"));
+
+        let customer = Some(Caller { plan: Slug::FREE, org_id: None });
+        let cached = Caller::from_cache(&Caller::to_cache(&customer)).unwrap();
+        assert_eq!(cached.plan.to_string(), "free");
+        assert_eq!(cached.org_id, None);
+
+        assert!(Caller::from_cache(&Caller::to_cache(&None)).is_none());
+    }
+
+    #[test]
+    fn trusts_only_the_proxy_hop_of_forwarded_for() {
+        let request = Request::builder()
+            .header("X-Forwarded-For", "1.1.1.1, 10.0.0.1, 203.0.113.7")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(client_ip(request.headers(), &request, true), "203.0.113.7");
+
+        let request = Request::builder()
+            .header("X-Forwarded-For", "1.1.1.1")
+            .header("Fly-Client-IP", "198.51.100.2")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(client_ip(request.headers(), &request, true), "198.51.100.2");
+    }
+
+    #[test]
+    fn ignores_proxy_headers_unless_trusted() {
+        let mut request = Request::builder()
+            .header("X-Forwarded-For", "1.1.1.1")
+            .header("Fly-Client-IP", "198.51.100.2")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 10], 4000))));
+
+        assert_eq!(client_ip(request.headers(), &request, false), "192.0.2.10");
+    }
+
+    #[test]
+    fn access_tokens_are_cached_apart_from_the_org_header() {
+        let org_id = String::from("org");
+        assert_eq!(caller_cache_key(Some(&org_id), "token"), caller_cache_key(None, "token"));
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures_util::StreamExt;
+use log::{error, info};
+use mongodb::{bson::doc, options::IndexOptions, Database, IndexModel};
+use rand::{distributions::Alphanumeric, Rng};
+use regex::Regex;
+
+use crate::{
+    storage::mongo::{get_organizations_collection, get_request_logs_collection},
+    types::{
+        request_log::{RequestLogEntry, RequestLogSettings},
+        state::AppState,
+    },
+};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+// masks what usually identifies a person or leaks a credential, the rest of the
+// prompt stays searchable
+static REDACTIONS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    vec![
+        (Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(), "[email]"),
+        (Regex::new(r"\b(?:sk|pk|rk|key|token)[-_][A-Za-z0-9_-]{12,}\b").unwrap(), "[secret]"),
+        (Regex::new(r"\+?\d[\d\s().-]{6,}\d").unwrap(), "[number]"),
+    ]
+});
+
+pub fn redact_prompt(prompt: &str) -> String {
+    let mut redacted = prompt.to_string();
+    for (regex, replacement) in REDACTIONS.iter() {
+        redacted = regex.replace_all(&redacted, *replacement).to_string();
+    }
+
+    redacted
+}
+
+// searches filter by org and page on (timestamp, id), newest first
+pub async fn create_request_log_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
+    let collection = get_request_logs_collection(db).await;
+    let index = IndexModel::builder()
+        .keys(doc! {"org_id": 1, "timestamp": -1, "id": -1})
+        .options(IndexOptions::builder().name(String::from("org_id_timestamp_id")).build())
+        .build();
+
+    collection.create_index(index, None).await?;
+    Ok(())
+}
+
+// sampled and written in the background, the routing response never waits on it,
+// the entry id is assigned here
+pub fn log_request(state: &AppState, settings: &RequestLogSettings, mut entry: RequestLogEntry) {
+    if !settings.enabled || settings.sample_rate <= 0.0 {
+        return;
+    }
+
+    if settings.sample_rate < 1.0 && rand::thread_rng().gen::<f64>() >= settings.sample_rate {
+        return;
+    }
+
+    entry.id = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+
+    if settings.redact_prompts {
+        entry.prompt = redact_prompt(&entry.prompt);
+        entry.prompt_redacted = true;
+    }
+
+    let db = state.mongo_db.clone();
+    tokio::spawn(async move {
+        let collection = get_request_logs_collection(&db).await;
+        if let Err(e) = collection.insert_one(entry, None).await {
+            error!("failed to store request log: {}", e);
+        }
+    });
+}
+
+// deletes entries older than their org's retention, entries of deleted orgs go at once
+pub async fn purge_request_logs(state: &AppState) -> Result<u64, mongodb::error::Error> {
+    let organizations = get_organizations_collection(&state.mongo_db).await;
+    let request_logs = get_request_logs_collection(&state.mongo_db).await;
+    let now = Utc::now().timestamp_millis();
+    let mut purged = 0;
+
+    let mut cursor = organizations.find(doc! {}, None).await?;
+    while let Some(org) = cursor.next().await {
+        let org = org?;
+        let filter = match org.deleted {
+            true => doc! {"org_id": &org.id},
+            false => doc! {
+                "org_id": &org.id,
+                "timestamp": {"$lt": now - org.request_log.retention_days as i64 * DAY_MILLIS},
+            },
+        };
+
+        purged += request_logs.delete_many(filter, None).await?.deleted_count;
+    }
+
+    Ok(purged)
+}
+
+pub fn start_request_log_purge(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match purge_request_logs(&state).await {
+                Ok(0) => (),
+                Ok(purged) => info!("purged {} request logs past their retention", purged),
+                Err(e) => error!("request log purge failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_emails_secrets_and_numbers() {
+        let redacted = redact_prompt("mail jane@example.com with sk-abcdefghijklmnop or call +1 (555) 010-2030");
+        assert_eq!(redacted, "mail [email] with [secret] or call [number]");
+    }
+}
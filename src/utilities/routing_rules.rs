@@ -0,0 +1,172 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use log::{error, warn};
+use redis::{AsyncCommands, RedisResult};
+use tokio::task;
+
+use crate::{
+    storage::diesel_postgres::usage::find_org_spend,
+    types::{
+        router::{Router, RoutingRule, RuleConditions},
+        state::AppState,
+    },
+};
+
+// spend conditions may lag the ledger by this long
+const SPEND_CACHE_TTL_SECS: u64 = 60;
+
+// weekday from monday and hour of now in the rule's time zone, daylight saving
+// included. None when the zone or the legacy offset is invalid
+fn local_time(conditions: &RuleConditions, now: DateTime<Utc>) -> Option<(u32, u32)> {
+    if !conditions.timezone.is_empty() {
+        let zone = conditions.timezone.parse::<Tz>().ok()?;
+        let local = now.with_timezone(&zone);
+        return Some((local.weekday().num_days_from_monday(), local.hour()));
+    }
+
+    let local = now.with_timezone(&FixedOffset::east_opt(conditions.utc_offset_minutes * 60)?);
+    Some((local.weekday().num_days_from_monday(), local.hour()))
+}
+
+fn in_time_window(conditions: &RuleConditions, (weekday, hour): (u32, u32)) -> bool {
+    if !conditions.weekdays.is_empty() && !conditions.weekdays.contains(&weekday) {
+        return false;
+    }
+
+    match (conditions.start_hour, conditions.end_hour) {
+        (Some(start), Some(end)) if start <= end => hour >= start && hour < end,
+        (Some(start), Some(end)) => hour >= start || hour < end,
+        (Some(start), None) => hour >= start,
+        (None, Some(end)) => hour < end,
+        (None, None) => true,
+    }
+}
+
+fn month_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    match Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0).single() {
+        Some(start) => start,
+        None => now - Duration::days(31),
+    }
+}
+
+// cached in redis, every routing call of a busy org would hit postgres otherwise
+async fn current_month_spend(state: &AppState, org_id: &str) -> Option<f64> {
+    let pool = match &state.postgres_conn {
+        Some(pool) => pool.clone(),
+        None => return None,
+    };
+
+    let now = Utc::now();
+    let cache_key = format!("routing_rules:spend:{}:{}{:02}", org_id, now.year(), now.month());
+    let mut redis_conn = state.redis().await.ok();
+    if let Some(conn) = redis_conn.as_mut() {
+        if let Ok(spend) = conn.get::<_, f64>(&cache_key).await {
+            return Some(spend);
+        }
+    }
+
+    let org = org_id.to_string();
+    let models = match task::spawn_blocking(move || find_org_spend(&pool, &org, month_start(now))).await {
+        Ok(Ok(models)) => models,
+        Ok(Err(e)) => {
+            error!("failed to load spend of org {}: {}", org_id, e);
+            return None;
+        }
+        Err(_) => return None,
+    };
+
+    let spend: f64 = models
+        .iter()
+        .map(|model| state.model_catalog.cost(&model.model_id, model.prompt_tokens as u64, model.completion_tokens as u64).unwrap_or(0.0))
+        .sum();
+
+    if let Some(conn) = redis_conn.as_mut() {
+        let _: RedisResult<()> = conn.set_ex(&cache_key, spend, SPEND_CACHE_TTL_SECS).await;
+    }
+
+    Some(spend)
+}
+
+// the first rule of the router whose conditions hold, spend is only loaded when
+// a rule needs it and conditions on an unknown spend don't hold
+pub async fn matching_rule<'a>(state: &AppState, org_id: &str, router: &'a Router) -> Option<&'a RoutingRule> {
+    if router.rules.is_empty() {
+        return None;
+    }
+
+    let now = Utc::now();
+    let mut spend: Option<Option<f64>> = None;
+
+    for rule in &router.rules {
+        let local = match local_time(&rule.conditions, now) {
+            Some(local) => local,
+            None => {
+                warn!("routing rule {} of router {} has an invalid time zone", rule.name, router.id);
+                continue;
+            }
+        };
+
+        if !in_time_window(&rule.conditions, local) {
+            continue;
+        }
+
+        if let Some(min_monthly_spend) = rule.conditions.min_monthly_spend {
+            if spend.is_none() {
+                spend = Some(current_month_spend(state, org_id).await);
+            }
+
+            match spend.flatten() {
+                Some(spend) if spend >= min_monthly_spend => (),
+                _ => continue,
+            }
+        }
+
+        return Some(rule);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(timezone: &str, utc_offset_minutes: i32) -> RuleConditions {
+        RuleConditions {
+            timezone: timezone.to_string(),
+            utc_offset_minutes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reads_local_time_in_the_zone_across_daylight_saving() {
+        let new_york = conditions("America/New_York", 0);
+        let summer = Utc.with_ymd_and_hms(2024, 7, 1, 13, 0, 0).unwrap();
+        let winter = Utc.with_ymd_and_hms(2024, 1, 15, 13, 0, 0).unwrap();
+
+        assert_eq!(local_time(&new_york, summer), Some((0, 9)));
+        assert_eq!(local_time(&new_york, winter), Some((0, 8)));
+    }
+
+    #[test]
+    fn falls_back_to_the_legacy_offset_without_a_zone() {
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 1, 0, 0).unwrap();
+
+        assert_eq!(local_time(&conditions("", -300), now), Some((6, 20)));
+        assert_eq!(local_time(&conditions("Mars/Olympus", 0), now), None);
+    }
+
+    #[test]
+    fn hour_windows_wrap_past_midnight() {
+        let night = RuleConditions {
+            start_hour: Some(22),
+            end_hour: Some(6),
+            ..Default::default()
+        };
+
+        assert!(in_time_window(&night, (0, 23)));
+        assert!(in_time_window(&night, (0, 3)));
+        assert!(!in_time_window(&night, (0, 12)));
+    }
+}
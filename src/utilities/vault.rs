@@ -0,0 +1,172 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+
+use crate::types::organization::EncryptedSecret;
+
+// CREDENTIALS_ENCRYPTION_KEY is 32 bytes, hex encoded
+pub fn parse_encryption_key(hex_key: &str) -> Result<[u8; 32], String> {
+    let bytes = match hex::decode(hex_key.trim()) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(String::from("encryption key must be hex encoded")),
+    };
+
+    match bytes.try_into() {
+        Ok(key) => Ok(key),
+        Err(_) => Err(String::from("encryption key must be 32 bytes")),
+    }
+}
+
+// every secret gets its own data key, only the data key is encrypted with the
+// configured key, so rotating it means re-wrapping data keys, not api keys
+pub fn encrypt_secret(encryption_key: &[u8; 32], secret: &str) -> Result<EncryptedSecret, String> {
+    let data_key = Aes256Gcm::generate_key(OsRng);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = match Aes256Gcm::new(&data_key).encrypt(&nonce, secret.as_bytes()) {
+        Ok(ciphertext) => ciphertext,
+        Err(_) => return Err(String::from("error encrypting secret")),
+    };
+
+    let key_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(encryption_key));
+    let data_key_nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let wrapped_data_key = match key_cipher.encrypt(&data_key_nonce, data_key.as_slice()) {
+        Ok(wrapped_data_key) => wrapped_data_key,
+        Err(_) => return Err(String::from("error encrypting data key")),
+    };
+
+    Ok(EncryptedSecret {
+        ciphertext: hex::encode(ciphertext),
+        nonce: hex::encode(nonce),
+        wrapped_data_key: hex::encode(wrapped_data_key),
+        data_key_nonce: hex::encode(data_key_nonce),
+    })
+}
+
+fn decode_nonce(value: &str) -> Result<Vec<u8>, String> {
+    match hex::decode(value) {
+        Ok(nonce) if nonce.len() == 12 => Ok(nonce),
+        _ => Err(String::from("invalid nonce")),
+    }
+}
+
+pub fn decrypt_secret(encryption_key: &[u8; 32], secret: &EncryptedSecret) -> Result<String, String> {
+    let wrapped_data_key = hex::decode(&secret.wrapped_data_key).map_err(|_| String::from("invalid data key"))?;
+    let data_key_nonce = decode_nonce(&secret.data_key_nonce)?;
+    let ciphertext = hex::decode(&secret.ciphertext).map_err(|_| String::from("invalid ciphertext"))?;
+    let nonce = decode_nonce(&secret.nonce)?;
+
+    let key_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(encryption_key));
+    let data_key = match key_cipher.decrypt(Nonce::from_slice(&data_key_nonce), wrapped_data_key.as_slice()) {
+        Ok(data_key) if data_key.len() == 32 => data_key,
+        _ => return Err(String::from("error decrypting data key")),
+    };
+
+    let plaintext = match Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key)).decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice()) {
+        Ok(plaintext) => plaintext,
+        Err(_) => return Err(String::from("error decrypting secret")),
+    };
+
+    match String::from_utf8(plaintext) {
+        Ok(secret) => Ok(secret),
+        Err(_) => Err(String::from("secret is not valid utf-8")),
+    }
+}
+
+// keeps the prefix and the last 4 characters, enough to tell keys apart
+pub fn mask_secret(secret: &str) -> String {
+    let characters: Vec<char> = secret.chars().collect();
+    if characters.len() <= 12 {
+        return String::from("****");
+    }
+
+    let prefix: String = characters[..3].iter().collect();
+    let suffix: String = characters[characters.len() - 4..].iter().collect();
+    format!("{}...{}", prefix, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    // flips the last bit of a hex encoded value
+    fn tamper(value: &str) -> String {
+        let mut bytes = hex::decode(value).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        hex::encode(bytes)
+    }
+
+    #[test]
+    fn round_trips_a_secret() {
+        let encrypted = encrypt_secret(&KEY, "sk-test-1234567890").unwrap();
+
+        assert_ne!(encrypted.ciphertext, hex::encode("sk-test-1234567890"));
+        assert_eq!(decrypt_secret(&KEY, &encrypted).unwrap(), "sk-test-1234567890");
+    }
+
+    #[test]
+    fn uses_a_new_data_key_and_nonce_every_time() {
+        let first = encrypt_secret(&KEY, "sk-test").unwrap();
+        let second = encrypt_secret(&KEY, "sk-test").unwrap();
+
+        assert_ne!(first.ciphertext, second.ciphertext);
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.wrapped_data_key, second.wrapped_data_key);
+    }
+
+    #[test]
+    fn rejects_tampered_values() {
+        let encrypted = encrypt_secret(&KEY, "sk-test").unwrap();
+
+        let mut ciphertext = encrypted.clone();
+        ciphertext.ciphertext = tamper(&encrypted.ciphertext);
+        assert!(decrypt_secret(&KEY, &ciphertext).is_err());
+
+        let mut nonce = encrypted.clone();
+        nonce.nonce = tamper(&encrypted.nonce);
+        assert!(decrypt_secret(&KEY, &nonce).is_err());
+
+        let mut wrapped_data_key = encrypted.clone();
+        wrapped_data_key.wrapped_data_key = tamper(&encrypted.wrapped_data_key);
+        assert!(decrypt_secret(&KEY, &wrapped_data_key).is_err());
+
+        let mut data_key_nonce = encrypted.clone();
+        data_key_nonce.data_key_nonce = tamper(&encrypted.data_key_nonce);
+        assert!(decrypt_secret(&KEY, &data_key_nonce).is_err());
+
+        let mut short_nonce = encrypted.clone();
+        short_nonce.nonce = String::from("00ff");
+        assert_eq!(decrypt_secret(&KEY, &short_nonce), Err(String::from("invalid nonce")));
+    }
+
+    #[test]
+    fn rejects_the_wrong_key() {
+        let encrypted = encrypt_secret(&KEY, "sk-test").unwrap();
+
+        assert_eq!(decrypt_secret(&[8; 32], &encrypted), Err(String::from("error decrypting data key")));
+    }
+
+    #[test]
+    fn parses_hex_keys_of_32_bytes() {
+        assert_eq!(parse_encryption_key(&hex::encode(KEY)), Ok(KEY));
+        assert_eq!(parse_encryption_key(&format!(" {}\n", hex::encode(KEY))), Ok(KEY));
+    }
+
+    #[test]
+    fn rejects_keys_with_the_wrong_length_or_format() {
+        assert_eq!(parse_encryption_key(&hex::encode([7u8; 16])), Err(String::from("encryption key must be 32 bytes")));
+        assert_eq!(parse_encryption_key(&hex::encode([7u8; 33])), Err(String::from("encryption key must be 32 bytes")));
+        assert_eq!(parse_encryption_key(""), Err(String::from("encryption key must be 32 bytes")));
+        assert_eq!(parse_encryption_key(&"zz".repeat(32)), Err(String::from("encryption key must be hex encoded")));
+        assert_eq!(parse_encryption_key("abc"), Err(String::from("encryption key must be hex encoded")));
+    }
+
+    #[test]
+    fn masks_secrets() {
+        assert_eq!(mask_secret("sk-abcdefghijklmnop"), "sk-...mnop");
+        assert_eq!(mask_secret("short"), "****");
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use futures_util::StreamExt;
+use log::{error, warn};
+use mongodb::{
+    bson::doc,
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    Database, IndexModel,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::{
+    storage::mongo::{build_organizations_filter, find_organization, get_organizations_collection, get_webhook_deliveries_collection},
+    types::{
+        organization::Organization,
+        state::AppState,
+        webhook::{DeliveryStatus, Webhook, WebhookDelivery, WebhookEvent},
+    },
+};
+
+use super::vault::{decrypt_secret, encrypt_secret, mask_secret};
+
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+// how long an attempt keeps its delivery claimed, longer than the request can take
+const DELIVERY_LEASE: Duration = Duration::from_secs(60);
+const RETRY_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+// receivers check X-Signature against hmac-sha256("{timestamp}.{body}") with
+// their secret, the timestamp lets them reject replayed deliveries
+pub fn sign_payload(secret: &str, timestamp: i64, payload: &str) -> Option<String> {
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+        Ok(mac) => mac,
+        Err(_) => return None,
+    };
+
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    Some(hex::encode(mac.finalize().into_bytes()))
+}
+
+fn random_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+pub fn new_delivery(org_id: &str, webhook_id: &str, event: WebhookEvent, payload: String, redelivery_of: Option<String>) -> WebhookDelivery {
+    let now = Utc::now();
+    WebhookDelivery {
+        id: random_id(),
+        org_id: org_id.to_string(),
+        webhook_id: webhook_id.to_string(),
+        event,
+        payload,
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        next_attempt_at: None,
+        response_status: None,
+        error: None,
+        created_at: now.to_rfc3339(),
+        timestamp: now.timestamp_millis(),
+        last_attempt_at: None,
+        redelivery_of,
+    }
+}
+
+// loopback, private, link-local (cloud metadata at 169.254.169.254), unique
+// local and other addresses that aren't reachable from the internet
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // shared address space of carrier grade nat
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(ip));
+            }
+
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// the address the webhook host resolves to, every address it resolves to must
+// be public. Deliveries connect to the returned address so a host can't
+// resolve to a public address here and to an internal one when connecting
+pub async fn resolve_webhook_host(url: &reqwest::Url) -> Result<SocketAddr, &'static str> {
+    // ipv6 hosts come in brackets
+    let host = match url.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return Err("webhook.url.invalid"),
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let addresses: Vec<SocketAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(addresses) => addresses.collect(),
+        Err(_) => return Err("webhook.url.unresolvable"),
+    };
+
+    if addresses.is_empty() {
+        return Err("webhook.url.unresolvable");
+    }
+
+    if addresses.iter().any(|address| !is_public_address(address.ip())) {
+        return Err("webhook.url.address.forbidden");
+    }
+
+    Ok(addresses[0])
+}
+
+// 2s, 4s, 8s, 16s between attempts
+fn backoff_delay(attempt: u32) -> Duration {
+    RETRY_BACKOFF * 2u32.pow(attempt.saturating_sub(1))
+}
+
+// timeouts, rate limits and server errors are worth another try, any other
+// client error would fail the same way again
+fn is_retriable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::REQUEST_TIMEOUT || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+// pinned to the checked address and without redirects, a redirect could point
+// anywhere
+fn delivery_client(host: &str, address: SocketAddr) -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .resolve(host, address)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+async fn save_attempt(db: &Database, delivery: &WebhookDelivery) {
+    let collection = get_webhook_deliveries_collection(db).await;
+    let update = doc! {"$set": {
+            "status": delivery.status.clone(),
+            "attempts": delivery.attempts as i64,
+            "next_attempt_at": delivery.next_attempt_at,
+            "response_status": delivery.response_status.map(|status| status as i32),
+            "error": delivery.error.clone(),
+            "last_attempt_at": delivery.last_attempt_at.clone(),
+        }
+    };
+
+    if let Err(e) = collection.update_one(doc! {"id": &delivery.id}, update, None).await {
+        error!("failed to update webhook delivery {}: {}", delivery.id, e);
+    }
+}
+
+fn lease_until() -> i64 {
+    Utc::now().timestamp_millis() + DELIVERY_LEASE.as_millis() as i64
+}
+
+pub fn webhook_secret(encryption_key: Option<&[u8; 32]>, webhook: &Webhook) -> Result<String, String> {
+    match (&webhook.encrypted_secret, &webhook.secret) {
+        (Some(encrypted_secret), _) => match encryption_key {
+            Some(encryption_key) => decrypt_secret(encryption_key, encrypted_secret),
+            None => Err(String::from("credentials.vault.disabled")),
+        },
+        (None, Some(secret)) => Ok(secret.clone()),
+        (None, None) => Err(String::from("webhook.secret.missing")),
+    }
+}
+
+fn fail(delivery: &mut WebhookDelivery, error: String) {
+    delivery.status = DeliveryStatus::Failed;
+    delivery.next_attempt_at = None;
+    delivery.error = Some(error);
+}
+
+// one attempt, a retriable failure leaves the delivery pending until its backoff
+// has passed
+async fn attempt(db: &Database, encryption_key: Option<[u8; 32]>, webhook: &Webhook, delivery: &mut WebhookDelivery) {
+    let secret = match webhook_secret(encryption_key.as_ref(), webhook) {
+        Ok(secret) => secret,
+        Err(e) => {
+            fail(delivery, e);
+            save_attempt(db, delivery).await;
+            return;
+        }
+    };
+
+    let timestamp = Utc::now().timestamp();
+    let signature = match sign_payload(&secret, timestamp, &delivery.payload) {
+        Some(signature) => signature,
+        None => {
+            fail(delivery, String::from("signature.error"));
+            save_attempt(db, delivery).await;
+            return;
+        }
+    };
+
+    // resolved again on every attempt, the host may have changed since registration
+    let url = match reqwest::Url::parse(&webhook.url) {
+        Ok(url) => url,
+        Err(_) => {
+            fail(delivery, String::from("webhook.url.invalid"));
+            save_attempt(db, delivery).await;
+            return;
+        }
+    };
+
+    let client = match resolve_webhook_host(&url).await {
+        Ok(address) => delivery_client(url.host_str().unwrap_or_default(), address),
+        Err(e) => Err(e.to_string()),
+    };
+
+    delivery.attempts += 1;
+    delivery.last_attempt_at = Some(Utc::now().to_rfc3339());
+
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("webhook delivery {} to {} blocked: {}", delivery.id, webhook.url, e);
+            fail(delivery, e);
+            save_attempt(db, delivery).await;
+            return;
+        }
+    };
+
+    let result = client
+        .post(url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Id", &webhook.id)
+        .header("X-Webhook-Delivery", &delivery.id)
+        .header("X-Webhook-Event", delivery.event.as_str())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Signature", signature)
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    let retriable = match result {
+        Ok(response) if response.status().is_success() => {
+            delivery.status = DeliveryStatus::Delivered;
+            delivery.next_attempt_at = None;
+            delivery.response_status = Some(response.status().as_u16());
+            delivery.error = None;
+            save_attempt(db, delivery).await;
+            return;
+        }
+        Ok(response) => {
+            delivery.response_status = Some(response.status().as_u16());
+            delivery.error = Some(format!("unexpected status {}", response.status().as_u16()));
+            is_retriable(response.status())
+        }
+        Err(e) => {
+            delivery.response_status = None;
+            delivery.error = Some(e.to_string());
+            true
+        }
+    };
+
+    match retriable && delivery.attempts < MAX_DELIVERY_ATTEMPTS {
+        true => {
+            delivery.status = DeliveryStatus::Pending;
+            delivery.next_attempt_at = Some(Utc::now().timestamp_millis() + backoff_delay(delivery.attempts).as_millis() as i64);
+        }
+        false => {
+            delivery.status = DeliveryStatus::Failed;
+            delivery.next_attempt_at = None;
+            warn!("webhook delivery {} to {} failed after {} attempts", delivery.id, webhook.url, delivery.attempts);
+        }
+    }
+
+    save_attempt(db, delivery).await;
+}
+
+// stores the delivery and makes the first attempt in the background, retries
+// are picked up from webhook_deliveries by start_webhook_retries
+pub fn dispatch(state: &AppState, webhook: Webhook, mut delivery: WebhookDelivery) {
+    let db = state.mongo_db.clone();
+    let encryption_key = state.credentials_encryption_key;
+    delivery.next_attempt_at = Some(lease_until());
+
+    tokio::spawn(async move {
+        let collection = get_webhook_deliveries_collection(&db).await;
+        if let Err(e) = collection.insert_one(delivery.clone(), None).await {
+            error!("failed to store webhook delivery {}: {}", delivery.id, e);
+            return;
+        }
+
+        attempt(&db, encryption_key, &webhook, &mut delivery).await;
+    });
+}
+
+// claims every due delivery, the lease keeps other instances and the next scan
+// off it while it's being sent
+pub async fn retry_pending_deliveries(state: &AppState) -> Result<u64, mongodb::error::Error> {
+    let collection = get_webhook_deliveries_collection(&state.mongo_db).await;
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    let mut claimed = 0;
+
+    loop {
+        let filter = doc! {"status": DeliveryStatus::Pending, "next_attempt_at": {"$lte": Utc::now().timestamp_millis()}};
+        let update = doc! {"$set": {"next_attempt_at": lease_until()}};
+        let mut delivery = match collection.find_one_and_update(filter, update, options.clone()).await? {
+            Some(delivery) => delivery,
+            None => return Ok(claimed),
+        };
+        claimed += 1;
+
+        let filter = build_organizations_filter(&delivery.org_id).await;
+        let webhook = match find_organization(&state.mongo_db, filter).await {
+            Ok(org) => org.webhooks.into_iter().find(|webhook| webhook.id == delivery.webhook_id),
+            Err(_) => None,
+        };
+
+        let webhook = match webhook {
+            Some(webhook) => webhook,
+            None => {
+                fail(&mut delivery, String::from("webhook.not.found"));
+                save_attempt(&state.mongo_db, &delivery).await;
+                continue;
+            }
+        };
+
+        let db = state.mongo_db.clone();
+        let encryption_key = state.credentials_encryption_key;
+        tokio::spawn(async move {
+            attempt(&db, encryption_key, &webhook, &mut delivery).await;
+        });
+    }
+}
+
+pub fn start_webhook_retries(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(RETRY_SCAN_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = retry_pending_deliveries(&state).await {
+                error!("webhook retry scan failed: {}", e);
+            }
+        }
+    });
+}
+
+// moves plain secrets of webhooks created before the vault into it and indexes
+// the retry scan
+pub async fn migrate_webhooks(state: &AppState) -> Result<(), mongodb::error::Error> {
+    let organizations = get_organizations_collection(&state.mongo_db).await;
+
+    if let Some(encryption_key) = &state.credentials_encryption_key {
+        let mut cursor = organizations.find(doc! {"webhooks.secret": {"$exists": true}}, None).await?;
+        while let Some(org) = cursor.next().await {
+            let org = org?;
+            for webhook in &org.webhooks {
+                let secret = match (&webhook.secret, &webhook.encrypted_secret) {
+                    (Some(secret), None) => secret,
+                    _ => continue,
+                };
+
+                let encrypted_secret = match encrypt_secret(encryption_key, secret) {
+                    Ok(encrypted_secret) => encrypted_secret,
+                    Err(e) => {
+                        error!("failed to encrypt secret of webhook {}: {}", webhook.id, e);
+                        continue;
+                    }
+                };
+
+                let update = doc! {
+                    "$set": {
+                        "webhooks.$.encrypted_secret": encrypted_secret,
+                        "webhooks.$.masked_secret": mask_secret(secret),
+                    },
+                    "$unset": {"webhooks.$.secret": ""},
+                };
+                organizations.update_one(doc! {"id": &org.id, "webhooks.id": &webhook.id}, update, None).await?;
+            }
+        }
+    }
+
+    let index = IndexModel::builder().keys(doc! {"status": 1, "next_attempt_at": 1}).build();
+    get_webhook_deliveries_collection(&state.mongo_db).await.create_index(index, None).await?;
+
+    Ok(())
+}
+
+// delivers the event to every webhook of the org subscribed to it, the event id
+// in the body is shared by those deliveries and kept on redelivery
+pub fn emit_event(state: &AppState, org: &Organization, event: WebhookEvent, data: Value) {
+    let payload = json!({
+        "id": random_id(),
+        "event": event.as_str(),
+        "org_id": org.id,
+        "created_at": Utc::now().to_rfc3339(),
+        "data": data,
+    })
+    .to_string();
+
+    for webhook in org.webhooks.iter().filter(|webhook| webhook.events.contains(&event)) {
+        let delivery = new_delivery(&org.id, &webhook.id, event, payload.clone(), None);
+        dispatch(state, webhook.clone(), delivery);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(encrypted_secret: Option<crate::types::organization::EncryptedSecret>, secret: Option<&str>) -> Webhook {
+        Webhook {
+            id: String::from("webhook"),
+            url: String::from("https://example.com/hook"),
+            events: vec![WebhookEvent::ModelAdded],
+            encrypted_secret,
+            masked_secret: String::new(),
+            secret: secret.map(String::from),
+            created_by: String::from("customer"),
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn signs_with_the_vault_secret_or_a_plain_one_not_yet_migrated() {
+        let key = [7u8; 32];
+        let encrypted = encrypt_secret(&key, "whsec_abc").unwrap();
+
+        assert_eq!(webhook_secret(Some(&key), &webhook(Some(encrypted.clone()), None)).unwrap(), "whsec_abc");
+        assert_eq!(webhook_secret(None, &webhook(Some(encrypted), None)).unwrap_err(), "credentials.vault.disabled");
+        assert_eq!(webhook_secret(None, &webhook(None, Some("whsec_old"))).unwrap(), "whsec_old");
+        assert_eq!(webhook_secret(Some(&key), &webhook(None, None)).unwrap_err(), "webhook.secret.missing");
+    }
+
+    #[test]
+    fn only_public_addresses_receive_deliveries() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fc00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public_address(ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public_address(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}